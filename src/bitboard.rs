@@ -0,0 +1,162 @@
+use crate::board_position::BoardIndex;
+use std::fmt;
+use std::fmt::Formatter;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+/// A set of board positions, one bit per position: bit `n` stands for the
+/// [`BoardIndex`] `n`, so A1 is the least significant bit and H8 the most significant.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const ALL: Bitboard = Bitboard(!0);
+
+    pub const FILE_A: Bitboard = Bitboard(0x0101_0101_0101_0101);
+    pub const FILE_H: Bitboard = Bitboard(0x8080_8080_8080_8080);
+    pub const RANK_1: Bitboard = Bitboard(0xFF);
+    pub const RANK_8: Bitboard = Bitboard(0xFF << 56);
+
+    pub const fn from_index(index: BoardIndex) -> Self {
+        Self(1 << index.get_pos())
+    }
+
+    /// file: between 1 and 8
+    pub const fn file(file: u8) -> Self {
+        Self(Self::FILE_A.0 << (file - 1))
+    }
+
+    /// rank: between 1 and 8
+    pub const fn rank(rank: u8) -> Self {
+        Self(Self::RANK_1.0 << (8 * (rank - 1)))
+    }
+
+    pub const fn contains(self, index: BoardIndex) -> bool {
+        self.0 & (1 << index.get_pos()) != 0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Whether more than one position is in the set.
+    pub const fn has_many(self) -> bool {
+        self.0 & self.0.wrapping_sub(1) != 0
+    }
+
+    /// The position with the lowest index in the set.
+    pub fn first(self) -> Option<BoardIndex> {
+        if self.is_empty() {
+            return None;
+        }
+
+        Some(unsafe { BoardIndex::new_unchecked(self.0.trailing_zeros() as u8) })
+    }
+
+    /// The position with the highest index in the set.
+    pub fn last(self) -> Option<BoardIndex> {
+        if self.is_empty() {
+            return None;
+        }
+
+        Some(unsafe { BoardIndex::new_unchecked(63 - self.0.leading_zeros() as u8) })
+    }
+
+    pub fn iter(self) -> BitboardIter {
+        BitboardIter(self.0)
+    }
+}
+
+impl IntoIterator for Bitboard {
+    type Item = BoardIndex;
+    type IntoIter = BitboardIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterates the positions of a [`Bitboard`], lowest index first.
+#[derive(Clone)]
+pub struct BitboardIter(u64);
+
+impl Iterator for BitboardIter {
+    type Item = BoardIndex;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+
+        let pos = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Some(unsafe { BoardIndex::new_unchecked(pos) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for BitboardIter {}
+
+impl FromIterator<BoardIndex> for Bitboard {
+    fn from_iter<T: IntoIterator<Item = BoardIndex>>(iter: T) -> Self {
+        iter.into_iter().fold(Bitboard::EMPTY, |bb, index| {
+            bb | Bitboard::from_index(index)
+        })
+    }
+}
+
+impl fmt::Debug for Bitboard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Bitboard({:#018x})", self.0)?;
+        for rank in (0..8).rev() {
+            for file in 0..8 {
+                let set = self.0 & (1 << (rank * 8 + file)) != 0;
+                write!(f, "{}", if set { 'x' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! bitboard_ops {
+    ($($trait:ident::$fn:ident, $assign_trait:ident::$assign_fn:ident => $op:tt;)*) => {
+        $(
+            impl $trait for Bitboard {
+                type Output = Bitboard;
+
+                fn $fn(self, rhs: Bitboard) -> Self::Output {
+                    Bitboard(self.0 $op rhs.0)
+                }
+            }
+
+            impl $assign_trait for Bitboard {
+                fn $assign_fn(&mut self, rhs: Bitboard) {
+                    self.0 = self.0 $op rhs.0;
+                }
+            }
+        )*
+    };
+}
+
+bitboard_ops!(
+    BitAnd::bitand, BitAndAssign::bitand_assign => &;
+    BitOr::bitor, BitOrAssign::bitor_assign => |;
+    BitXor::bitxor, BitXorAssign::bitxor_assign => ^;
+);
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Self::Output {
+        Bitboard(!self.0)
+    }
+}
@@ -1,15 +1,25 @@
+use crate::attacks;
+use crate::bitboard::Bitboard;
 use crate::board_position::BoardIndex;
 use crate::castle_rights::CastleRights;
 use crate::cell_buffer::{RankCellBuffer, WholeBoardCellBuffer};
 use crate::en_passant_target::EnPassantTarget;
 use crate::piece::{BoardPiece, BoardPieceKind, PieceColor};
-use crate::piece_move::{Move, MoveInfo};
+use crate::piece_move::{Move, MoveInfo, MoveSet};
+use crate::zobrist;
 use std::fmt;
 use std::fmt::Formatter;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Board {
+    /// What's on every position, for quick lookups by position.
     repr: WholeBoardCellBuffer,
+    /// Where the pieces of every kind are, indexed by `BoardPieceKind as usize - 1`.
+    by_kind: [Bitboard; 6],
+    /// Where the pieces of every color are, indexed by `PieceColor as usize`.
+    by_color: [Bitboard; 2],
+    /// The zobrist key of the pieces on the board, kept up to date as they move.
+    zobrist: u64,
 }
 
 impl fmt::Debug for Board {
@@ -23,6 +33,46 @@ impl Board {
         self.repr.get_piece(index)
     }
 
+    /// Puts `piece` at `index`, replacing whatever was there.
+    fn set_piece(&mut self, index: BoardIndex, piece: Option<BoardPiece>) {
+        let bit = Bitboard::from_index(index);
+
+        if let Some(old) = self.repr.get_piece(index) {
+            self.by_kind[old.kind() as usize - 1] ^= bit;
+            self.by_color[old.color() as usize] ^= bit;
+            self.zobrist ^= zobrist::piece_key(old, index);
+        }
+
+        if let Some(new) = piece {
+            self.by_kind[new.kind() as usize - 1] |= bit;
+            self.by_color[new.color() as usize] |= bit;
+            self.zobrist ^= zobrist::piece_key(new, index);
+        }
+
+        self.repr.set_piece(index, piece);
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.by_color[0] | self.by_color[1]
+    }
+
+    pub fn pieces_of_color(&self, color: PieceColor) -> Bitboard {
+        self.by_color[color as usize]
+    }
+
+    pub fn pieces_of_kind(&self, kind: BoardPieceKind) -> Bitboard {
+        self.by_kind[kind as usize - 1]
+    }
+
+    pub fn pieces(&self, piece: BoardPiece) -> Bitboard {
+        self.pieces_of_kind(piece.kind()) & self.pieces_of_color(piece.color())
+    }
+
+    /// The zobrist key of the piece placement alone, see [`crate::zobrist`].
+    pub fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
+
     fn parse_rank(fen: &str) -> Result<RankCellBuffer, ParseBoardError> {
         let mut cells = RankCellBuffer::init_empty();
         let mut idx = 0;
@@ -35,6 +85,10 @@ impl Board {
                     let piece = BoardPiece::try_from_fen_char(c)
                         .ok_or(ParseBoardError::InvalidFENPieceChar(c))?;
 
+                    if idx >= 8 {
+                        return Err(ParseBoardError::TooManyFilesInFEN(fen.to_string()));
+                    }
+
                     cells.set_piece(unsafe { BoardIndex::new_unchecked(idx) }, Some(piece));
                     idx += 1;
                 }
@@ -44,6 +98,9 @@ impl Board {
         if idx < 8 {
             return Err(ParseBoardError::MissingFilesInFEN(idx, fen.to_string()));
         }
+        if idx > 8 {
+            return Err(ParseBoardError::TooManyFilesInFEN(fen.to_string()));
+        }
 
         Ok(cells)
     }
@@ -51,6 +108,9 @@ impl Board {
     pub fn empty_board() -> Self {
         Self {
             repr: WholeBoardCellBuffer::init_empty(),
+            by_kind: [Bitboard::EMPTY; 6],
+            by_color: [Bitboard::EMPTY; 2],
+            zobrist: 0,
         }
     }
 
@@ -65,6 +125,15 @@ impl Board {
             init.repr.copy_rank_from(rank, &parsed_rank);
         }
 
+        for (idx, piece) in init.repr.iter_pieces() {
+            if let Some(piece) = piece {
+                let bit = Bitboard::from_index(idx);
+                init.by_kind[piece.kind() as usize - 1] |= bit;
+                init.by_color[piece.color() as usize] |= bit;
+                init.zobrist ^= zobrist::piece_key(piece, idx);
+            }
+        }
+
         Ok(init)
     }
 
@@ -103,9 +172,9 @@ impl Board {
     }
 
     pub fn piece_iterator<'a>(&'a self) -> impl Iterator<Item = (BoardIndex, BoardPiece)> + 'a {
-        self.repr
-            .iter_pieces()
-            .filter_map(|(idx, p)| p.map(|p| (idx, p)))
+        self.occupied()
+            .iter()
+            .map(|idx| (idx, self.repr.get_piece(idx).unwrap()))
     }
 
     pub fn all_possible_moves_for_turn<'a>(
@@ -114,45 +183,55 @@ impl Board {
         en_passant_target: Option<EnPassantTarget>,
         castle_rights: CastleRights,
     ) -> impl Iterator<Item = Move> + 'a {
-        self.piece_iterator()
-            .filter(move |(_, p)| p.color() == turn)
-            .flat_map(move |(idx, p)| {
-                p.moves_on_board(idx, self, en_passant_target, castle_rights)
-                    .into_iter()
-            })
+        let mut moves = Vec::with_capacity(64);
+        self.push_possible_moves(
+            turn,
+            en_passant_target,
+            castle_rights,
+            MoveSet::All,
+            &mut moves,
+        );
+        moves.into_iter()
+    }
+
+    /// Appends the moves in `set` for `turn` to `moves`, including ones that leave the
+    /// king in check.
+    pub fn push_possible_moves(
+        &self,
+        turn: PieceColor,
+        en_passant_target: Option<EnPassantTarget>,
+        castle_rights: CastleRights,
+        set: MoveSet,
+        moves: &mut Vec<Move>,
+    ) {
+        for idx in self.pieces_of_color(turn) {
+            let piece = self.repr.get_piece(idx).unwrap();
+            piece.push_move_set_on_board(idx, self, en_passant_target, castle_rights, set, moves);
+        }
     }
 
     pub fn board_after_move(&self, m: Move) -> (Self, MoveInfo) {
-        let mut new_board = Self { repr: self.repr };
+        let mut new_board = *self;
+        let move_info = new_board.make_move(m);
+        (new_board, move_info)
+    }
 
-        let simple_move = |new_board: &mut Board, start: BoardIndex, end: BoardIndex| {
-            let piece = new_board.repr.get_piece(start).unwrap();
-            let captured = new_board.repr.get_piece(end);
-            new_board.repr.set_piece(end, Some(piece));
-            new_board.repr.set_piece(start, None);
+    /// Plays `m` on this board. [`Board::unmake_move`] takes it back.
+    pub fn make_move(&mut self, m: Move) -> MoveInfo {
+        let simple_move = |board: &mut Board, start: BoardIndex, end: BoardIndex| {
+            let piece = board.get_piece_at(start).unwrap();
+            let captured = board.get_piece_at(end);
+            board.set_piece(end, Some(piece));
+            board.set_piece(start, None);
             MoveInfo {
                 captured,
                 moved_piece_color: piece.color(),
                 pawn_advanced: piece.kind() == BoardPieceKind::Pawn,
-                revoked_castle_rights: match piece {
-                    BoardPiece::WhiteRook => match start.get_pos() {
-                        0 => CastleRights::WHITE_QUEEN_SIDE,
-                        7 => CastleRights::WHITE_KING_SIDE,
-                        _ => CastleRights::EMPTY,
-                    },
-                    BoardPiece::BlackRook => match start.get_pos() {
-                        56 => CastleRights::BLACK_QUEEN_SIDE,
-                        63 => CastleRights::BLACK_KING_SIDE,
-                        _ => CastleRights::EMPTY,
-                    },
-                    BoardPiece::WhiteKing => {
-                        CastleRights::WHITE_KING_SIDE | CastleRights::WHITE_QUEEN_SIDE
-                    }
-                    BoardPiece::BlackKing => {
-                        CastleRights::BLACK_KING_SIDE | CastleRights::BLACK_QUEEN_SIDE
-                    }
-                    _ => CastleRights::EMPTY,
-                },
+                // anything leaving or landing on a king or rook home square
+                // (e.g. capturing a rook that never moved) revokes the
+                // corresponding rights.
+                revoked_castle_rights: Self::castle_rights_touched_by(start)
+                    | Self::castle_rights_touched_by(end),
                 new_en_passant_target: match piece {
                     BoardPiece::WhitePawn if end.get_pos() - start.get_pos() == 16 => {
                         Some(EnPassantTarget(unsafe {
@@ -166,27 +245,29 @@ impl Board {
                     }
                     _ => None,
                 },
+                promoted_to: None,
             }
         };
 
-        let move_info = match m {
-            Move::Simple(start, end) => simple_move(&mut new_board, start, end),
+        match m {
+            Move::Simple(start, end) => simple_move(self, start, end),
             Move::EnPassant {
                 en_passant_target,
                 pawn_doing_en_passant,
                 pawn_being_captured,
             } => {
-                let pawn = new_board.repr.get_piece(pawn_doing_en_passant).unwrap();
-                let captured_pawn = new_board.repr.get_piece(pawn_being_captured).unwrap();
-                new_board.repr.set_piece(pawn_doing_en_passant, None);
-                new_board.repr.set_piece(pawn_being_captured, None);
-                new_board.repr.set_piece(en_passant_target.0, Some(pawn));
+                let pawn = self.get_piece_at(pawn_doing_en_passant).unwrap();
+                let captured_pawn = self.get_piece_at(pawn_being_captured).unwrap();
+                self.set_piece(pawn_doing_en_passant, None);
+                self.set_piece(pawn_being_captured, None);
+                self.set_piece(en_passant_target.0, Some(pawn));
                 MoveInfo {
                     moved_piece_color: pawn.color(),
                     revoked_castle_rights: CastleRights::EMPTY,
                     captured: Some(captured_pawn),
                     pawn_advanced: true,
                     new_en_passant_target: None,
+                    promoted_to: None,
                 }
             }
             Move::Castle {
@@ -195,35 +276,206 @@ impl Board {
                 rook_from,
                 rook_to,
             } => {
-                let mi = simple_move(&mut new_board, king_from, king_to);
-                let mi2 = simple_move(&mut new_board, rook_from, rook_to);
+                let mi = simple_move(self, king_from, king_to);
+                let mi2 = simple_move(self, rook_from, rook_to);
 
                 mi.combine_composite(mi2)
             }
-        };
+            Move::Promotion {
+                from,
+                to,
+                promote_to,
+            } => {
+                let mi = simple_move(self, from, to);
+                let promoted = promote_to.of_color(mi.moved_piece_color);
+                self.set_piece(to, Some(promoted));
+                MoveInfo {
+                    promoted_to: Some(promoted),
+                    ..mi
+                }
+            }
+        }
+    }
 
-        (new_board, move_info)
+    /// Takes back `m`, which must have been the last move played on this board with
+    /// [`Board::make_move`]. `captured` is the piece it captured, as reported by
+    /// [`MoveInfo`].
+    pub fn unmake_move(&mut self, m: Move, captured: Option<BoardPiece>) {
+        match m {
+            Move::Simple(start, end) => {
+                self.set_piece(start, self.get_piece_at(end));
+                self.set_piece(end, captured);
+            }
+            Move::EnPassant {
+                en_passant_target,
+                pawn_doing_en_passant,
+                pawn_being_captured,
+            } => {
+                self.set_piece(
+                    pawn_doing_en_passant,
+                    self.get_piece_at(en_passant_target.0),
+                );
+                self.set_piece(en_passant_target.0, None);
+                self.set_piece(pawn_being_captured, captured);
+            }
+            Move::Castle {
+                king_from,
+                king_to,
+                rook_from,
+                rook_to,
+            } => {
+                let king = self.get_piece_at(king_to);
+                let rook = self.get_piece_at(rook_to);
+                self.set_piece(king_to, None);
+                self.set_piece(rook_to, None);
+                self.set_piece(king_from, king);
+                self.set_piece(rook_from, rook);
+            }
+            Move::Promotion { from, to, .. } => {
+                let color = self.get_piece_at(to).unwrap().color();
+                self.set_piece(from, Some(BoardPieceKind::Pawn.of_color(color)));
+                self.set_piece(to, captured);
+            }
+        }
+    }
+
+    fn castle_rights_touched_by(index: BoardIndex) -> CastleRights {
+        match index.get_pos() {
+            0 => CastleRights::WHITE_QUEEN_SIDE,
+            4 => CastleRights::WHITE_KING_SIDE | CastleRights::WHITE_QUEEN_SIDE,
+            7 => CastleRights::WHITE_KING_SIDE,
+            56 => CastleRights::BLACK_QUEEN_SIDE,
+            60 => CastleRights::BLACK_KING_SIDE | CastleRights::BLACK_QUEEN_SIDE,
+            63 => CastleRights::BLACK_KING_SIDE,
+            _ => CastleRights::EMPTY,
+        }
     }
 
-    pub fn check_move_validity(
+    /// The positions of all the pieces of color `by_color` attacking the square at
+    /// `index`, as if the occupied positions were `occupied`.
+    pub fn attackers_with_occupancy(
         &self,
-        turn: PieceColor,
-        m: Move,
-        en_passant_target: Option<EnPassantTarget>,
-        castle_rights: CastleRights,
-    ) -> bool {
-        let (new_board, _mi) = self.board_after_move(m);
-        let other_turn = turn.other();
-        for m in new_board.all_possible_moves_for_turn(other_turn, en_passant_target, castle_rights)
-        {
-            if let Move::Simple(_start, end) = m {
-                if new_board.get_piece_at(end) == Some(turn.king_of_color()) {
-                    return false;
-                }
+        index: BoardIndex,
+        by_color: PieceColor,
+        occupied: Bitboard,
+    ) -> Bitboard {
+        let queens = self.pieces_of_kind(BoardPieceKind::Queen);
+        let rook_likes = self.pieces_of_kind(BoardPieceKind::Rook) | queens;
+        let bishop_likes = self.pieces_of_kind(BoardPieceKind::Bishop) | queens;
+
+        // a pawn attacks us iff a pawn of the other color standing here would attack it.
+        let attackers = attacks::pawn_attacks(by_color.other(), index)
+            & self.pieces_of_kind(BoardPieceKind::Pawn)
+            | attacks::knight_attacks(index) & self.pieces_of_kind(BoardPieceKind::Knight)
+            | attacks::king_attacks(index) & self.pieces_of_kind(BoardPieceKind::King)
+            | attacks::rook_attacks(index, occupied) & rook_likes
+            | attacks::bishop_attacks(index, occupied) & bishop_likes;
+
+        attackers & self.pieces_of_color(by_color) & occupied
+    }
+
+    /// Whether `m` takes a piece.
+    pub fn is_capture(&self, m: Move) -> bool {
+        matches!(m, Move::EnPassant { .. })
+            || !matches!(m, Move::Castle { .. }) && self.get_piece_at(m.end()).is_some()
+    }
+
+    /// Static exchange evaluation: the material `m` wins, or loses if negative, once
+    /// all the captures that follow on its destination are played out, each side taking
+    /// with its least valuable piece and stopping whenever that's better.
+    pub fn see(&self, m: Move) -> i32 {
+        let (after, info) = self.board_after_move(m);
+
+        let mut gain = info.captured.map_or(0, |p| p.kind().value());
+        if let Some(promoted_to) = info.promoted_to {
+            gain += promoted_to.kind().value() - BoardPieceKind::Pawn.value();
+        }
+        let target = m.end();
+        let on_target = after
+            .get_piece_at(target)
+            .expect("the moved piece is on the target")
+            .kind()
+            .value();
+
+        gain - after.exchange(target, info.moved_piece_color.other(), on_target)
+    }
+
+    /// What `side` gains by starting a sequence of captures on `target`, where a piece
+    /// worth `on_target` stands; never negative, as `side` may just not capture.
+    fn exchange(&self, target: BoardIndex, mut side: PieceColor, mut on_target: i32) -> i32 {
+        const ORDER: [BoardPieceKind; 6] = [
+            BoardPieceKind::Pawn,
+            BoardPieceKind::Knight,
+            BoardPieceKind::Bishop,
+            BoardPieceKind::Rook,
+            BoardPieceKind::Queen,
+            BoardPieceKind::King,
+        ];
+
+        let mut occupied = self.occupied();
+        let mut gains = Vec::new();
+        loop {
+            // sliders behind the pieces already gone join in, thanks to `occupied`.
+            let attackers = self.attackers_with_occupancy(target, side, occupied);
+            let Some((kind, from)) = ORDER.iter().find_map(|&kind| {
+                let from = (attackers & self.pieces_of_kind(kind)).first()?;
+                Some((kind, from))
+            }) else {
+                break;
+            };
+
+            occupied ^= Bitboard::from_index(from);
+            // the king may only take if nothing can take it back.
+            if kind == BoardPieceKind::King
+                && !self
+                    .attackers_with_occupancy(target, side.other(), occupied)
+                    .is_empty()
+            {
+                break;
             }
+
+            gains.push(on_target);
+            on_target = kind.value();
+            side = side.other();
         }
 
-        true
+        gains
+            .into_iter()
+            .rev()
+            .fold(0, |reply, gain| (gain - reply).max(0))
+    }
+
+    /// The positions of all the pieces of color `by_color` attacking the square at `index`.
+    pub fn attackers(&self, index: BoardIndex, by_color: PieceColor) -> Bitboard {
+        self.attackers_with_occupancy(index, by_color, self.occupied())
+    }
+
+    /// Whether any piece of color `by_color` attacks the square at `index`.
+    pub fn is_square_attacked(&self, index: BoardIndex, by_color: PieceColor) -> bool {
+        !self.attackers(index, by_color).is_empty()
+    }
+
+    pub fn king_position(&self, color: PieceColor) -> Option<BoardIndex> {
+        self.pieces(color.king_of_color()).first()
+    }
+
+    /// Whether the king of color `color` is attacked. A board without such a king
+    /// is never in check.
+    pub fn is_in_check(&self, color: PieceColor) -> bool {
+        self.king_position(color)
+            .is_some_and(|k| self.is_square_attacked(k, color.other()))
+    }
+
+    /// The positions of all the pieces giving check to the king of color `color`.
+    pub fn checkers(&self, color: PieceColor) -> Bitboard {
+        self.king_position(color)
+            .map_or(Bitboard::EMPTY, |k| self.attackers(k, color.other()))
+    }
+
+    /// Whether playing `m` leaves the king of color `turn` out of check.
+    pub fn check_move_validity(&self, turn: PieceColor, m: Move) -> bool {
+        let (new_board, _mi) = self.board_after_move(m);
+        !new_board.is_in_check(turn)
     }
 
     pub fn all_legal_moves_for_turn<'a>(
@@ -233,7 +485,7 @@ impl Board {
         castle_rights: CastleRights,
     ) -> impl Iterator<Item = Move> + 'a {
         self.all_possible_moves_for_turn(turn, en_passant_target, castle_rights)
-            .filter(move |m| self.check_move_validity(turn, *m, en_passant_target, castle_rights))
+            .filter(move |m| self.check_move_validity(turn, *m))
     }
 
     pub fn to_visual(&self) -> BoardVisual {
@@ -281,6 +533,8 @@ pub enum ParseBoardError {
     MissingRank(u8),
     #[error("there were some missing files in the FEN string (got only {0} in {1:?})")]
     MissingFilesInFEN(u8, String),
+    #[error("there are more than 8 files in {0:?}")]
+    TooManyFilesInFEN(String),
     #[error("char {0} is an invalid FEN piece char")]
     InvalidFENPieceChar(char),
 }
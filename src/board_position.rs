@@ -57,6 +57,10 @@ impl BoardColumn {
     }
 
     /// index: between 1 and 8
+    ///
+    /// # Safety
+    ///
+    /// `index` must be between 1 and 8.
     pub unsafe fn from_index_unchecked(index: u8) -> Self {
         std::mem::transmute::<u8, Self>(index)
     }
@@ -111,12 +115,21 @@ impl BoardIndex {
         self.pos % 8 + 1
     }
 
-    pub fn get_pos(self) -> u8 {
+    pub const fn get_pos(self) -> u8 {
         self.pos
     }
+
+    /// The lowercase name of the position, as used by SAN and UCI, e.g. `e4`.
+    pub fn algebraic(self) -> String {
+        let file = (b'a' + self.file() - 1) as char;
+        format!("{file}{}", self.rank())
+    }
 }
 
 impl BoardIndex {
+    /// # Safety
+    ///
+    /// `pos` must be between 0 and 63.
     pub unsafe fn new_unchecked(pos: u8) -> Self {
         Self { pos }
     }
@@ -129,6 +142,9 @@ impl BoardIndex {
         Some(unsafe { Self::new_unchecked(pos) })
     }
 
+    /// # Safety
+    ///
+    /// The resulting position must still be on the board.
     pub unsafe fn unchecked_add(self, rhs: BoardIndexDelta) -> BoardIndex {
         Self::new_unchecked((self.pos as i8 + 8 * rhs.delta_rank + rhs.delta_file) as u8)
     }
@@ -184,6 +200,9 @@ impl Add<BoardIndexDelta> for BoardIndex {
 }
 
 impl BoardPosition {
+    /// # Safety
+    ///
+    /// `row` must be between 1 and 8.
     pub unsafe fn new_unchecked(row: u8, column: BoardColumn) -> Self {
         Self { row, column }
     }
@@ -0,0 +1,221 @@
+//! Opening books in the Polyglot `.bin` format: 16-byte big-endian entries of a
+//! position's Zobrist key, a move, its weight and learning data, sorted by key.
+
+use crate::board_position::BoardIndex;
+use crate::game_state::GameState;
+use crate::piece::BoardPieceKind;
+use crate::piece_move::Move;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+use std::str::FromStr;
+
+const ENTRY_SIZE: usize = 16;
+
+/// One entry of a book, as stored in the file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BookEntry {
+    pub key: u64,
+    /// The move, encoded as described at [`decode_move`].
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// A book move that is legal in the position it was looked up for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BookMove {
+    pub m: Move,
+    pub weight: u16,
+}
+
+/// How to choose among the book moves of a position.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Selection {
+    /// Always the move with the highest weight.
+    Best,
+    /// Each move with a chance proportional to its weight.
+    #[default]
+    WeightedRandom,
+    /// Any move with a weight, all equally likely.
+    Uniform,
+}
+
+impl Selection {
+    pub const ALL: [Selection; 3] = [Self::Best, Self::WeightedRandom, Self::Uniform];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Best => "Best",
+            Self::WeightedRandom => "WeightedRandom",
+            Self::Uniform => "Uniform",
+        }
+    }
+}
+
+impl FromStr for Selection {
+    type Err = ParseSelectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|selection| selection.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseSelectionError(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown book move selection {0:?}")]
+pub struct ParseSelectionError(String);
+
+#[derive(Debug, thiserror::Error)]
+pub enum BookError {
+    #[error("cannot read book: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("book size {0} is not a multiple of 16 bytes")]
+    Truncated(usize),
+}
+
+/// A Polyglot opening book, read into memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PolyglotBook {
+    /// Sorted by key.
+    entries: Vec<BookEntry>,
+}
+
+impl PolyglotBook {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BookError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError::Truncated(bytes.len()));
+        }
+        let mut entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| BookEntry::from_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        // books are sorted already, but a stable sort keeps their order of moves.
+        entries.sort_by_key(|entry| entry.key);
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries for the position with `key`, in the order of the book.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// The legal book moves with a weight in `state`, highest weight first.
+    pub fn moves(&self, state: &GameState) -> Vec<BookMove> {
+        let mut moves = self
+            .entries(state.zobrist())
+            .iter()
+            .filter(|entry| entry.weight > 0)
+            .filter_map(|entry| {
+                Some(BookMove {
+                    m: decode_move(state, entry.raw_move)?,
+                    weight: entry.weight,
+                })
+            })
+            .collect::<Vec<_>>();
+        moves.sort_by_key(|book_move| std::cmp::Reverse(book_move.weight));
+        moves
+    }
+
+    /// A book move to play in `state`, chosen according to `selection`.
+    pub fn pick(&self, state: &GameState, selection: Selection) -> Option<Move> {
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        self.pick_with(state, selection, random)
+    }
+
+    /// Like [`pick`](Self::pick), with the randomness to choose with given.
+    pub fn pick_with(&self, state: &GameState, selection: Selection, random: u64) -> Option<Move> {
+        let moves = self.moves(state);
+        let chosen = match selection {
+            Selection::Best => moves.first(),
+            Selection::Uniform if moves.is_empty() => None,
+            Selection::Uniform => moves.get((random % moves.len() as u64) as usize),
+            Selection::WeightedRandom => {
+                let total = moves.iter().map(|m| m.weight as u64).sum::<u64>();
+                let mut target = random.checked_rem(total)?;
+                moves.iter().find(|m| {
+                    let found = target < m.weight as u64;
+                    target = target.saturating_sub(m.weight as u64);
+                    found
+                })
+            }
+        };
+        chosen.map(|book_move| book_move.m)
+    }
+}
+
+/// Finds the legal move in `state` that a Polyglot move stands for. Its bits are, from
+/// the lowest: the file and rank of the destination, the file and rank of the start, and
+/// the promotion piece (1 for a knight up to 4 for a queen). Castling is written as the
+/// king taking its own rook.
+pub fn decode_move(state: &GameState, raw: u16) -> Option<Move> {
+    let square = |bits: u16| BoardIndex::new((bits & 0o77) as u8);
+    let to = square(raw)?;
+    let from = square(raw >> 6)?;
+    let promote_to = match (raw >> 12) & 0b111 {
+        0 => None,
+        1 => Some(BoardPieceKind::Knight),
+        2 => Some(BoardPieceKind::Bishop),
+        3 => Some(BoardPieceKind::Rook),
+        4 => Some(BoardPieceKind::Queen),
+        _ => return None,
+    };
+
+    state.legal_moves_from(from).find(|&m| match m {
+        Move::Castle { rook_from, .. } => to == rook_from,
+        _ => m.end() == to && m.promotion() == promote_to,
+    })
+}
+
+/// The Polyglot encoding of `m`, as read by [`decode_move`].
+pub fn encode_move(m: Move) -> u16 {
+    let to = match m {
+        Move::Castle { rook_from, .. } => rook_from,
+        _ => m.end(),
+    };
+    let promotion = match m.promotion() {
+        None => 0,
+        Some(BoardPieceKind::Knight) => 1,
+        Some(BoardPieceKind::Bishop) => 2,
+        Some(BoardPieceKind::Rook) => 3,
+        Some(_) => 4,
+    };
+    promotion << 12 | (m.start().get_pos() as u16) << 6 | to.get_pos() as u16
+}
@@ -0,0 +1,170 @@
+//! Building Polyglot opening books from games: every move played in the opening is
+//! counted, and the moves that scored well enough become the book's entries.
+
+use crate::book::{encode_move, BookEntry};
+use crate::game_state::GameState;
+use crate::pgn::{GameResult, PgnError, PgnReader};
+use crate::piece::PieceColor;
+use crate::piece_move::Move;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// Which moves make it into the book.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildOptions {
+    /// How many plies of each game are counted.
+    pub max_ply: u32,
+    /// How many games a move must have been played in.
+    pub min_games: u32,
+    /// The smallest share of the points a move must have scored for the side that
+    /// played it, between 0 and 1.
+    pub min_score: f64,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            max_ply: 24,
+            min_games: 1,
+            min_score: 0.0,
+        }
+    }
+}
+
+/// How the games a move was played in ended, for the side that played it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn games(self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of the points scored, between 0 and 1.
+    pub fn score(self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// As Polyglot weighs moves: two points for a win, one for a draw.
+    fn weight(self) -> u64 {
+        2 * self.wins as u64 + self.draws as u64
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("game {game}: {error}")]
+pub struct BuildBookError {
+    /// Counted from 1.
+    pub game: usize,
+    pub error: PgnError,
+}
+
+/// Counts the moves of games, to write them as a book.
+#[derive(Clone, Debug, Default)]
+pub struct BookBuilder {
+    options: BuildOptions,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl BookBuilder {
+    pub fn new(options: BuildOptions) -> Self {
+        Self {
+            options,
+            stats: HashMap::new(),
+            games: 0,
+        }
+    }
+
+    /// How many games were added so far.
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// Counts the moves of a game from `start`, which `winner` won, or drew if `None`.
+    pub fn add_game(&mut self, start: GameState, moves: &[Move], winner: Option<PieceColor>) {
+        let mut state = start;
+        for &m in moves.iter().take(self.options.max_ply as usize) {
+            let stats = self
+                .stats
+                .entry((state.zobrist(), encode_move(m)))
+                .or_default();
+            match winner {
+                Some(color) if color == state.next_move() => stats.wins += 1,
+                Some(_) => stats.losses += 1,
+                None => stats.draws += 1,
+            }
+            state.perform_move(m);
+        }
+        self.games += 1;
+    }
+
+    /// Adds every finished game of a PGN database, skipping unfinished ones (`*`).
+    /// Comments, variations and NAGs are ignored.
+    pub fn add_pgn(&mut self, pgn: &str) -> Result<(), BuildBookError> {
+        self.read_pgn(pgn.as_bytes())
+    }
+
+    /// As [`Self::add_pgn`], reading the games one at a time.
+    pub fn read_pgn(&mut self, pgn: impl BufRead) -> Result<(), BuildBookError> {
+        for (game, read) in PgnReader::new(pgn).enumerate() {
+            let pgn_game = read.map_err(|error| BuildBookError {
+                game: game + 1,
+                error,
+            })?;
+            if pgn_game.result == GameResult::Unfinished {
+                continue;
+            }
+            let moves = pgn_game.moves().collect::<Vec<_>>();
+            self.add_game(pgn_game.start, &moves, pgn_game.result.winner());
+        }
+        Ok(())
+    }
+
+    /// The entries of the book, by position and then best first. Moves played too
+    /// rarely or that scored too little are left out, and weights are scaled down to
+    /// fit if need be.
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let kept = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| {
+                stats.games() >= self.options.min_games && stats.score() >= self.options.min_score
+            })
+            .collect::<Vec<_>>();
+        let max_weight = kept
+            .iter()
+            .map(|(_, stats)| stats.weight())
+            .max()
+            .unwrap_or(0);
+        let scale = |weight: u64| match max_weight {
+            0..=0xffff => weight,
+            // rounding up, so that no move that scored at all ends up with a weight of 0.
+            _ => (weight * 0xffff).div_ceil(max_weight),
+        };
+
+        let mut entries = kept
+            .into_iter()
+            .map(|(&(key, raw_move), &stats)| BookEntry {
+                key,
+                raw_move,
+                weight: scale(stats.weight()) as u16,
+                learn: 0,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight), entry.raw_move));
+        entries
+    }
+
+    /// Writes the book in the Polyglot `.bin` format.
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        for entry in self.entries() {
+            out.write_all(&entry.to_bytes())?;
+        }
+        out.flush()
+    }
+}
@@ -47,21 +47,15 @@ impl BoardCellRepr {
 
     pub fn set_piece0(&mut self, p0: Option<BoardPiece>) {
         self.r &= 0x0F;
-        match p0 {
-            Some(p0) => {
-                self.r |= (p0 as u8) << 4;
-            }
-            None => {}
+        if let Some(p0) = p0 {
+            self.r |= (p0 as u8) << 4;
         }
     }
 
     pub fn set_piece1(&mut self, p1: Option<BoardPiece>) {
         self.r &= 0xF0;
-        match p1 {
-            Some(p1) => {
-                self.r |= p1 as u8;
-            }
-            None => {}
+        if let Some(p1) = p1 {
+            self.r |= p1 as u8;
         }
     }
 
@@ -185,10 +179,6 @@ impl RankCellBuffer {
         self.0.set_piece(index, p)
     }
 
-    pub fn get_piece(&self, index: BoardIndex) -> Option<BoardPiece> {
-        self.0.get_piece(index)
-    }
-
     pub fn iter_pieces<'a>(
         &'a self,
     ) -> impl Iterator<Item = (BoardIndex, Option<BoardPiece>)> + 'a {
@@ -199,7 +189,7 @@ impl RankCellBuffer {
 #[cfg(test)]
 mod tests {
     use crate::board_position::BoardIndex;
-    use crate::cell_buffer::{BoardCellBuffer, BoardCellRepr, WholeBoardCellBuffer};
+    use crate::cell_buffer::{BoardCellRepr, WholeBoardCellBuffer};
     use crate::piece::BoardPiece;
 
     #[test]
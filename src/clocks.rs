@@ -21,7 +21,13 @@ impl HalfMoveClock {
     }
 
     pub fn advance(&mut self) {
-        self.clock += 1;
+        self.clock = self.clock.saturating_add(1);
+    }
+}
+
+impl Default for HalfMoveClock {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -44,6 +50,12 @@ impl FullMoveCounter {
     }
 
     pub fn inc(&mut self) {
-        self.counter += 1;
+        self.counter = self.counter.saturating_add(1);
+    }
+}
+
+impl Default for FullMoveCounter {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -1,16 +1,52 @@
-use crate::board_position::{BoardIndex, BoardPosition};
+use crate::board::Board;
+use crate::board_position::{BoardIndex, BoardIndexDelta, BoardPosition};
+use crate::piece::{BoardPieceKind, PieceColor};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct EnPassantTarget(pub(crate) BoardIndex);
 
 impl EnPassantTarget {
-    pub fn from_fen(s: &str) -> Option<EnPassantTarget> {
+    /// Parses the en passant field of a FEN string. Only squares on the 3rd and 6th
+    /// ranks can be the target of a double pawn push, anything else is rejected.
+    pub fn from_fen(s: &str) -> Result<Option<EnPassantTarget>, InvalidEnPassantTarget> {
         if s == "-" {
-            return None;
+            return Ok(None);
         }
 
-        s.parse::<BoardPosition>()
-            .map(|it| EnPassantTarget(it.to_index()))
-            .ok()
+        let index = s
+            .parse::<BoardPosition>()
+            .map_err(|_| InvalidEnPassantTarget(s.to_string()))?
+            .to_index();
+
+        if index.rank() != 3 && index.rank() != 6 {
+            return Err(InvalidEnPassantTarget(s.to_string()));
+        }
+
+        Ok(Some(EnPassantTarget(index)))
+    }
+
+    /// Whether the pawn of the side that just moved could have stepped over this
+    /// target with a double push, with `next_move` to play now.
+    pub(crate) fn is_possible_on(self, board: &Board, next_move: PieceColor) -> bool {
+        // direction the opponent's pawns move in.
+        let (target_rank, direction) = match next_move {
+            PieceColor::White => (6, -1),
+            PieceColor::Black => (3, 1),
+        };
+
+        if self.0.rank() != target_rank || board.get_piece_at(self.0).is_some() {
+            return false;
+        }
+
+        // the rank checks above keep both of these on the board.
+        let pawn = self.0 + BoardIndexDelta::delta_rank(direction);
+        let origin = self.0 + BoardIndexDelta::delta_rank(-direction);
+
+        board.get_piece_at(pawn) == Some(BoardPieceKind::Pawn.of_color(next_move.other()))
+            && board.get_piece_at(origin).is_none()
     }
 }
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, thiserror::Error)]
+#[error("{0:?} is an invalid en passant target")]
+pub struct InvalidEnPassantTarget(String);
@@ -0,0 +1,493 @@
+//! Static evaluation: how good a position looks, without searching.
+//!
+//! Every term has a middlegame and an endgame weight, blended by how much material
+//! is left on the board. The weights can be read from and written to a plain text
+//! format, one term per line, so they can be tuned without recompiling.
+
+use crate::attacks;
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::board_position::BoardIndex;
+use crate::game_state::GameState;
+use crate::piece::{BoardPieceKind, PieceColor};
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// A weight with separate middlegame and endgame values, in centipawns.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tapered {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Tapered {
+    pub const ZERO: Tapered = Tapered { mg: 0, eg: 0 };
+
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    const fn scaled(self, n: i32) -> Self {
+        Self::new(self.mg * n, self.eg * n)
+    }
+
+    const fn add(self, other: Self) -> Self {
+        Self::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+/// The weights of all the evaluation terms. Tables indexed by piece kind are in
+/// [`BoardPieceKind`] order (pawn, rook, knight, bishop, queen, king), and tables
+/// indexed by position are from white's point of view, A1 first.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Weights {
+    pub material: [Tapered; 6],
+    pub piece_square: [[Tapered; 64]; 6],
+    /// Per pawn beyond the first on a file.
+    pub doubled_pawn: Tapered,
+    /// Per pawn without friendly pawns on the files next to it.
+    pub isolated_pawn: Tapered,
+    /// By rank, from the pawn's own point of view.
+    pub passed_pawn: [Tapered; 8],
+    /// Per friendly pawn on the two ranks in front of the king.
+    pub king_shield: Tapered,
+    /// Per attack of an enemy piece on the king or the positions around it.
+    pub king_attack: Tapered,
+    /// Per position a piece can go to that isn't guarded by an enemy pawn.
+    pub mobility: [Tapered; 6],
+    pub bishop_pair: Tapered,
+}
+
+impl Weights {
+    /// Every term weighs nothing.
+    pub const ZERO: Weights = Weights {
+        material: [Tapered::ZERO; 6],
+        piece_square: [[Tapered::ZERO; 64]; 6],
+        doubled_pawn: Tapered::ZERO,
+        isolated_pawn: Tapered::ZERO,
+        passed_pawn: [Tapered::ZERO; 8],
+        king_shield: Tapered::ZERO,
+        king_attack: Tapered::ZERO,
+        mobility: [Tapered::ZERO; 6],
+        bishop_pair: Tapered::ZERO,
+    };
+
+    /// The terms as (name, weights) pairs, in the order they are written out.
+    fn terms(&self) -> [(&'static str, &[Tapered]); 14] {
+        [
+            ("material", &self.material),
+            ("pawn_square", &self.piece_square[0]),
+            ("rook_square", &self.piece_square[1]),
+            ("knight_square", &self.piece_square[2]),
+            ("bishop_square", &self.piece_square[3]),
+            ("queen_square", &self.piece_square[4]),
+            ("king_square", &self.piece_square[5]),
+            ("doubled_pawn", std::slice::from_ref(&self.doubled_pawn)),
+            ("isolated_pawn", std::slice::from_ref(&self.isolated_pawn)),
+            ("passed_pawn", &self.passed_pawn),
+            ("king_shield", std::slice::from_ref(&self.king_shield)),
+            ("king_attack", std::slice::from_ref(&self.king_attack)),
+            ("mobility", &self.mobility),
+            ("bishop_pair", std::slice::from_ref(&self.bishop_pair)),
+        ]
+    }
+
+    fn term_mut(&mut self, name: &str) -> Option<&mut [Tapered]> {
+        let term: &mut [Tapered] = match name {
+            "material" => &mut self.material,
+            "pawn_square" => &mut self.piece_square[0],
+            "rook_square" => &mut self.piece_square[1],
+            "knight_square" => &mut self.piece_square[2],
+            "bishop_square" => &mut self.piece_square[3],
+            "queen_square" => &mut self.piece_square[4],
+            "king_square" => &mut self.piece_square[5],
+            "doubled_pawn" => std::slice::from_mut(&mut self.doubled_pawn),
+            "isolated_pawn" => std::slice::from_mut(&mut self.isolated_pawn),
+            "passed_pawn" => &mut self.passed_pawn,
+            "king_shield" => std::slice::from_mut(&mut self.king_shield),
+            "king_attack" => std::slice::from_mut(&mut self.king_attack),
+            "mobility" => &mut self.mobility,
+            "bishop_pair" => std::slice::from_mut(&mut self.bishop_pair),
+            _ => return None,
+        };
+
+        Some(term)
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        let material = [
+            Tapered::new(82, 94),
+            Tapered::new(477, 512),
+            Tapered::new(337, 281),
+            Tapered::new(365, 297),
+            Tapered::new(1025, 936),
+            Tapered::ZERO,
+        ];
+
+        let same = |table: [i32; 64]| table.map(|v| Tapered::new(v, v));
+        let split =
+            |mg: [i32; 64], eg: [i32; 64]| std::array::from_fn(|i| Tapered::new(mg[i], eg[i]));
+
+        Self {
+            material,
+            piece_square: [
+                split(from_visual(PAWN_MG), from_visual(PAWN_EG)),
+                same(from_visual(ROOK)),
+                same(from_visual(KNIGHT)),
+                same(from_visual(BISHOP)),
+                same(from_visual(QUEEN)),
+                split(from_visual(KING_MG), from_visual(KING_EG)),
+            ],
+            doubled_pawn: Tapered::new(-10, -20),
+            isolated_pawn: Tapered::new(-10, -15),
+            passed_pawn: [
+                Tapered::ZERO,
+                Tapered::new(5, 10),
+                Tapered::new(5, 15),
+                Tapered::new(10, 25),
+                Tapered::new(20, 45),
+                Tapered::new(35, 75),
+                Tapered::new(55, 110),
+                Tapered::ZERO,
+            ],
+            king_shield: Tapered::new(12, 0),
+            king_attack: Tapered::new(-8, -2),
+            mobility: [
+                Tapered::ZERO,
+                Tapered::new(2, 4),
+                Tapered::new(4, 4),
+                Tapered::new(4, 5),
+                Tapered::new(1, 2),
+                Tapered::ZERO,
+            ],
+            bishop_pair: Tapered::new(30, 50),
+        }
+    }
+}
+
+/// One term per line: its name, then as many `mg/eg` pairs as it has weights.
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, weights) in self.terms() {
+            write!(f, "{name}")?;
+            for w in weights {
+                write!(f, " {}/{}", w.mg, w.eg)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the format written by [`Weights`]'s `Display`. Terms that are left out keep
+/// their default weights, and `#` starts a comment.
+impl FromStr for Weights {
+    type Err = ParseWeightsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Weights::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.split('#').next().unwrap().trim();
+            let mut fields = line.split_whitespace();
+            let Some(name) = fields.next() else {
+                continue;
+            };
+
+            let term = weights
+                .term_mut(name)
+                .ok_or_else(|| ParseWeightsError::UnknownTerm(line_number, name.to_string()))?;
+            let values = fields
+                .map(|field| {
+                    let (mg, eg) = field.split_once('/')?;
+                    Some(Tapered::new(mg.parse().ok()?, eg.parse().ok()?))
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ParseWeightsError::InvalidWeight(line_number))?;
+
+            if values.len() != term.len() {
+                return Err(ParseWeightsError::WrongCount {
+                    line: line_number,
+                    expected: term.len(),
+                    got: values.len(),
+                });
+            }
+            term.copy_from_slice(&values);
+        }
+
+        Ok(weights)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseWeightsError {
+    #[error("line {0}: unknown term {1:?}")]
+    UnknownTerm(usize, String),
+    #[error("line {0}: weights have to be written as mg/eg")]
+    InvalidWeight(usize),
+    #[error("line {line}: expected {expected} weights, got {got}")]
+    WrongCount {
+        line: usize,
+        expected: usize,
+        got: usize,
+    },
+}
+
+/// The phase of the starting position.
+pub const MAX_PHASE: i32 = 24;
+
+/// How far from the endgame the position is, from 0 (bare kings and pawns) to
+/// [`MAX_PHASE`] (all the pieces still on the board).
+pub fn phase(board: &Board) -> i32 {
+    let count = |kind| board.pieces_of_kind(kind).count() as i32;
+    let phase = count(BoardPieceKind::Knight)
+        + count(BoardPieceKind::Bishop)
+        + 2 * count(BoardPieceKind::Rook)
+        + 4 * count(BoardPieceKind::Queen);
+    phase.min(MAX_PHASE)
+}
+
+/// The evaluation of `state` in centipawns, from the side to move's point of view.
+pub fn evaluate(state: &GameState, weights: &Weights) -> i32 {
+    let board = state.board();
+    let score = evaluate_side(board, PieceColor::White, weights)
+        .add(evaluate_side(board, PieceColor::Black, weights).scaled(-1));
+
+    let phase = phase(board);
+    let white = (score.mg * phase + score.eg * (MAX_PHASE - phase)) / MAX_PHASE;
+
+    match state.next_move() {
+        PieceColor::White => white,
+        PieceColor::Black => -white,
+    }
+}
+
+/// Everything in favor of `color`.
+fn evaluate_side(board: &Board, color: PieceColor, weights: &Weights) -> Tapered {
+    let own = board.pieces_of_color(color);
+    let enemies = board.pieces_of_color(color.other());
+    let occupied = own | enemies;
+    let own_pawns = board.pieces(BoardPieceKind::Pawn.of_color(color));
+    let enemy_pawns = board.pieces(BoardPieceKind::Pawn.of_color(color.other()));
+
+    let enemy_pawn_attacks = enemy_pawns.iter().fold(Bitboard::EMPTY, |bb, pawn| {
+        bb | attacks::pawn_attacks(color.other(), pawn)
+    });
+
+    // positions are mirrored for black, so the tables read the same for both sides.
+    let relative = |index: BoardIndex| match color {
+        PieceColor::White => index.get_pos() as usize,
+        PieceColor::Black => index.get_pos() as usize ^ 56,
+    };
+
+    let mut score = Tapered::ZERO;
+
+    for index in own {
+        let kind = board
+            .get_piece_at(index)
+            .expect("own pieces are on the board")
+            .kind();
+        let k = kind as usize - 1;
+
+        score = score
+            .add(weights.material[k])
+            .add(weights.piece_square[k][relative(index)]);
+
+        let reach = match kind {
+            BoardPieceKind::Knight => attacks::knight_attacks(index),
+            BoardPieceKind::Bishop => attacks::bishop_attacks(index, occupied),
+            BoardPieceKind::Rook => attacks::rook_attacks(index, occupied),
+            BoardPieceKind::Queen => attacks::queen_attacks(index, occupied),
+            BoardPieceKind::Pawn | BoardPieceKind::King => Bitboard::EMPTY,
+        };
+        let mobility = (reach & !own & !enemy_pawn_attacks).count() as i32;
+        score = score.add(weights.mobility[k].scaled(mobility));
+    }
+
+    // pawn structure
+    for file in 1..=8 {
+        let on_file = (own_pawns & Bitboard::file(file)).count() as i32;
+        if on_file > 1 {
+            score = score.add(weights.doubled_pawn.scaled(on_file - 1));
+        }
+
+        let neighbours = adjacent_files(file);
+        if on_file > 0 && (own_pawns & neighbours).is_empty() {
+            score = score.add(weights.isolated_pawn.scaled(on_file));
+        }
+    }
+
+    for pawn in own_pawns {
+        let ahead = ranks_ahead(color, pawn.rank());
+        let blockers = (Bitboard::file(pawn.file()) | adjacent_files(pawn.file())) & ahead;
+        if (enemy_pawns & blockers).is_empty() {
+            let rank = match color {
+                PieceColor::White => pawn.rank(),
+                PieceColor::Black => 9 - pawn.rank(),
+            };
+            score = score.add(weights.passed_pawn[rank as usize - 1]);
+        }
+    }
+
+    // king safety
+    if let Some(king) = board.king_position(color) {
+        let files = Bitboard::file(king.file()) | adjacent_files(king.file());
+        let shield_ranks = match color {
+            PieceColor::White => [king.rank() + 1, king.rank() + 2],
+            PieceColor::Black => [king.rank().wrapping_sub(1), king.rank().wrapping_sub(2)],
+        }
+        .into_iter()
+        .filter(|rank| (1..=8).contains(rank))
+        .fold(Bitboard::EMPTY, |bb, rank| bb | Bitboard::rank(rank));
+        let shield = (own_pawns & files & shield_ranks).count() as i32;
+        score = score.add(weights.king_shield.scaled(shield));
+
+        let zone = attacks::king_attacks(king) | Bitboard::from_index(king);
+        let attacks_on_zone = zone
+            .iter()
+            .map(|index| board.attackers(index, color.other()).count() as i32)
+            .sum::<i32>();
+        score = score.add(weights.king_attack.scaled(attacks_on_zone));
+    }
+
+    if board
+        .pieces(BoardPieceKind::Bishop.of_color(color))
+        .has_many()
+    {
+        score = score.add(weights.bishop_pair);
+    }
+
+    score
+}
+
+fn adjacent_files(file: u8) -> Bitboard {
+    let mut files = Bitboard::EMPTY;
+    if file > 1 {
+        files |= Bitboard::file(file - 1);
+    }
+    if file < 8 {
+        files |= Bitboard::file(file + 1);
+    }
+    files
+}
+
+/// The ranks in front of `rank`, as seen by `color`.
+fn ranks_ahead(color: PieceColor, rank: u8) -> Bitboard {
+    match color {
+        PieceColor::White if rank == 8 => Bitboard::EMPTY,
+        PieceColor::White => Bitboard(!0 << (8 * rank)),
+        PieceColor::Black => Bitboard((1 << (8 * (rank - 1))) - 1),
+    }
+}
+
+/// Turns a table written the way a board is drawn, rank 8 first, into one indexed by
+/// position, A1 first.
+const fn from_visual(table: [i32; 64]) -> [i32; 64] {
+    let mut flipped = [0; 64];
+    let mut i = 0;
+    while i < 64 {
+        flipped[i] = table[i ^ 56];
+        i += 1;
+    }
+    flipped
+}
+
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
@@ -1,12 +1,17 @@
 use crate::board::{Board, BoardVisual, ParseBoardError};
+use crate::attacks;
+use crate::bitboard::Bitboard;
+use crate::board_position::{BoardIndex, BoardPosition};
 use crate::castle_rights::{CastleRights, InvalidCastleRight};
 use crate::clocks::{FullMoveCounter, HalfMoveClock};
-use crate::en_passant_target::EnPassantTarget;
-use crate::piece::PieceColor;
+use crate::en_passant_target::{EnPassantTarget, InvalidEnPassantTarget};
+use crate::outcome::{DecisiveReason, DrawReason, Outcome};
+use crate::piece::{BoardPiece, BoardPieceKind, PieceColor};
+use crate::zobrist;
 use std::fmt;
 use std::fmt::Formatter;
 use std::num::ParseIntError;
-use crate::piece_move::Move;
+use crate::piece_move::{Move, MoveSet};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct GameState {
@@ -16,6 +21,8 @@ pub struct GameState {
     en_passant_target: Option<EnPassantTarget>,
     half_move_clock: HalfMoveClock,
     full_move_counter: FullMoveCounter,
+    /// See [`GameState::zobrist`].
+    zobrist: u64,
 }
 
 impl GameState {
@@ -49,18 +56,27 @@ impl GameState {
         };
 
         let castling_rights = CastleRights::rights_from_fen_str(rights)?;
-        let en_passant_target = EnPassantTarget::from_fen(en_passant_target);
+        // a target no pawn could have produced is dropped rather than offered to move
+        // generation.
+        let en_passant_target = EnPassantTarget::from_fen(en_passant_target)?
+            .filter(|ept| ept.is_possible_on(&board, next_move));
         let half_move_clock = HalfMoveClock::new_from_clock(half_moves.parse()?);
         let full_move_counter = FullMoveCounter::new_from_counter(full_moves.parse()?);
 
-        Ok(Self {
+        let mut state = Self {
             board,
             next_move,
             castling_rights,
             en_passant_target,
             half_move_clock,
             full_move_counter,
-        })
+            zobrist: 0,
+        };
+        state.zobrist = board.zobrist()
+            ^ zobrist::castle_rights_key(castling_rights)
+            ^ zobrist::turn_key(next_move)
+            ^ state.en_passant_key();
+        Ok(state)
     }
 
     pub fn board_to_fen(&self) -> String {
@@ -108,7 +124,8 @@ impl GameState {
 
         match self.en_passant_target {
             Some(ept) => {
-                write!(fen, "{}", ept.0).unwrap();
+                // squares are displayed in upper case, but FEN wants them in lower case.
+                fen.push_str(&ept.0.to_string().to_ascii_lowercase());
             }
             None => {
                 fen.push('-');
@@ -133,9 +150,153 @@ impl GameState {
         )
     }
 
+    /// Appends the moves in `set` to `moves`, including ones that leave the king in
+    /// check, which [`GameState::is_legal`] tells apart.
+    pub fn push_possible_moves(&self, set: MoveSet, moves: &mut Vec<Move>) {
+        self.board.push_possible_moves(
+            self.next_move,
+            self.en_passant_target,
+            self.castling_rights,
+            set,
+            moves,
+        );
+    }
+
+    /// Whether `m`, one of the possible moves, leaves the king out of check.
+    pub fn is_legal(&self, m: Move) -> bool {
+        self.board.check_move_validity(self.next_move, m)
+    }
+
+    /// The legal moves of the piece at `index`, if it is the side to move's.
+    pub fn legal_moves_from(&self, index: BoardIndex) -> impl Iterator<Item = Move> + '_ {
+        let moves = match self.board.get_piece_at(index) {
+            Some(piece) if piece.color() == self.next_move => piece.moves_on_board(
+                index,
+                &self.board,
+                self.en_passant_target,
+                self.castling_rights,
+            ),
+            _ => Vec::new(),
+        };
+        moves.into_iter().filter(|&m| self.is_legal(m))
+    }
+
+    /// Finds the legal move written in UCI notation, e.g. `e2e4` or `e7e8q`.
+    ///
+    /// Castling may be written either as the king's two-square move (`e1g1`) or as the
+    /// king taking its own rook (`e1h1`).
+    pub fn parse_uci_move(&self, uci: &str) -> Result<Move, ParseUciMoveError> {
+        let invalid = || ParseUciMoveError::InvalidSyntax(uci.to_string());
+
+        if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+            return Err(invalid());
+        }
+        let square = |s: &str| {
+            s.parse::<BoardPosition>()
+                .map(BoardPosition::to_index)
+                .map_err(|_| invalid())
+        };
+        let from = square(&uci[0..2])?;
+        let to = square(&uci[2..4])?;
+        let promote_to = match uci.as_bytes().get(4) {
+            None => None,
+            Some(b'q') => Some(BoardPieceKind::Queen),
+            Some(b'r') => Some(BoardPieceKind::Rook),
+            Some(b'b') => Some(BoardPieceKind::Bishop),
+            Some(b'n') => Some(BoardPieceKind::Knight),
+            Some(_) => return Err(invalid()),
+        };
+
+        self.legal_moves()
+            .find(|m| {
+                let end_matches = match *m {
+                    Move::Castle {
+                        king_to, rook_from, ..
+                    } => to == king_to || to == rook_from,
+                    _ => to == m.end(),
+                };
+                m.start() == from && end_matches && m.promotion() == promote_to
+            })
+            .ok_or_else(|| ParseUciMoveError::IllegalMove(uci.to_string()))
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn next_move(&self) -> PieceColor {
+        self.next_move
+    }
+
+    /// Plies since the last capture or pawn move.
+    pub fn half_move_clock(&self) -> u8 {
+        self.half_move_clock.get()
+    }
+
+    /// The number of the move being played, starting at 1 and going up after black's.
+    pub fn full_move_counter(&self) -> u16 {
+        self.full_move_counter.get()
+    }
+
+    pub fn castling_rights(&self) -> CastleRights {
+        self.castling_rights
+    }
+
+    /// The zobrist key of the position: pieces, side to move, castling rights and
+    /// en passant target. It's the same key Polyglot opening books use.
+    ///
+    /// It's kept up to date as moves are made and taken back.
+    pub fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
+
+    /// The en passant part of the zobrist key.
+    fn en_passant_key(&self) -> u64 {
+        // like Polyglot, only count the target if a pawn stands ready to capture there.
+        match self.en_passant_target {
+            Some(ept) => {
+                let pawns = self
+                    .board
+                    .pieces(BoardPieceKind::Pawn.of_color(self.next_move));
+                if (attacks::pawn_attacks(self.next_move.other(), ept.0) & pawns).is_empty() {
+                    0
+                } else {
+                    zobrist::en_passant_key(ept.0.file())
+                }
+            }
+            None => 0,
+        }
+    }
+
+    /// Whether the side to move is in check.
+    pub fn is_check(&self) -> bool {
+        self.board.is_in_check(self.next_move)
+    }
+
+    /// The positions of the pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        self.board.checkers(self.next_move)
+    }
+
     pub fn perform_move(&mut self, m: Move) {
-        let (b, mi) = self.board.board_after_move(m);
-        self.board = b;
+        self.make_move(m);
+    }
+
+    /// Plays `m`, returning what's needed to take it back with
+    /// [`GameState::unmake_move`].
+    pub fn make_move(&mut self, m: Move) -> UndoInfo {
+        let undo = UndoInfo {
+            captured: None,
+            castling_rights: self.castling_rights,
+            en_passant_target: self.en_passant_target,
+            half_move_clock: self.half_move_clock,
+            full_move_counter: self.full_move_counter,
+            zobrist: self.zobrist,
+        };
+
+        let pieces_key = self.board.zobrist();
+        self.zobrist ^= self.en_passant_key();
+        let mi = self.board.make_move(m);
 
         debug_assert_eq!(self.next_move, mi.moved_piece_color);
 
@@ -150,8 +311,183 @@ impl GameState {
         }
 
         self.en_passant_target = mi.new_en_passant_target;
-        self.castling_rights.revoke(mi.revoked_castle_rights);
+        if self.castling_rights & mi.revoked_castle_rights != CastleRights::EMPTY {
+            self.zobrist ^= zobrist::castle_rights_key(self.castling_rights);
+            self.castling_rights.revoke(mi.revoked_castle_rights);
+            self.zobrist ^= zobrist::castle_rights_key(self.castling_rights);
+        }
         self.next_move = mi.moved_piece_color.other();
+
+        self.zobrist ^= pieces_key
+            ^ self.board.zobrist()
+            ^ zobrist::turn_key(PieceColor::White)
+            ^ zobrist::turn_key(PieceColor::Black)
+            ^ self.en_passant_key();
+
+        UndoInfo {
+            captured: mi.captured,
+            ..undo
+        }
+    }
+
+    /// Takes back `m`, which must be the last move made with [`GameState::make_move`],
+    /// restoring the state exactly as it was before.
+    pub fn unmake_move(&mut self, m: Move, undo: UndoInfo) {
+        self.board.unmake_move(m, undo.captured);
+        self.next_move = self.next_move.other();
+        self.castling_rights = undo.castling_rights;
+        self.en_passant_target = undo.en_passant_target;
+        self.half_move_clock = undo.half_move_clock;
+        self.full_move_counter = undo.full_move_counter;
+        self.zobrist = undo.zobrist;
+    }
+
+    /// Counts the leaf nodes of the legal move tree `depth` plies deep.
+    pub fn perft(&self, depth: u32) -> u64 {
+        let mut state = *self;
+        state.perft_in_place(depth)
+    }
+
+    fn perft_in_place(&mut self, depth: u32) -> u64 {
+        match depth {
+            0 => 1,
+            // no need to play the last moves just to count them.
+            1 => self.legal_moves().count() as u64,
+            _ => {
+                let moves = self.legal_moves().collect::<Vec<_>>();
+                moves
+                    .into_iter()
+                    .map(|m| {
+                        let undo = self.make_move(m);
+                        let nodes = self.perft_in_place(depth - 1);
+                        self.unmake_move(m, undo);
+                        nodes
+                    })
+                    .sum()
+            }
+        }
+    }
+
+    /// Like [`GameState::perft`], but split by the legal moves in this position.
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        let mut state = *self;
+        let moves = state.legal_moves().collect::<Vec<_>>();
+        moves
+            .into_iter()
+            .map(|m| {
+                let undo = state.make_move(m);
+                let nodes = state.perft_in_place(depth - 1);
+                state.unmake_move(m, undo);
+                (m, nodes)
+            })
+            .collect()
+    }
+
+    /// Whether the game is over in this position, or a draw may be claimed. Repetitions
+    /// can't be detected without the previous positions, see
+    /// [`GameState::outcome_with_history`].
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome_with_history(&[])
+    }
+
+    /// Like [`GameState::outcome`], but also detects repetitions. `history` holds the
+    /// positions that occurred before this one in the game, oldest first.
+    ///
+    /// Automatic results take precedence over claimable draws, so
+    /// [`Outcome::is_automatic`] tells whether the game is actually over.
+    pub fn outcome_with_history(&self, history: &[GameState]) -> Option<Outcome> {
+        if self.legal_moves().next().is_none() {
+            return Some(if self.is_check() {
+                Outcome::Decisive {
+                    winner: self.next_move.other(),
+                    reason: DecisiveReason::Checkmate,
+                }
+            } else {
+                Outcome::Draw {
+                    reason: DrawReason::Stalemate,
+                }
+            });
+        }
+
+        let repetitions = self.repetition_count(history);
+        let half_moves = self.half_move_clock.get();
+
+        let reason = if repetitions >= 5 {
+            DrawReason::FivefoldRepetition
+        } else if half_moves >= 150 {
+            DrawReason::SeventyFiveMoveRule
+        } else if self.is_insufficient_material() {
+            DrawReason::InsufficientMaterial
+        } else if repetitions >= 3 {
+            DrawReason::ThreefoldRepetition
+        } else if half_moves >= 100 {
+            DrawReason::FiftyMoveRule
+        } else {
+            return None;
+        };
+
+        Some(Outcome::Draw { reason })
+    }
+
+    /// How many times this position occurred, counting this occurrence. `history` holds
+    /// the positions before this one, oldest first.
+    pub fn repetition_count(&self, history: &[GameState]) -> usize {
+        // nothing before the last capture or pawn move can repeat.
+        let reversible = history.len().min(self.half_move_clock.get() as usize);
+
+        let zobrist = self.board.zobrist();
+        1 + history[history.len() - reversible..]
+            .iter()
+            .filter(|previous| previous.board.zobrist() == zobrist)
+            .filter(|previous| previous.is_same_position(self))
+            .count()
+    }
+
+    /// Whether both states are the same position for the purpose of repetitions: the
+    /// same pieces, side to move, castling rights and en passant captures.
+    pub fn is_same_position(&self, other: &GameState) -> bool {
+        self.board == other.board
+            && self.next_move == other.next_move
+            && self.castling_rights == other.castling_rights
+            && self.en_passant_capture() == other.en_passant_capture()
+    }
+
+    /// The en passant target, but only if a pawn can actually capture there.
+    fn en_passant_capture(&self) -> Option<EnPassantTarget> {
+        self.en_passant_target.filter(|_| {
+            self.legal_moves()
+                .any(|m| matches!(m, Move::EnPassant { .. }))
+        })
+    }
+
+    /// Whether neither side has enough material left to checkmate: bare kings, a
+    /// single minor piece, or only bishops that all stand on the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishop_square_colors = [0; 2];
+
+        for (idx, piece) in self.board.piece_iterator() {
+            match piece.kind() {
+                BoardPieceKind::King => {}
+                BoardPieceKind::Knight => knights += 1,
+                BoardPieceKind::Bishop => {
+                    bishop_square_colors[((idx.rank() + idx.file()) % 2) as usize] += 1;
+                }
+                BoardPieceKind::Pawn | BoardPieceKind::Rook | BoardPieceKind::Queen => {
+                    return false
+                }
+            }
+        }
+
+        // any number of bishops on squares of a single color, or a lone knight.
+        matches!(
+            (knights, bishop_square_colors),
+            (0, [0, _] | [_, 0]) | (1, [0, 0])
+        )
     }
 
     pub fn board_to_visual(&self) -> BoardVisual {
@@ -159,6 +495,33 @@ impl GameState {
     }
 }
 
+/// What [`GameState::make_move`] changes irreversibly, kept so that
+/// [`GameState::unmake_move`] can restore it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct UndoInfo {
+    captured: Option<BoardPiece>,
+    castling_rights: CastleRights,
+    en_passant_target: Option<EnPassantTarget>,
+    half_move_clock: HalfMoveClock,
+    full_move_counter: FullMoveCounter,
+    zobrist: u64,
+}
+
+impl UndoInfo {
+    /// The piece the move captured, if any.
+    pub fn captured(&self) -> Option<BoardPiece> {
+        self.captured
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseUciMoveError {
+    #[error("{0:?} is not a valid UCI move")]
+    InvalidSyntax(String),
+    #[error("{0:?} is not a legal move")]
+    IllegalMove(String),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum ParseGameStateError {
     #[error("missing fields (got only {field_count})")]
@@ -171,4 +534,6 @@ pub enum ParseGameStateError {
     InvalidNextMove(String),
     #[error("invalid castle right: {0:?}")]
     InvalidCastleRight(#[from] InvalidCastleRight),
+    #[error("invalid en passant target: {0}")]
+    InvalidEnPassantTarget(#[from] InvalidEnPassantTarget),
 }
@@ -1,12 +1,29 @@
 pub(crate) mod cell_buffer;
 pub mod piece;
+pub mod bitboard;
+pub mod attacks;
 pub mod board_position;
 pub mod board;
 pub mod piece_move;
+pub(crate) mod move_picker;
 pub mod castle_rights;
 pub mod en_passant_target;
 pub mod clocks;
 pub mod game_state;
+pub mod outcome;
+pub mod zobrist;
+pub mod san;
+pub mod pgn;
+pub mod book;
+pub mod book_builder;
+pub mod eval;
+pub mod search;
+#[cfg(feature = "syzygy")]
+pub mod tablebase;
+pub mod time_manager;
+pub mod transposition;
+pub mod uci;
+pub mod xboard;
 
 #[cfg(test)]
 mod tests;
@@ -1,7 +1,15 @@
 use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
 use rustyline::{Behavior, ColorMode};
-use knix::game_state::GameState;
+use knix::board_position::BoardPosition;
+use knix::book_builder::{BookBuilder, BuildOptions};
+use knix::eval::{self, Weights};
+use knix::game_state::{GameState, UndoInfo};
+use knix::piece::BoardPiece;
+use knix::piece_move::Move;
+#[cfg(feature = "syzygy")]
+use knix::tablebase::Tablebase;
+use std::time::Instant;
 
 pub type R<T = ()> = anyhow::Result<T>;
 
@@ -17,20 +25,199 @@ fn do_read_fen(file: &str) -> R<GameState> {
     Ok(GameState::parse_from_fen(&fen)?)
 }
 
+fn do_perft(state: &GameState, depth: &str) -> R {
+    let depth = depth.trim().parse()?;
+    let start = Instant::now();
+    let nodes = state.perft(depth);
+    println!("{nodes} nodes in {:?}", start.elapsed());
+    Ok(())
+}
+
+fn do_divide(state: &GameState, depth: &str) -> R {
+    let depth = depth.trim().parse()?;
+    let mut total = 0;
+    for (m, nodes) in state.perft_divide(depth) {
+        println!("{}: {nodes}", m.to_uci());
+        total += nodes;
+    }
+    println!("total: {total}");
+    Ok(())
+}
+
+fn parse_position(s: &str) -> R<BoardPosition> {
+    s.parse()
+        .map_err(|e| anyhow::anyhow!("invalid position {s:?}: {e:?}"))
+}
+
+/// `move <san or uci>`, e.g. `move Nf3` or `move g1f3`, or `move <from> <to> [promotion piece]`, e.g. `move e7 e8 q`.
+fn do_move(state: &mut GameState, history: &mut Vec<(Move, UndoInfo)>, args: &str) -> R {
+    let parts = args.split_whitespace().collect::<Vec<_>>();
+    let m = match parts.as_slice() {
+        [m] => match state.parse_uci_move(m) {
+            Ok(m) => m,
+            Err(_) => state.parse_san(m)?,
+        },
+        [from, to, promote_to @ ..] if promote_to.len() <= 1 => {
+            let from = parse_position(from)?.to_index();
+            let to = parse_position(to)?.to_index();
+            let promote_to = promote_to
+                .first()
+                .map(|p| {
+                    p.chars()
+                        .next()
+                        .and_then(|c| BoardPiece::try_from_fen_char(c.to_ascii_uppercase()))
+                        .map(BoardPiece::kind)
+                        .ok_or_else(|| anyhow::anyhow!("invalid promotion piece {p:?}"))
+                })
+                .transpose()?;
+
+            state
+                .legal_moves()
+                .find(|m| m.start() == from && m.end() == to && m.promotion() == promote_to)
+                .ok_or_else(|| anyhow::anyhow!("illegal move"))?
+        }
+        _ => anyhow::bail!("usage: move <san or uci> | move <from> <to> [promotion piece]"),
+    };
+
+    println!("{}", state.move_to_san(m));
+    let undo = state.make_move(m);
+    history.push((m, undo));
+    Ok(())
+}
+
+fn do_undo(state: &mut GameState, history: &mut Vec<(Move, UndoInfo)>) -> R {
+    let (m, undo) = history
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("no moves to undo"))?;
+    state.unmake_move(m, undo);
+    println!("undid {}", state.move_to_san(m));
+    Ok(())
+}
+
+fn do_eval(state: &GameState, file: &str) -> R {
+    let weights = match file.trim() {
+        "" => Weights::default(),
+        file => std::fs::read_to_string(file)?.parse()?,
+    };
+    println!("{}", eval::evaluate(state, &weights));
+    Ok(())
+}
+
+/// `tb <dir>`: what the Syzygy tables in `dir` say about the position and its moves.
+#[cfg(feature = "syzygy")]
+fn do_tb(state: &GameState, dir: &str) -> R {
+    let tablebase = Tablebase::open(dir.trim())?;
+    println!("wdl: {:?}", tablebase.probe_wdl(state)?);
+    println!("dtz: {}", tablebase.probe_dtz(state)?);
+    for root in tablebase.rank_root_moves(state)? {
+        println!(
+            "{}: {:?}, dtz {}",
+            state.move_to_san(root.m),
+            root.wdl,
+            root.dtz
+        );
+    }
+    Ok(())
+}
+
+/// `knix book build <book.bin> <games.pgn>... [--max-ply <n>] [--min-games <n>]
+/// [--min-score <0 to 1>]`
+fn do_book(args: &[String]) -> R {
+    let usage = "usage: knix book build <book.bin> <games.pgn>... [--max-ply <n>] \
+                 [--min-games <n>] [--min-score <0 to 1>]";
+    let [command, out, rest @ ..] = args else {
+        anyhow::bail!(usage);
+    };
+    if command != "build" {
+        anyhow::bail!(usage);
+    }
+
+    let mut options = BuildOptions::default();
+    let mut files = Vec::new();
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || {
+            rest.next()
+                .ok_or_else(|| anyhow::anyhow!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--max-ply" => options.max_ply = value()?.parse()?,
+            "--min-games" => options.min_games = value()?.parse()?,
+            "--min-score" => options.min_score = value()?.parse()?,
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        anyhow::bail!(usage);
+    }
+
+    let mut builder = BookBuilder::new(options);
+    for file in files {
+        builder
+            .read_pgn(std::io::BufReader::new(std::fs::File::open(file)?))
+            .map_err(|e| anyhow::anyhow!("{file}: {e}"))?;
+    }
+    let entries = builder.entries().len();
+    builder.write(std::io::BufWriter::new(std::fs::File::create(out)?))?;
+    println!(
+        "{} games, {entries} entries written to {out}",
+        builder.games()
+    );
+    Ok(())
+}
+
+fn run_command(state: &mut GameState, history: &mut Vec<(Move, UndoInfo)>, line: &str) -> R {
+    let line = line.trim();
+    let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+    match command {
+        "" => {}
+        "read-fen" => {
+            *state = do_read_fen(args)?;
+            history.clear();
+        }
+        "perft" => do_perft(state, args)?,
+        "divide" => do_divide(state, args)?,
+        "move" => do_move(state, history, args)?,
+        "undo" => do_undo(state, history)?,
+        "eval" => do_eval(state, args)?,
+        #[cfg(feature = "syzygy")]
+        "tb" => do_tb(state, args)?,
+        "show" => {
+            println!("{}", state.to_fen());
+            println!("{}", state.board_to_visual());
+        }
+        _ => anyhow::bail!("unknown command {command:?}"),
+    }
+    Ok(())
+}
+
 fn main() -> R {
+    match std::env::args().nth(1).as_deref() {
+        Some("uci") => return Ok(knix::uci::run(std::io::stdin().lock(), std::io::stdout())?),
+        Some("book") => return do_book(&std::env::args().skip(2).collect::<Vec<_>>()),
+        Some("xboard") => {
+            return Ok(knix::xboard::run(
+                std::io::stdin().lock(),
+                std::io::stdout(),
+            )?)
+        }
+        _ => {}
+    }
+
     let mut editor = rustyline::Editor::<(), rustyline::history::DefaultHistory>::new().unwrap();
     editor.set_auto_add_history(true);
     editor.set_behavior(Behavior::PreferTerm);
     editor.set_color_mode(ColorMode::Enabled);
 
     let mut state = GameState::starting();
+    let mut history = Vec::new();
     println!("{}", std::mem::size_of::<GameState>());
 
     loop {
         match editor.readline(concat!("knix ", env!("CARGO_PKG_VERSION"), "> ")) {
             Ok(line) => {
-                if line.starts_with("read-fen ") {
-                    state = do_read_fen(line.strip_prefix("read-fen ").unwrap())?;
+                if let Err(e) = run_command(&mut state, &mut history, &line) {
+                    eprintln!("{e}");
                 }
             }
             Err(ReadlineError::Eof) => break,
@@ -0,0 +1,271 @@
+//! Move ordering for the search: the moves most likely to be best are tried first, so
+//! that alpha-beta can cut off the rest.
+
+use crate::game_state::GameState;
+use crate::piece::{BoardPieceKind, PieceColor};
+use crate::piece_move::{Move, MoveSet};
+
+/// History scores stay between these, so that new successes still count.
+const MAX_HISTORY: i32 = 16_384;
+
+/// What the search has learned about which quiet moves tend to be good.
+pub(crate) struct MoveOrdering {
+    /// Up to two quiet moves per ply that caused a cutoff in a sibling position.
+    killers: Vec<[Option<Move>; 2]>,
+    /// How often a quiet move by each side from one position to another caused a
+    /// cutoff, against how often it didn't.
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    /// The quiet move that last refuted each move, by its start and end.
+    counter_moves: Box<[[Option<Move>; 64]; 64]>,
+}
+
+impl MoveOrdering {
+    pub(crate) fn new() -> Self {
+        Self {
+            killers: Vec::new(),
+            history: Box::new([[[0; 64]; 64]; 2]),
+            counter_moves: Box::new([[None; 64]; 64]),
+        }
+    }
+
+    /// Remembers that the quiet move `m` caused a cutoff `depth` plies from the horizon,
+    /// after `previous` and the quiet moves in `tried` failed to.
+    pub(crate) fn update(
+        &mut self,
+        color: PieceColor,
+        ply: u32,
+        depth: u32,
+        m: Move,
+        previous: Option<Move>,
+        tried: &[Move],
+    ) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+
+        let bonus = (depth * depth).min(MAX_HISTORY as u32) as i32;
+        self.add_history(color, m, bonus);
+        for &other in tried {
+            self.add_history(color, other, -bonus);
+        }
+
+        if let Some(previous) = previous {
+            self.counter_moves[from(previous)][to(previous)] = Some(m);
+        }
+    }
+
+    fn add_history(&mut self, color: PieceColor, m: Move, bonus: i32) {
+        let entry = &mut self.history[color as usize][from(m)][to(m)];
+        // the closer to the limit, the less a bonus counts.
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+
+    fn killers(&self, ply: u32) -> [Option<Move>; 2] {
+        self.killers.get(ply as usize).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the legal moves of a position one at a time, best first: the hash move,
+/// captures that don't lose material by MVV-LVA, killer moves, the other quiet moves
+/// by history, and lastly losing captures. Quiet moves are only generated if no
+/// capture caused a cutoff.
+pub(crate) struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    next_killer: usize,
+    counter_move: Option<Move>,
+    /// The moves of the current stage with their scores, of which those before `next`
+    /// were handed out already.
+    moves: Vec<(Move, i32)>,
+    next: usize,
+    bad_captures: Vec<(Move, i32)>,
+}
+
+impl MovePicker {
+    /// `hash_move` must be legal. `previous` is the move that led to the position.
+    pub(crate) fn new(
+        ordering: &MoveOrdering,
+        ply: u32,
+        hash_move: Option<Move>,
+        previous: Option<Move>,
+    ) -> Self {
+        let killers = ordering
+            .killers(ply)
+            .map(|killer| killer.filter(|&m| Some(m) != hash_move));
+        let counter_move =
+            previous.and_then(|previous| ordering.counter_moves[from(previous)][to(previous)]);
+
+        Self {
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            next_killer: 0,
+            counter_move,
+            moves: Vec::new(),
+            next: 0,
+            bad_captures: Vec::new(),
+        }
+    }
+
+    /// The next legal move, or `None` once all have been handed out.
+    pub(crate) fn next(&mut self, state: &GameState, ordering: &MoveOrdering) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if self.hash_move.is_some() {
+                        return self.hash_move;
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.generate(state, MoveSet::Captures, |m| capture_score(state, m));
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match self.pick_best() {
+                    Some((m, _)) if Some(m) == self.hash_move => {}
+                    Some((m, _)) if losing(state, m) => {
+                        self.bad_captures.push((m, 0));
+                    }
+                    Some((m, _)) if state.is_legal(m) => return Some(m),
+                    Some(_) => {}
+                    None => self.stage = Stage::Killers,
+                },
+                Stage::Killers => match self.killers.get(self.next_killer) {
+                    Some(&killer) => {
+                        self.next_killer += 1;
+                        if let Some(m) = killer.filter(|&m| is_quiet_move_in(state, m)) {
+                            return Some(m);
+                        }
+                    }
+                    None => self.stage = Stage::GenerateQuiets,
+                },
+                Stage::GenerateQuiets => {
+                    let color = state.next_move() as usize;
+                    let counter_move = self.counter_move;
+                    self.generate(state, MoveSet::Quiets, |m| {
+                        let bonus = if Some(m) == counter_move {
+                            MAX_HISTORY
+                        } else {
+                            0
+                        };
+                        ordering.history[color][from(m)][to(m)] + bonus
+                    });
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.pick_best() {
+                    Some((m, _)) if self.already_picked(m) => {}
+                    Some((m, _)) if state.is_legal(m) => return Some(m),
+                    Some(_) => {}
+                    None => {
+                        self.moves = std::mem::take(&mut self.bad_captures);
+                        self.next = 0;
+                        self.stage = Stage::BadCaptures;
+                    }
+                },
+                Stage::BadCaptures => match self.moves.get(self.next) {
+                    Some(&(m, _)) => {
+                        self.next += 1;
+                        if state.is_legal(m) {
+                            return Some(m);
+                        }
+                    }
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn generate(&mut self, state: &GameState, set: MoveSet, score: impl Fn(Move) -> i32) {
+        let mut moves = Vec::with_capacity(32);
+        state.push_possible_moves(set, &mut moves);
+        self.moves = moves.into_iter().map(|m| (m, score(m))).collect();
+        self.next = 0;
+    }
+
+    /// Moves the best of the remaining moves of the stage to the front, without
+    /// sorting the rest: most of them will never be looked at after a cutoff.
+    fn pick_best(&mut self) -> Option<(Move, i32)> {
+        let remaining = self.moves.get_mut(self.next..)?;
+        let best = (0..remaining.len()).max_by_key(|&i| remaining[i].1)?;
+        remaining.swap(0, best);
+        self.next += 1;
+        Some(self.moves[self.next - 1])
+    }
+
+    /// Whether `m` was handed out before its stage, as the hash move or a killer.
+    fn already_picked(&self, m: Move) -> bool {
+        Some(m) == self.hash_move || self.killers.contains(&Some(m))
+    }
+}
+
+/// Most valuable victim, least valuable attacker: taking a queen with a pawn is tried
+/// before taking a pawn with a queen. Promotions count as taking the new piece.
+fn capture_score(state: &GameState, m: Move) -> i32 {
+    let board = state.board();
+    let victim = match m {
+        Move::EnPassant { .. } => BoardPieceKind::Pawn.value(),
+        _ => board
+            .get_piece_at(m.end())
+            .map_or(0, |piece| piece.kind().value()),
+    };
+    let attacker = board
+        .get_piece_at(m.start())
+        .map_or(0, |piece| piece.kind().value());
+    let promotion = m.promotion().map_or(0, |kind| kind.value());
+
+    16 * (victim + promotion) - attacker.min(1_000)
+}
+
+/// Whether `m` loses material, as far as the static exchange evaluation can tell.
+/// Underpromotions are as good as lost.
+fn losing(state: &GameState, m: Move) -> bool {
+    if m.promotion()
+        .is_some_and(|kind| kind != BoardPieceKind::Queen)
+    {
+        return true;
+    }
+
+    let board = state.board();
+    let value = |index| {
+        board
+            .get_piece_at(index)
+            .map_or(BoardPieceKind::Pawn.value(), |piece| piece.kind().value())
+    };
+    // taking something worth at least as much can't lose.
+    value(m.start()) > value(m.end()) && board.see(m) < 0
+}
+
+/// Whether `m` is a legal quiet move in `state`.
+fn is_quiet_move_in(state: &GameState, m: Move) -> bool {
+    !state.board().is_capture(m)
+        && m.promotion().is_none()
+        && state.legal_moves_from(m.start()).any(|legal| legal == m)
+}
+
+fn from(m: Move) -> usize {
+    m.start().get_pos() as usize
+}
+
+fn to(m: Move) -> usize {
+    m.end().get_pos() as usize
+}
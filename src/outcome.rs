@@ -0,0 +1,57 @@
+use crate::piece::PieceColor;
+
+/// How a game ended, or may be ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Decisive {
+        winner: PieceColor,
+        reason: DecisiveReason,
+    },
+    Draw {
+        reason: DrawReason,
+    },
+}
+
+impl Outcome {
+    pub fn winner(self) -> Option<PieceColor> {
+        match self {
+            Self::Decisive { winner, .. } => Some(winner),
+            Self::Draw { .. } => None,
+        }
+    }
+
+    /// Whether the game is over by itself, as opposed to one of the players having
+    /// the right to claim a draw.
+    pub fn is_automatic(self) -> bool {
+        match self {
+            Self::Decisive { .. } => true,
+            Self::Draw { reason } => !reason.is_claimable(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DecisiveReason {
+    Checkmate,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DrawReason {
+    Stalemate,
+    /// Neither side can possibly checkmate.
+    InsufficientMaterial,
+    /// 50 moves by each side without a capture or a pawn move; has to be claimed.
+    FiftyMoveRule,
+    /// 75 moves by each side without a capture or a pawn move.
+    SeventyFiveMoveRule,
+    /// The same position occurred 3 times; has to be claimed.
+    ThreefoldRepetition,
+    /// The same position occurred 5 times.
+    FivefoldRepetition,
+}
+
+impl DrawReason {
+    pub fn is_claimable(self) -> bool {
+        matches!(self, Self::FiftyMoveRule | Self::ThreefoldRepetition)
+    }
+}
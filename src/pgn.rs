@@ -0,0 +1,624 @@
+//! Reading games in Portable Game Notation: tag pairs, then the moves in SAN with
+//! comments, variations, annotations and the result. Games are read one at a time, so
+//! that databases of any size can be gone through.
+
+use crate::game_state::{GameState, ParseGameStateError};
+use crate::piece::PieceColor;
+use crate::piece_move::Move;
+use crate::san::ParseSanError;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+/// The tags every game should have, in the order they are written in.
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// How a game ended.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// Still going on, abandoned, or not known: `*`.
+    #[default]
+    Unfinished,
+}
+
+impl GameResult {
+    /// The winner, if the game was won.
+    pub fn winner(self) -> Option<PieceColor> {
+        match self {
+            Self::WhiteWins => Some(PieceColor::White),
+            Self::BlackWins => Some(PieceColor::Black),
+            Self::Draw | Self::Unfinished => None,
+        }
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1-0" => Ok(Self::WhiteWins),
+            "0-1" => Ok(Self::BlackWins),
+            "1/2-1/2" => Ok(Self::Draw),
+            "*" => Ok(Self::Unfinished),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WhiteWins => "1-0",
+            Self::BlackWins => "0-1",
+            Self::Draw => "1/2-1/2",
+            Self::Unfinished => "*",
+        })
+    }
+}
+
+/// A game read from PGN.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    /// Every tag pair, in the order they were written.
+    pub tags: Vec<(String, String)>,
+    /// The starting position, from the `FEN` tag if there is one.
+    pub start: GameState,
+    /// With the comments written before the tags, if any.
+    pub mainline: Line,
+    /// From the end of the moves, or the `Result` tag if they don't have one.
+    pub result: GameResult,
+}
+
+impl Game {
+    /// The value of the tag `name`, if the game has it.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The moves of the main line.
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.mainline.moves.iter().map(|played| played.m)
+    }
+
+    /// The position at the end of the main line.
+    pub fn end(&self) -> GameState {
+        let mut state = self.start;
+        for m in self.moves() {
+            state.perform_move(m);
+        }
+        state
+    }
+}
+
+/// A sequence of moves: the main line, or a variation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Line {
+    /// The comments before the first move.
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+/// A move of a line, with what was written after it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnMove {
+    pub m: Move,
+    /// Numeric annotation glyphs, as in `$1`, or `!` which is the same.
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    /// Lines that could have been played instead of this move.
+    pub variations: Vec<Line>,
+}
+
+/// Why reading PGN failed, and where.
+#[derive(Debug, thiserror::Error)]
+#[error("line {line}, column {column}: {kind}")]
+pub struct PgnError {
+    /// Counted from 1.
+    pub line: usize,
+    /// In characters, counted from 1.
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PgnErrorKind {
+    #[error("cannot read: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid tag: {0}")]
+    InvalidTag(String),
+    #[error("comment never ends")]
+    UnterminatedComment,
+    #[error("variation never ends")]
+    UnterminatedVariation,
+    #[error("unexpected {0:?}")]
+    Unexpected(String),
+    #[error("invalid FEN: {0}")]
+    InvalidFen(#[from] ParseGameStateError),
+    #[error(transparent)]
+    InvalidMove(#[from] ParseSanError),
+}
+
+/// Reads the games of a PGN database one after the other. After an error, reading
+/// goes on with the next game.
+pub struct PgnReader<R> {
+    lexer: Lexer<R>,
+    peeked: Option<Token>,
+    /// Set once the input can't be read any more.
+    failed: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lexer: Lexer::new(reader),
+            peeked: None,
+            failed: false,
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, PgnError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnError> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.lexer.next_token(),
+        }
+    }
+
+    /// Reads the next game, if there is one before the end of the input.
+    fn read_game(&mut self) -> Result<Option<Game>, PgnError> {
+        // from between games, kept for the next one.
+        let mut comments = Vec::new();
+        loop {
+            // what can't start a game, such as text after the last one's result.
+            loop {
+                match self.peek()? {
+                    None => return Ok(None),
+                    Some(Token {
+                        kind: TokenKind::Comment(comment),
+                        ..
+                    }) => comments.push(comment.clone()),
+                    Some(Token {
+                        kind: TokenKind::Result(_) | TokenKind::VariationEnd | TokenKind::Nag(_),
+                        ..
+                    }) => {}
+                    Some(Token {
+                        kind: TokenKind::San(san, _),
+                        ..
+                    }) if GameState::starting().parse_san(san).is_err() => {}
+                    Some(_) => break,
+                }
+                self.next_token()?;
+            }
+
+            let mut game = self.read_tags_and_moves()?;
+            if !game.tags.is_empty() || !game.mainline.moves.is_empty() {
+                comments.append(&mut game.mainline.comments);
+                game.mainline.comments = comments;
+                return Ok(Some(game));
+            }
+            // a move number with no moves, say.
+            comments.append(&mut game.mainline.comments);
+        }
+    }
+
+    fn read_tags_and_moves(&mut self) -> Result<Game, PgnError> {
+        let mut tags = Vec::new();
+        let mut start = GameState::starting();
+        while let Some(Token {
+            kind: TokenKind::Tag(..),
+            ..
+        }) = self.peek()?
+        {
+            let Some(Token {
+                kind: TokenKind::Tag(name, value),
+                line,
+                column,
+            }) = self.next_token()?
+            else {
+                unreachable!()
+            };
+            // `SetUp` says whether `FEN` is there, which it tells by itself anyway.
+            if name == "FEN" {
+                start = GameState::parse_from_fen(&value)
+                    .map_err(|e| error(line, column, PgnErrorKind::InvalidFen(e)))?;
+            }
+            tags.push((name, value));
+        }
+
+        // the line being read, and those it branches off from.
+        let mut stack = vec![Branch::new(start)];
+        let mut result = None;
+        loop {
+            // the tags of the next game, without a result for this one.
+            if let Some(Token {
+                kind: TokenKind::Tag(..),
+                ..
+            }) = self.peek()?
+            {
+                break;
+            }
+            let Some(token) = self.next_token()? else {
+                break;
+            };
+            let (line, column) = (token.line, token.column);
+            let branch = stack.last_mut().unwrap();
+
+            match token.kind {
+                TokenKind::Tag(..) => unreachable!(),
+                TokenKind::MoveNumber => {}
+                TokenKind::San(san, nag) => {
+                    let m = branch
+                        .state
+                        .parse_san(&san)
+                        .map_err(|e| error(line, column, e.into()))?;
+                    branch.before_last = Some(branch.state);
+                    branch.state.perform_move(m);
+                    branch.line.moves.push(PgnMove {
+                        m,
+                        nags: nag.into_iter().collect(),
+                        comments: Vec::new(),
+                        variations: Vec::new(),
+                    });
+                }
+                TokenKind::Nag(nag) => match branch.line.moves.last_mut() {
+                    Some(played) => played.nags.push(nag),
+                    None => {
+                        return Err(error(
+                            line,
+                            column,
+                            PgnErrorKind::Unexpected(format!("${nag}")),
+                        ))
+                    }
+                },
+                TokenKind::Comment(comment) => match branch.line.moves.last_mut() {
+                    Some(played) => played.comments.push(comment),
+                    None => branch.line.comments.push(comment),
+                },
+                // an alternative to the last move.
+                TokenKind::VariationStart => match branch.before_last {
+                    Some(state) => stack.push(Branch::new(state)),
+                    None => return Err(error(line, column, PgnErrorKind::Unexpected("(".into()))),
+                },
+                TokenKind::VariationEnd => {
+                    if stack.len() == 1 {
+                        return Err(error(line, column, PgnErrorKind::Unexpected(")".into())));
+                    }
+                    let variation = stack.pop().unwrap().line;
+                    let parent = stack.last_mut().unwrap();
+                    parent
+                        .line
+                        .moves
+                        .last_mut()
+                        .unwrap()
+                        .variations
+                        .push(variation);
+                }
+                TokenKind::Result(end) => {
+                    if stack.len() > 1 {
+                        return Err(error(line, column, PgnErrorKind::UnterminatedVariation));
+                    }
+                    result = Some(end);
+                    break;
+                }
+            }
+        }
+
+        if stack.len() > 1 {
+            let (line, column) = self.lexer.position();
+            return Err(error(line, column, PgnErrorKind::UnterminatedVariation));
+        }
+        let tag_result = tags
+            .iter()
+            .find(|(name, _)| name == "Result")
+            .and_then(|(_, value)| value.parse().ok());
+        Ok(Game {
+            tags,
+            start,
+            mainline: stack.pop().unwrap().line,
+            result: result.or(tag_result).unwrap_or_default(),
+        })
+    }
+
+    /// Skips the rest of a game that couldn't be read.
+    fn skip_game(&mut self) {
+        loop {
+            match self.peek() {
+                Ok(Some(Token {
+                    kind: TokenKind::Tag(..),
+                    ..
+                }))
+                | Ok(None) => return,
+                Err(PgnError {
+                    kind: PgnErrorKind::Io(_),
+                    ..
+                }) => {
+                    self.failed = true;
+                    return;
+                }
+                Err(_) => {}
+                Ok(Some(_)) => {
+                    if let Ok(Some(Token {
+                        kind: TokenKind::Result(_),
+                        ..
+                    })) = self.next_token()
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.peek() {
+            Ok(None) => return None,
+            Ok(Some(_)) => {}
+            Err(e) => {
+                self.skip_game();
+                return Some(Err(e));
+            }
+        }
+        match self.read_game() {
+            Ok(game) => game.map(Ok),
+            Err(e) => {
+                if matches!(e.kind, PgnErrorKind::Io(_)) {
+                    self.failed = true;
+                } else {
+                    self.skip_game();
+                }
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A line being read, and where it stands.
+struct Branch {
+    line: Line,
+    /// After the line's moves.
+    state: GameState,
+    /// Before the line's last move, where its variations start.
+    before_last: Option<GameState>,
+}
+
+impl Branch {
+    fn new(state: GameState) -> Self {
+        Self {
+            line: Line::default(),
+            state,
+            before_last: None,
+        }
+    }
+}
+
+fn error(line: usize, column: usize, kind: PgnErrorKind) -> PgnError {
+    PgnError { line, column, kind }
+}
+
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+enum TokenKind {
+    Tag(String, String),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Nag(u8),
+    /// As in `12.` or `12...`.
+    MoveNumber,
+    /// A move, and the annotation written right after it, as in `e4!`.
+    San(String, Option<u8>),
+    Result(GameResult),
+}
+
+/// Splits PGN into tokens, a line at a time.
+struct Lexer<R> {
+    reader: R,
+    chars: Vec<char>,
+    /// Into `chars`.
+    pos: usize,
+    /// Of `chars`, from 1.
+    line: usize,
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            chars: Vec::new(),
+            pos: 0,
+            line: 0,
+        }
+    }
+
+    /// The line and column of the next character.
+    fn position(&self) -> (usize, usize) {
+        (self.line.max(1), self.pos + 1)
+    }
+
+    fn peek_char(&mut self) -> Result<Option<char>, PgnError> {
+        while self.pos == self.chars.len() {
+            let mut bytes = Vec::new();
+            let read = self.reader.read_until(b'\n', &mut bytes).map_err(|e| {
+                let (line, column) = self.position();
+                error(line, column, e.into())
+            })?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            self.pos = 0;
+            // older databases aren't always UTF-8.
+            self.chars = String::from_utf8_lossy(&bytes).chars().collect();
+            // escaped lines, for other programs.
+            if self.chars.first() == Some(&'%') {
+                self.chars.clear();
+            }
+        }
+        Ok(Some(self.chars[self.pos]))
+    }
+
+    fn next_char(&mut self) -> Result<Option<char>, PgnError> {
+        let c = self.peek_char()?;
+        if c.is_some() {
+            self.pos += 1;
+        }
+        Ok(c)
+    }
+
+    /// Takes characters as long as `keep` holds, within the current line.
+    fn take_while(&mut self, keep: impl Fn(char) -> bool) -> Result<String, PgnError> {
+        let mut taken = String::new();
+        while let Some(c) = self.peek_char()? {
+            if !keep(c) {
+                break;
+            }
+            taken.push(c);
+            self.pos += 1;
+        }
+        Ok(taken)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnError> {
+        // and dots between a move number and its move.
+        while self
+            .peek_char()?
+            .is_some_and(|c| c.is_whitespace() || c == '.')
+        {
+            self.pos += 1;
+        }
+        let (line, column) = self.position();
+        let Some(c) = self.next_char()? else {
+            return Ok(None);
+        };
+        let fail = |kind| Err(error(line, column, kind));
+
+        let kind = match c {
+            '[' => self.tag(line, column)?,
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match self.next_char()? {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return fail(PgnErrorKind::UnterminatedComment),
+                    }
+                }
+                TokenKind::Comment(comment.trim().to_string())
+            }
+            ';' => {
+                let comment = self.take_while(|c| c != '\n')?;
+                TokenKind::Comment(comment.trim().to_string())
+            }
+            '(' => TokenKind::VariationStart,
+            ')' => TokenKind::VariationEnd,
+            '$' => {
+                let digits = self.take_while(|c| c.is_ascii_digit())?;
+                match digits.parse() {
+                    Ok(nag) => TokenKind::Nag(nag),
+                    Err(_) => return fail(PgnErrorKind::Unexpected(format!("${digits}"))),
+                }
+            }
+            '*' => TokenKind::Result(GameResult::Unfinished),
+            c if is_symbol_char(c) => {
+                let symbol = format!("{c}{}", self.take_while(is_symbol_char)?);
+                if let Ok(result) = symbol.parse() {
+                    TokenKind::Result(result)
+                } else if symbol.chars().all(|c| c.is_ascii_digit()) {
+                    // the dots after it, if any.
+                    self.take_while(|c| c == '.')?;
+                    TokenKind::MoveNumber
+                } else {
+                    let san = symbol.trim_end_matches(['!', '?']);
+                    let suffix = &symbol[san.len()..];
+                    if suffix.is_empty() {
+                        TokenKind::San(symbol, None)
+                    } else {
+                        let Some(nag) = suffix_nag(suffix) else {
+                            return fail(PgnErrorKind::Unexpected(symbol));
+                        };
+                        if san.is_empty() {
+                            TokenKind::Nag(nag)
+                        } else {
+                            TokenKind::San(san.to_string(), Some(nag))
+                        }
+                    }
+                }
+            }
+            c => return fail(PgnErrorKind::Unexpected(c.to_string())),
+        };
+        Ok(Some(Token { kind, line, column }))
+    }
+
+    /// `[Name "value"]`, after the `[`.
+    fn tag(&mut self, line: usize, column: usize) -> Result<TokenKind, PgnError> {
+        let invalid = |text: String| Err(error(line, column, PgnErrorKind::InvalidTag(text)));
+
+        self.take_while(|c| c == ' ' || c == '\t')?;
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_')?;
+        self.take_while(|c| c == ' ' || c == '\t')?;
+        if name.is_empty() || self.next_char()? != Some('"') {
+            return invalid(name);
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.next_char()? {
+                Some('"') => break,
+                Some('\\') => match self.next_char()? {
+                    Some(c) if c != '\n' => value.push(c),
+                    _ => return invalid(name),
+                },
+                Some('\n') | None => return invalid(name),
+                Some(c) => value.push(c),
+            }
+        }
+
+        self.take_while(|c| c == ' ' || c == '\t')?;
+        if self.next_char()? != Some(']') {
+            return invalid(name);
+        }
+        Ok(TokenKind::Tag(name, value))
+    }
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_+#=:-/!?".contains(c)
+}
+
+/// The NAG that a move suffix such as `!?` stands for.
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    Some(match suffix {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return None,
+    })
+}
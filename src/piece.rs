@@ -1,8 +1,10 @@
+use crate::attacks;
+use crate::bitboard::Bitboard;
 use crate::board::Board;
 use crate::board_position::{BoardIndex, BoardIndexDelta};
 use crate::castle_rights::CastleRights;
 use crate::en_passant_target::EnPassantTarget;
-use crate::piece_move::Move;
+use crate::piece_move::{Move, MoveSet};
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 #[repr(u8)]
@@ -16,6 +18,29 @@ pub enum BoardPieceKind {
 }
 
 impl BoardPieceKind {
+    pub fn char(self) -> char {
+        match self {
+            Self::Pawn => 'P',
+            Self::Rook => 'R',
+            Self::Knight => 'N',
+            Self::Bishop => 'B',
+            Self::Queen => 'Q',
+            Self::King => 'K',
+        }
+    }
+
+    /// The conventional worth of the piece in centipawns; the king's is only nominal.
+    pub fn value(self) -> i32 {
+        match self {
+            Self::Pawn => 100,
+            Self::Knight => 300,
+            Self::Bishop => 300,
+            Self::Rook => 500,
+            Self::Queen => 900,
+            Self::King => 20_000,
+        }
+    }
+
     pub fn of_color(self, color: PieceColor) -> BoardPiece {
         // Safety: only valid variants.
         unsafe { std::mem::transmute::<u8, BoardPiece>(self as u8 + 8 * (color as u8)) }
@@ -143,153 +168,197 @@ impl BoardPiece {
         position: BoardIndex,
         b: &Board,
         en_passant_target: Option<EnPassantTarget>,
-        _castle_rights: CastleRights,
+        castle_rights: CastleRights,
     ) -> Vec<Move> {
-        let self_color = self.color();
+        let mut moves = Vec::new();
+        self.push_moves_on_board(position, b, en_passant_target, castle_rights, &mut moves);
+        moves
+    }
 
-        let piece_at_delta = |d: BoardIndexDelta| {
-            let new_pos = position.checked_add(d);
-            match new_pos {
-                Some(new_pos) => (true, b.get_piece_at(new_pos)),
-                None => (false, None),
-            }
-        };
+    /// Like [`BoardPiece::moves_on_board`], but appends the moves to `moves`.
+    pub fn push_moves_on_board(
+        &self,
+        position: BoardIndex,
+        b: &Board,
+        en_passant_target: Option<EnPassantTarget>,
+        castle_rights: CastleRights,
+        moves: &mut Vec<Move>,
+    ) {
+        self.push_move_set_on_board(
+            position,
+            b,
+            en_passant_target,
+            castle_rights,
+            MoveSet::All,
+            moves,
+        );
+    }
 
-        let mut moves = Vec::new();
+    /// Like [`BoardPiece::push_moves_on_board`], but only the moves in `set`.
+    pub fn push_move_set_on_board(
+        &self,
+        position: BoardIndex,
+        b: &Board,
+        en_passant_target: Option<EnPassantTarget>,
+        castle_rights: CastleRights,
+        set: MoveSet,
+        moves: &mut Vec<Move>,
+    ) {
+        let self_color = self.color();
+        let own = b.pieces_of_color(self_color);
+        let enemies = b.pieces_of_color(self_color.other());
+        let occupied = own | enemies;
+        let (captures, quiets) = match set {
+            MoveSet::All => (true, true),
+            MoveSet::Captures => (true, false),
+            MoveSet::Quiets => (false, true),
+        };
+        // where pieces other than pawns may go.
+        let targets = match set {
+            MoveSet::All => !own,
+            MoveSet::Captures => enemies,
+            MoveSet::Quiets => !occupied,
+        };
 
-        let std_directional = |directions: &[(i8, i8)], moves: &mut Vec<Move>| {
-            for dir in directions.iter().copied() {
-                for i in 1..=7 {
-                    let delta = BoardIndexDelta::new(dir.0 * i, dir.1 * i);
-                    match piece_at_delta(delta) {
-                        (true, None) => moves.push(Move::from_delta(position, delta).unwrap()),
-                        (true, Some(p)) => {
-                            if p.color() != self_color {
-                                moves.push(Move::from_delta(position, delta).unwrap());
-                            }
-                            break;
-                        }
-                        (false, _) => break,
-                    }
-                }
-            }
+        let push_targets = |targets: Bitboard, moves: &mut Vec<Move>| {
+            moves.extend(targets.iter().map(|to| Move::Simple(position, to)));
         };
 
         let pawn = |direction: i8, moves: &mut Vec<Move>| {
-            if let (true, None) = piece_at_delta(BoardIndexDelta::delta_rank(direction)) {
-                moves.push(
-                    Move::from_delta(position, BoardIndexDelta::delta_rank(direction)).unwrap(),
-                );
+            // a pawn reaching the last rank has to promote.
+            let push_pawn_move = |moves: &mut Vec<Move>, to: BoardIndex| {
+                if to.rank() == 1 || to.rank() == 8 {
+                    moves.extend(Move::PROMOTION_KINDS.map(|promote_to| Move::Promotion {
+                        from: position,
+                        to,
+                        promote_to,
+                    }));
+                } else {
+                    moves.push(Move::Simple(position, to));
+                }
+            };
+
+            let single = position.checked_add(BoardIndexDelta::delta_rank(direction));
+            if let Some(single) = single.filter(|to| !occupied.contains(*to)) {
+                // promotions count as captures.
+                let promotes = single.rank() == 1 || single.rank() == 8;
+                if promotes && captures || !promotes && quiets {
+                    push_pawn_move(moves, single);
+                }
 
                 // check for starting position.
-                if direction == 1 && position.rank() == 2 || direction == -1 && position.rank() == 7
+                if quiets
+                    && (direction == 1 && position.rank() == 2
+                        || direction == -1 && position.rank() == 7)
                 {
-                    if let (true, None) = piece_at_delta(BoardIndexDelta::delta_rank(2 * direction))
-                    {
-                        moves.push(
-                            Move::from_delta(position, BoardIndexDelta::delta_rank(2 * direction))
-                                .unwrap(),
-                        );
+                    let double = single + BoardIndexDelta::delta_rank(direction);
+                    if !occupied.contains(double) {
+                        moves.push(Move::Simple(position, double));
                     }
                 }
             }
 
-            for delta_file in [-1, 1] {
-                if let (true, Some(p)) = piece_at_delta(BoardIndexDelta::new(direction, delta_file))
-                {
-                    if p.color() != self.color() {
-                        moves.push(
-                            Move::from_delta(position, BoardIndexDelta::new(direction, delta_file))
-                                .unwrap(),
-                        );
-                    }
-                }
+            if !captures {
+                return;
             }
 
-            // en passant
-            if let Some(ept) = en_passant_target {
-                let delta = BoardIndexDelta::new(direction, 1);
-                if ept.0 == position + delta {
-                    moves.push(Move::EnPassant {
-                        en_passant_target: ept,
-                        pawn_being_captured: position + BoardIndexDelta::delta_file(1),
-                        pawn_doing_en_passant: position,
-                    })
-                }
+            let attacks = attacks::pawn_attacks(self_color, position);
+            for to in attacks & enemies {
+                push_pawn_move(moves, to);
+            }
 
-                let delta = BoardIndexDelta::new(direction, -1);
-                if ept.0 == position + delta {
+            // en passant
+            if let Some(ept) = en_passant_target.filter(|ept| attacks.contains(ept.0)) {
+                // the captured pawn is beside us, on the file of the target.
+                let pawn_being_captured = position
+                    + BoardIndexDelta::delta_file(ept.0.file() as i8 - position.file() as i8);
+                if b.get_piece_at(pawn_being_captured)
+                    == Some(BoardPieceKind::Pawn.of_color(self_color.other()))
+                {
                     moves.push(Move::EnPassant {
                         en_passant_target: ept,
-                        pawn_being_captured: position + BoardIndexDelta::delta_file(-1),
+                        pawn_being_captured,
                         pawn_doing_en_passant: position,
                     })
                 }
             }
         };
 
-        let direct = |dirs: &[BoardIndexDelta], moves: &mut Vec<Move>| {
-            for delta in dirs {
-                match piece_at_delta(*delta) {
-                    (true, None) => moves.push(Move::from_delta(position, *delta).unwrap()),
-                    (true, Some(p)) if p.color() != self_color => {
-                        moves.push(Move::from_delta(position, *delta).unwrap())
-                    }
-                    _ => {}
+        let castle = |moves: &mut Vec<Move>| {
+            let (home, king_side, queen_side) = match self_color {
+                PieceColor::White => (
+                    0,
+                    CastleRights::WHITE_KING_SIDE,
+                    CastleRights::WHITE_QUEEN_SIDE,
+                ),
+                PieceColor::Black => (
+                    56,
+                    CastleRights::BLACK_KING_SIDE,
+                    CastleRights::BLACK_QUEEN_SIDE,
+                ),
+            };
+            let at_file = |file: u8| unsafe { BoardIndex::new_unchecked(home + file) };
+
+            if position != at_file(4) || !quiets {
+                return;
+            }
+
+            let opponent = self_color.other();
+            let own_rook = BoardPieceKind::Rook.of_color(self_color);
+
+            // (right, rook file, rook destination file, king destination file,
+            //  files that must be empty)
+            let sides: [(CastleRights, u8, u8, u8, &[u8]); 2] = [
+                (king_side, 7, 5, 6, &[5, 6]),
+                (queen_side, 0, 3, 2, &[1, 2, 3]),
+            ];
+
+            for (right, rook_file, rook_to_file, king_to_file, must_be_empty) in sides {
+                if !castle_rights.has_rights(right)
+                    || b.get_piece_at(at_file(rook_file)) != Some(own_rook)
+                    || must_be_empty
+                        .iter()
+                        .any(|&file| occupied.contains(at_file(file)))
+                {
+                    continue;
+                }
+
+                // the king may not castle out of, through, or into check.
+                let (lo, hi) = (king_to_file.min(4), king_to_file.max(4));
+                if (lo..=hi).any(|file| b.is_square_attacked(at_file(file), opponent)) {
+                    continue;
                 }
+
+                moves.push(Move::Castle {
+                    rook_from: at_file(rook_file),
+                    rook_to: at_file(rook_to_file),
+                    king_from: position,
+                    king_to: at_file(king_to_file),
+                });
             }
         };
 
-        match self {
-            BoardPiece::WhitePawn => pawn(1, &mut moves),
-            BoardPiece::BlackPawn => pawn(-1, &mut moves),
-            BoardPiece::WhiteRook | BoardPiece::BlackRook => {
-                std_directional(&[(-1, 0), (1, 0), (0, -1), (0, 1)], &mut moves);
+        match self.kind() {
+            BoardPieceKind::Pawn => match self_color {
+                PieceColor::White => pawn(1, moves),
+                PieceColor::Black => pawn(-1, moves),
+            },
+            BoardPieceKind::Rook => {
+                push_targets(attacks::rook_attacks(position, occupied) & targets, moves)
+            }
+            BoardPieceKind::Knight => {
+                push_targets(attacks::knight_attacks(position) & targets, moves)
+            }
+            BoardPieceKind::Bishop => {
+                push_targets(attacks::bishop_attacks(position, occupied) & targets, moves)
             }
-            BoardPiece::WhiteKnight | BoardPiece::BlackKnight => direct(
-                &[
-                    BoardIndexDelta::new(-2, -1),
-                    BoardIndexDelta::new(-2, 1),
-                    BoardIndexDelta::new(-1, -2),
-                    BoardIndexDelta::new(-1, 2),
-                    BoardIndexDelta::new(1, -2),
-                    BoardIndexDelta::new(1, 2),
-                    BoardIndexDelta::new(2, -1),
-                    BoardIndexDelta::new(2, 1),
-                ],
-                &mut moves,
-            ),
-            BoardPiece::WhiteBishop | BoardPiece::BlackBishop => {
-                std_directional(&[(-1, -1), (-1, 1), (1, -1), (1, 1)], &mut moves)
+            BoardPieceKind::Queen => {
+                push_targets(attacks::queen_attacks(position, occupied) & targets, moves)
+            }
+            BoardPieceKind::King => {
+                push_targets(attacks::king_attacks(position) & targets, moves);
+                castle(moves);
             }
-            BoardPiece::WhiteQueen | BoardPiece::BlackQueen => std_directional(
-                &[
-                    (-1, 0),
-                    (1, 0),
-                    (0, -1),
-                    (0, 1),
-                    (-1, -1),
-                    (-1, 1),
-                    (1, -1),
-                    (1, 1),
-                ],
-                &mut moves,
-            ),
-            BoardPiece::WhiteKing | BoardPiece::BlackKing => direct(
-                &[
-                    BoardIndexDelta::new(-1, -1),
-                    BoardIndexDelta::new(-1, 0),
-                    BoardIndexDelta::new(-1, 1),
-                    BoardIndexDelta::new(0, -1),
-                    BoardIndexDelta::new(0, 1),
-                    BoardIndexDelta::new(1, -1),
-                    BoardIndexDelta::new(1, 0),
-                    BoardIndexDelta::new(1, 1),
-                ],
-                &mut moves,
-            ),
         }
-
-        moves
     }
 }
@@ -3,7 +3,7 @@ use std::fmt::Formatter;
 use crate::board_position::{BoardIndex, BoardIndexDelta};
 use crate::castle_rights::CastleRights;
 use crate::en_passant_target::EnPassantTarget;
-use crate::piece::{BoardPiece, PieceColor};
+use crate::piece::{BoardPiece, BoardPieceKind, PieceColor};
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Move {
@@ -19,8 +19,35 @@ pub enum Move {
         king_from: BoardIndex,
         king_to: BoardIndex,
     },
+    Promotion {
+        from: BoardIndex,
+        to: BoardIndex,
+        promote_to: BoardPieceKind,
+    },
+}
+
+
+/// Which moves to generate, so that captures can be generated and searched before the
+/// rest.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum MoveSet {
+    #[default]
+    All,
+    /// Captures, including en passant, and promotions.
+    Captures,
+    /// Every other move, including castling.
+    Quiets,
 }
 
+/// How castling is written in UCI notation.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum CastlingNotation {
+    /// The king moves two squares, e.g. `e1g1`.
+    #[default]
+    Standard,
+    /// The king takes its own rook, e.g. `e1h1`, as Chess960 GUIs expect.
+    Chess960,
+}
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct MoveInfo {
@@ -29,6 +56,7 @@ pub struct MoveInfo {
     pub(crate) captured: Option<BoardPiece>,
     pub(crate) pawn_advanced: bool,
     pub(crate) new_en_passant_target: Option<EnPassantTarget>,
+    pub(crate) promoted_to: Option<BoardPiece>,
 }
 
 impl MoveInfo {
@@ -40,14 +68,73 @@ impl MoveInfo {
             revoked_castle_rights: self.revoked_castle_rights | mi2.revoked_castle_rights,
             pawn_advanced: self.pawn_advanced || mi2.pawn_advanced,
             new_en_passant_target: self.new_en_passant_target.or(mi2.new_en_passant_target),
+            promoted_to: self.promoted_to.or(mi2.promoted_to),
         }
     }
 }
 
 impl Move {
+    /// The pieces a pawn may promote to, in the order they are generated.
+    pub const PROMOTION_KINDS: [BoardPieceKind; 4] = [
+        BoardPieceKind::Queen,
+        BoardPieceKind::Rook,
+        BoardPieceKind::Bishop,
+        BoardPieceKind::Knight,
+    ];
+
     pub fn from_delta(pos: BoardIndex, delta: BoardIndexDelta) -> Option<Self> {
         Some(Self::Simple(pos, pos.checked_add(delta)?))
     }
+
+    /// Where the moving piece starts; the king's position for castling.
+    pub fn start(self) -> BoardIndex {
+        match self {
+            Self::Simple(start, _) => start,
+            Self::EnPassant {
+                pawn_doing_en_passant,
+                ..
+            } => pawn_doing_en_passant,
+            Self::Castle { king_from, .. } => king_from,
+            Self::Promotion { from, .. } => from,
+        }
+    }
+
+    /// Where the moving piece ends up; the king's destination for castling.
+    pub fn end(self) -> BoardIndex {
+        match self {
+            Self::Simple(_, end) => end,
+            Self::EnPassant {
+                en_passant_target, ..
+            } => en_passant_target.0,
+            Self::Castle { king_to, .. } => king_to,
+            Self::Promotion { to, .. } => to,
+        }
+    }
+
+    /// The move in UCI's long algebraic notation, e.g. `e2e4`, `e7e8q` or `e1g1`.
+    pub fn to_uci(self) -> String {
+        self.to_uci_with(CastlingNotation::Standard)
+    }
+
+    pub fn to_uci_with(self, castling: CastlingNotation) -> String {
+        let end = match (self, castling) {
+            (Self::Castle { rook_from, .. }, CastlingNotation::Chess960) => rook_from,
+            _ => self.end(),
+        };
+
+        let mut uci = format!("{}{}", self.start().algebraic(), end.algebraic());
+        if let Some(promote_to) = self.promotion() {
+            uci.push(promote_to.char().to_ascii_lowercase());
+        }
+        uci
+    }
+
+    pub fn promotion(self) -> Option<BoardPieceKind> {
+        match self {
+            Self::Promotion { promote_to, .. } => Some(promote_to),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for Move {
@@ -78,6 +165,13 @@ impl fmt::Debug for Move {
                     en_passant_target.0
                 )
             }
+            Self::Promotion {
+                from,
+                to,
+                promote_to,
+            } => {
+                write!(f, "{from:?} -> {to:?}={}", promote_to.char())
+            }
         }
     }
 }
@@ -98,7 +192,14 @@ impl fmt::Display for Move {
                 pawn_doing_en_passant,
                 ..
             } => {
-                write!(f, "{pawn_doing_en_passant:?} -> {:?}", en_passant_target.0)
+                write!(f, "{pawn_doing_en_passant} -> {}", en_passant_target.0)
+            }
+            Self::Promotion {
+                from,
+                to,
+                promote_to,
+            } => {
+                write!(f, "{from} -> {to}={}", promote_to.char())
             }
         }
     }
@@ -0,0 +1,206 @@
+//! Standard Algebraic Notation, e.g. `Nbd2`, `exd6`, `e8=Q+`, `O-O-O#`.
+
+use crate::board_position::BoardPosition;
+use crate::game_state::GameState;
+use crate::piece::BoardPieceKind;
+use crate::piece_move::Move;
+
+impl GameState {
+    /// Writes `m`, which must be legal in this position, in SAN.
+    pub fn move_to_san(&self, m: Move) -> String {
+        let mut san = match m {
+            Move::Castle {
+                king_from, king_to, ..
+            } if king_to.file() > king_from.file() => "O-O".to_string(),
+            Move::Castle { .. } => "O-O-O".to_string(),
+            _ => {
+                let kind = self.moving_kind(m);
+                let (start, end) = (m.start(), m.end());
+                let capture =
+                    matches!(m, Move::EnPassant { .. }) || self.board().get_piece_at(end).is_some();
+                let mut san = String::new();
+
+                if kind == BoardPieceKind::Pawn {
+                    if capture {
+                        san.push(start.algebraic().chars().next().unwrap());
+                    }
+                } else {
+                    san.push(kind.char());
+
+                    // other pieces of the same kind that could go to the same square.
+                    let rivals = self
+                        .legal_moves()
+                        .filter(|other| {
+                            other.end() == end
+                                && other.start() != start
+                                && self.moving_kind(*other) == kind
+                        })
+                        .map(Move::start)
+                        .collect::<Vec<_>>();
+
+                    let (file, rank) = {
+                        let name = start.algebraic();
+                        let mut chars = name.chars();
+                        (chars.next().unwrap(), chars.next().unwrap())
+                    };
+                    // prefer the file, then the rank, and only if neither is enough, both.
+                    if !rivals.is_empty() {
+                        if rivals.iter().all(|r| r.file() != start.file()) {
+                            san.push(file);
+                        } else if rivals.iter().all(|r| r.rank() != start.rank()) {
+                            san.push(rank);
+                        } else {
+                            san.push(file);
+                            san.push(rank);
+                        }
+                    }
+                }
+
+                if capture {
+                    san.push('x');
+                }
+                san.push_str(&end.algebraic());
+
+                if let Some(promote_to) = m.promotion() {
+                    san.push('=');
+                    san.push(promote_to.char());
+                }
+
+                san
+            }
+        };
+
+        let mut after = *self;
+        after.make_move(m);
+        if after.is_check() {
+            san.push(if after.legal_moves().next().is_none() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+
+        san
+    }
+
+    /// Finds the legal move `san` stands for.
+    ///
+    /// Check and mate suffixes, as well as annotations like `!?`, are accepted but not
+    /// verified.
+    pub fn parse_san(&self, san: &str) -> Result<Move, ParseSanError> {
+        let invalid = || ParseSanError::InvalidSyntax(san.to_string());
+
+        let trimmed = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        if trimmed.is_empty() {
+            return Err(invalid());
+        }
+
+        let pick = |candidates: Vec<Move>| match candidates.as_slice() {
+            [] => Err(ParseSanError::IllegalMove(san.to_string())),
+            [m] => Ok(*m),
+            _ => Err(ParseSanError::AmbiguousMove(san.to_string())),
+        };
+
+        let castle_side = match trimmed {
+            "O-O" | "0-0" => Some(true),
+            "O-O-O" | "0-0-0" => Some(false),
+            _ => None,
+        };
+        if let Some(king_side) = castle_side {
+            return pick(
+                self.legal_moves()
+                    .filter(|m| match m {
+                        Move::Castle {
+                            king_from, king_to, ..
+                        } => (king_to.file() > king_from.file()) == king_side,
+                        _ => false,
+                    })
+                    .collect(),
+            );
+        }
+
+        let mut rest = trimmed;
+
+        let kind = match rest.chars().next().and_then(piece_kind_from_char) {
+            Some(kind) => {
+                rest = &rest[1..];
+                kind
+            }
+            None => BoardPieceKind::Pawn,
+        };
+
+        // a promotion is written `e8=Q`, though `e8Q` is common enough to accept too.
+        let promote_to = match rest.chars().last().and_then(piece_kind_from_char) {
+            Some(promote_to) if kind == BoardPieceKind::Pawn => {
+                rest = rest[..rest.len() - 1].trim_end_matches('=');
+                Some(promote_to)
+            }
+            _ => None,
+        };
+
+        if rest.len() < 2 || !rest.is_char_boundary(rest.len() - 2) {
+            return Err(invalid());
+        }
+        let (qualifiers, destination) = rest.split_at(rest.len() - 2);
+        let destination = destination
+            .parse::<BoardPosition>()
+            .map_err(|_| invalid())?
+            .to_index();
+        let qualifiers = qualifiers.strip_suffix('x').unwrap_or(qualifiers);
+
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in qualifiers.chars() {
+            match c {
+                'a'..='h' if from_file.is_none() && from_rank.is_none() => {
+                    from_file = Some(c as u8 - b'a' + 1)
+                }
+                '1'..='8' if from_rank.is_none() => from_rank = Some(c as u8 - b'0'),
+                _ => return Err(invalid()),
+            }
+        }
+
+        pick(
+            self.legal_moves()
+                .filter(|m| {
+                    !matches!(m, Move::Castle { .. })
+                        && m.end() == destination
+                        && m.promotion() == promote_to
+                        && self.moving_kind(*m) == kind
+                        && from_file.is_none_or(|file| m.start().file() == file)
+                        && from_rank.is_none_or(|rank| m.start().rank() == rank)
+                })
+                .collect(),
+        )
+    }
+
+    fn moving_kind(&self, m: Move) -> BoardPieceKind {
+        self.board()
+            .get_piece_at(m.start())
+            .expect("a move starts on an occupied square")
+            .kind()
+    }
+}
+
+fn piece_kind_from_char(c: char) -> Option<BoardPieceKind> {
+    let kind = match c {
+        'N' => BoardPieceKind::Knight,
+        'B' => BoardPieceKind::Bishop,
+        'R' => BoardPieceKind::Rook,
+        'Q' => BoardPieceKind::Queen,
+        'K' => BoardPieceKind::King,
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseSanError {
+    #[error("{0:?} is not valid SAN")]
+    InvalidSyntax(String),
+    #[error("{0:?} is not a legal move")]
+    IllegalMove(String),
+    #[error("{0:?} is ambiguous")]
+    AmbiguousMove(String),
+}
@@ -0,0 +1,669 @@
+//! Picking a move: negamax alpha-beta search with iterative deepening.
+
+use crate::book::{PolyglotBook, Selection};
+use crate::eval::{self, Weights};
+use crate::game_state::GameState;
+use crate::move_picker::{MoveOrdering, MovePicker};
+use crate::piece::BoardPieceKind;
+use crate::piece_move::Move;
+#[cfg(feature = "syzygy")]
+use crate::tablebase::{Tablebase, Wdl};
+use crate::time_manager::TimeManager;
+use crate::transposition::{Bound, TranspositionTable};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// When to stop searching, as given by UCI's `go`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub move_time: Option<Duration>,
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    pub moves_to_go: Option<u32>,
+    /// Search until told to stop.
+    pub infinite: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Score {
+    /// From the side to move's point of view.
+    Centipawns(i32),
+    /// Mate in this many moves, negative if the side to move is getting mated.
+    Mate(i32),
+}
+
+/// The outcome of a search, or of one of its iterations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// `None` only if there are no legal moves.
+    pub best_move: Option<Move>,
+    pub score: Score,
+    /// The expected line of play, starting with `best_move`.
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    /// The last fully searched depth.
+    pub depth: u32,
+    pub time: Duration,
+    /// How full the transposition table is, in permille.
+    pub hash_full: u32,
+}
+
+/// The deepest iteration a search goes to.
+pub const MAX_DEPTH: u32 = 64;
+
+const MATE: i32 = 32_000;
+/// Scores beyond this are mates, found at most `MAX_PLY` plies away.
+pub(crate) const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+const MAX_PLY: u32 = 128;
+const INFINITY: i32 = MATE + 1;
+/// The score of a tablebase win, below any mate the search can find.
+#[cfg(feature = "syzygy")]
+const TB_WIN: i32 = MATE_BOUND - MAX_PLY as i32;
+
+/// How many nodes to search between looking at the clock and the stop flag.
+const CHECK_EVERY: u64 = 1024;
+
+type Report = Box<dyn FnMut(&SearchResult) + Send>;
+
+/// Searches positions, one at a time, on as many threads as asked for.
+pub struct Searcher {
+    stop: Arc<AtomicBool>,
+    /// Zobrist keys of the positions played before the root, oldest first.
+    history: Vec<u64>,
+    report: Option<Report>,
+    weights: Weights,
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    book: Option<Arc<PolyglotBook>>,
+    book_selection: Selection,
+    #[cfg(feature = "syzygy")]
+    tablebase: Option<Arc<Tablebase>>,
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Searcher {
+    /// The most threads a search runs on, however many are asked for.
+    pub const MAX_THREADS: usize = 256;
+
+    pub fn new() -> Self {
+        Self {
+            stop: Arc::new(AtomicBool::new(false)),
+            history: Vec::new(),
+            report: None,
+            weights: Weights::default(),
+            table: Arc::default(),
+            threads: 1,
+            book: None,
+            book_selection: Selection::default(),
+            #[cfg(feature = "syzygy")]
+            tablebase: None,
+        }
+    }
+
+    /// A flag that stops the search as soon as it is set, from any thread.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// The positions played before the one to search, oldest first, so that
+    /// repetitions of them are scored as draws.
+    pub fn set_history(&mut self, history: &[GameState]) {
+        self.history = history.iter().map(GameState::zobrist).collect();
+    }
+
+    /// The weights to evaluate positions with.
+    pub fn set_weights(&mut self, weights: Weights) {
+        self.weights = weights;
+    }
+
+    /// The transposition table to use, which may be shared with other searchers and
+    /// kept from one search to the next.
+    pub fn set_table(&mut self, table: Arc<TranspositionTable>) {
+        self.table = table;
+    }
+
+    /// How many threads to search with, from one to `MAX_THREADS`. All but the first
+    /// are helpers that search the same position, sharing what they find through the
+    /// transposition table (Lazy SMP).
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.clamp(1, Self::MAX_THREADS);
+    }
+
+    /// An opening book whose moves are played without searching, picked by `selection`.
+    pub fn set_book(&mut self, book: Option<Arc<PolyglotBook>>, selection: Selection) {
+        self.book = book;
+        self.book_selection = selection;
+    }
+
+    /// Endgame tablebases to look positions up in: the root's best move is played
+    /// right away, and positions reached by a capture or pawn move are scored as won,
+    /// drawn or lost without searching further.
+    #[cfg(feature = "syzygy")]
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    /// Called with the result of each completed iteration.
+    pub fn on_iteration(&mut self, report: impl FnMut(&SearchResult) + Send + 'static) {
+        self.report = Some(Box::new(report));
+    }
+
+    /// Searches ever deeper until `limits` are reached or the search is stopped,
+    /// returning the result of the deepest completed iteration of any thread.
+    pub fn search(&mut self, state: &GameState, limits: Limits) -> SearchResult {
+        if let Some(result) = self.book_move(state, &limits) {
+            return result;
+        }
+        #[cfg(feature = "syzygy")]
+        if let Some(result) = self.probe_root(state, &limits) {
+            if let Some(report) = &mut self.report {
+                report(&result);
+            }
+            return result;
+        }
+
+        self.table.new_search();
+        let shared = Shared {
+            stop: &self.stop,
+            done: AtomicBool::new(false),
+            nodes: AtomicU64::new(0),
+            node_limit: limits.nodes,
+            history: &self.history,
+            weights: &self.weights,
+            table: &self.table,
+            #[cfg(feature = "syzygy")]
+            tablebase: self.tablebase.as_deref(),
+        };
+        let time = TimeManager::new(state, &limits);
+        let report = &mut self.report;
+
+        std::thread::scope(|scope| {
+            let helpers = (1..self.threads)
+                .map(|id| {
+                    let (shared, limits) = (&shared, &limits);
+                    scope.spawn(move || {
+                        Worker::new(shared, id, TimeManager::unlimited())
+                            .iterate(state, limits, &mut None)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut result = Worker::new(&shared, 0, time).iterate(state, &limits, report);
+            shared.done.store(true, Ordering::Relaxed);
+
+            let mut nodes = result.nodes;
+            for helper in helpers {
+                let helper = helper.join().expect("search thread panicked");
+                nodes += helper.nodes;
+                // a helper may have finished an iteration the main thread didn't get to.
+                if helper.depth > result.depth && helper.best_move.is_some() {
+                    result = SearchResult {
+                        time: result.time,
+                        ..helper
+                    };
+                }
+            }
+            result.nodes = nodes;
+            result.hash_full = self.table.hash_full();
+            result
+        })
+    }
+
+    /// A move from the book, if it has the root position. Infinite searches are for
+    /// analysis, which wants a search rather than a book move.
+    fn book_move(&self, state: &GameState, limits: &Limits) -> Option<SearchResult> {
+        let book = self.book.as_deref().filter(|_| !limits.infinite)?;
+        let m = book.pick(state, self.book_selection)?;
+        Some(SearchResult {
+            best_move: Some(m),
+            score: Score::Centipawns(0),
+            pv: vec![m],
+            nodes: 0,
+            depth: 0,
+            time: Duration::ZERO,
+            hash_full: self.table.hash_full(),
+        })
+    }
+
+    /// The tablebases' best move, if they have the root position. Infinite searches
+    /// go on as usual, as they are stopped from outside.
+    #[cfg(feature = "syzygy")]
+    fn probe_root(&self, state: &GameState, limits: &Limits) -> Option<SearchResult> {
+        let tablebase = self.tablebase.as_deref()?;
+        if limits.infinite || !tablebase.covers(state) {
+            return None;
+        }
+        let start = std::time::Instant::now();
+        let best = *tablebase.rank_root_moves(state).ok()?.first()?;
+        Some(SearchResult {
+            best_move: Some(best.m),
+            score: Score::Centipawns(tb_score(best.wdl, 0)),
+            pv: vec![best.m],
+            nodes: 1,
+            depth: 1,
+            time: start.elapsed(),
+            hash_full: self.table.hash_full(),
+        })
+    }
+}
+
+/// What the threads of one search share.
+struct Shared<'a> {
+    /// Set from outside to stop the search.
+    stop: &'a AtomicBool,
+    /// Set once the main thread is done, to stop the helpers.
+    done: AtomicBool,
+    /// Nodes searched by all threads, give or take the last `CHECK_EVERY` of each.
+    nodes: AtomicU64,
+    node_limit: Option<u64>,
+    history: &'a [u64],
+    weights: &'a Weights,
+    table: &'a TranspositionTable,
+    #[cfg(feature = "syzygy")]
+    tablebase: Option<&'a Tablebase>,
+}
+
+/// The search of a single thread: the main one (0), whose result counts and whose
+/// clock ends the search, or one of its helpers.
+struct Worker<'a> {
+    shared: &'a Shared<'a>,
+    id: usize,
+    /// Zobrist keys of the positions before the one being searched, oldest first.
+    history: Vec<u64>,
+    ordering: MoveOrdering,
+    /// The moves from the root to the position being searched.
+    played: Vec<Move>,
+    nodes: u64,
+    time: TimeManager,
+    /// The depth of the current iteration.
+    depth: u32,
+    aborted: bool,
+}
+
+impl<'a> Worker<'a> {
+    fn new(shared: &'a Shared<'a>, id: usize, time: TimeManager) -> Self {
+        Self {
+            shared,
+            id,
+            history: shared.history.to_vec(),
+            ordering: MoveOrdering::new(),
+            played: Vec::new(),
+            nodes: 0,
+            time,
+            depth: 0,
+            aborted: false,
+        }
+    }
+
+    fn is_main(&self) -> bool {
+        self.id == 0
+    }
+
+    /// Iterative deepening, reporting each completed iteration to `report`.
+    fn iterate(
+        mut self,
+        state: &GameState,
+        limits: &Limits,
+        report: &mut Option<Report>,
+    ) -> SearchResult {
+        let mut root = *state;
+        let legal_moves = root.legal_moves().count();
+        let mut result = SearchResult {
+            best_move: root.legal_moves().next(),
+            score: Score::Centipawns(0),
+            pv: Vec::new(),
+            nodes: 0,
+            depth: 0,
+            time: Duration::ZERO,
+            hash_full: 0,
+        };
+        if result.best_move.is_none() {
+            result.score = if root.is_check() {
+                Score::Mate(0)
+            } else {
+                Score::Centipawns(0)
+            };
+            return result;
+        }
+
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        for depth in 1..=max_depth {
+            self.depth = depth;
+            if self.should_stop() {
+                break;
+            }
+            if self.skips(depth) {
+                continue;
+            }
+
+            let mut pv = Vec::new();
+            let score = self.negamax(
+                &mut root, depth, 0, -INFINITY, INFINITY, &result.pv, &mut pv,
+            );
+            if self.aborted {
+                break;
+            }
+
+            result = SearchResult {
+                best_move: pv.first().copied(),
+                score: to_score(score),
+                pv,
+                nodes: self.total_nodes(),
+                depth,
+                time: self.time.elapsed(),
+                hash_full: self.shared.table.hash_full(),
+            };
+            if let Some(report) = report {
+                report(&result);
+            }
+
+            if !self.is_main() {
+                continue;
+            }
+            // nothing more to learn once a forced mate is found.
+            if score.abs() >= MATE_BOUND && !limits.infinite {
+                break;
+            }
+            if let Some(best_move) = result.best_move {
+                self.time.iteration_done(best_move, score);
+            }
+            if !self.time.should_continue(legal_moves) {
+                break;
+            }
+        }
+
+        result.nodes = self.nodes;
+        result.time = self.time.elapsed();
+        result
+    }
+
+    /// Helpers skip some depths, each in its own pattern, so that they don't all
+    /// search the same tree in step with the main thread.
+    fn skips(&self, depth: u32) -> bool {
+        const SKIP_SIZE: [u32; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+        const SKIP_PHASE: [u32; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+        if self.is_main() {
+            return false;
+        }
+        let i = (self.id - 1) % SKIP_SIZE.len();
+        (depth + SKIP_PHASE[i]) / SKIP_SIZE[i] % 2 == 1
+    }
+
+    /// Scores `state` from the side to move's point of view, filling `pv` with the
+    /// line that leads there. `previous_pv` is searched first if it passes through here.
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        state: &mut GameState,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+        previous_pv: &[Move],
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.count_node();
+        if self.aborted {
+            return 0;
+        }
+
+        if ply > 0 && self.is_draw(state) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(state, ply, alpha, beta);
+        }
+
+        let key = state.zobrist();
+        let entry = self.shared.table.probe(key, ply);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            // scores strictly inside the window are searched again, to get their pv.
+            let cuts = match entry.bound {
+                Bound::Exact => entry.score <= alpha || entry.score >= beta,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if cuts {
+                return entry.score;
+            }
+        }
+
+        // right after a capture or pawn move, the tables know the result for sure.
+        #[cfg(feature = "syzygy")]
+        if let Some(tablebase) = self.shared.tablebase {
+            if ply > 0 && state.half_move_clock() == 0 && tablebase.covers(state) {
+                if let Ok(wdl) = tablebase.probe_wdl(state) {
+                    return tb_score(wdl, ply);
+                }
+            }
+        }
+
+        // the best move of the previous iteration is likely still the best, and so is
+        // the one found here before.
+        let hash_move = previous_pv
+            .first()
+            .copied()
+            .or_else(|| entry.and_then(|entry| entry.best_move(state)));
+        let previous = self.played.last().copied();
+        let mut picker = MovePicker::new(&self.ordering, ply, hash_move, previous);
+
+        let mut best_move = None;
+        let mut searched = 0;
+        let mut quiets_tried = Vec::new();
+        let mut child_pv = Vec::new();
+        while let Some(m) = picker.next(state, &self.ordering) {
+            let child_previous_pv = match previous_pv {
+                [first, rest @ ..] if searched == 0 && *first == m => rest,
+                _ => &[],
+            };
+            let quiet = !state.board().is_capture(m) && m.promotion().is_none();
+            searched += 1;
+
+            let undo = state.make_move(m);
+            self.history.push(key);
+            self.played.push(m);
+            child_pv.clear();
+            let score = -self.negamax(
+                state,
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+                child_previous_pv,
+                &mut child_pv,
+            );
+            self.played.pop();
+            self.history.pop();
+            state.unmake_move(m, undo);
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                best_move = Some(m);
+                pv.clear();
+                pv.push(m);
+                pv.extend_from_slice(&child_pv);
+
+                if alpha >= beta {
+                    if quiet {
+                        let color = state.next_move();
+                        self.ordering
+                            .update(color, ply, depth, m, previous, &quiets_tried);
+                    }
+                    break;
+                }
+            }
+            if quiet {
+                quiets_tried.push(m);
+            }
+        }
+
+        if searched == 0 {
+            return if state.is_check() {
+                -MATE + ply as i32
+            } else {
+                0
+            };
+        }
+
+        let bound = if alpha >= beta {
+            Bound::Lower
+        } else if best_move.is_some() {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.shared
+            .table
+            .store(key, ply, depth, bound, alpha, best_move);
+
+        alpha
+    }
+
+    /// Searches only captures and promotions (or every move when in check), until the
+    /// position is quiet enough for its evaluation to be trusted.
+    fn quiescence(&mut self, state: &mut GameState, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        self.count_node();
+        if self.aborted {
+            return 0;
+        }
+
+        let in_check = state.is_check();
+        if !in_check {
+            // the side to move may also just not capture: stand pat.
+            let stand_pat = eval::evaluate(state, self.shared.weights);
+            if stand_pat >= beta || ply >= MAX_PLY {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+        }
+
+        let moves = state.legal_moves().collect::<Vec<_>>();
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        if ply >= MAX_PLY {
+            return eval::evaluate(state, self.shared.weights);
+        }
+
+        let board = state.board();
+        let mut moves = moves
+            .into_iter()
+            .filter(|&m| {
+                in_check || board.is_capture(m) || m.promotion() == Some(BoardPieceKind::Queen)
+            })
+            .map(|m| (m, board.see(m)))
+            // out of check, exchanges that lose material are not worth looking at.
+            .filter(|&(_, see)| in_check || see >= 0)
+            .collect::<Vec<_>>();
+        moves.sort_by_key(|&(_, see)| std::cmp::Reverse(see));
+
+        for (m, _) in moves {
+            let undo = state.make_move(m);
+            let score = -self.quiescence(state, ply + 1, -beta, -alpha);
+            state.unmake_move(m, undo);
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        alpha
+    }
+
+    /// Draws by the fifty-move rule, repetition or lack of material. A single
+    /// repetition is enough, since whatever was good the first time can be repeated.
+    fn is_draw(&self, state: &GameState) -> bool {
+        if state.is_insufficient_material() {
+            return true;
+        }
+        // mate on the move that reaches the limit still counts.
+        if state.half_move_clock() >= 100 {
+            return !state.is_check() || state.legal_moves().next().is_some();
+        }
+
+        let key = state.zobrist();
+        // a position can only repeat since the last capture or pawn move, with the same
+        // side to move.
+        self.history
+            .iter()
+            .rev()
+            .take(state.half_move_clock() as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&k| k == key)
+    }
+
+    /// Counts a node, and every so often checks whether to stop.
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CHECK_EVERY) {
+            self.shared.nodes.fetch_add(CHECK_EVERY, Ordering::Relaxed);
+            if self.should_stop() {
+                self.aborted = true;
+            }
+        }
+    }
+
+    fn total_nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed) + self.nodes % CHECK_EVERY
+    }
+
+    /// The main thread never stops during its first iteration, to always have a move
+    /// worth playing. Helpers stop with it.
+    fn should_stop(&self) -> bool {
+        let stopped = self.shared.stop.load(Ordering::Relaxed)
+            || self
+                .shared
+                .node_limit
+                .is_some_and(|limit| self.total_nodes() >= limit);
+
+        if self.is_main() {
+            self.depth > 1 && (stopped || self.time.out_of_time())
+        } else {
+            stopped || self.shared.done.load(Ordering::Relaxed)
+        }
+    }
+}
+
+/// The score of a tablebase result `ply` plies from the root, quicker wins first.
+/// Results the 50-move rule spoils are draws.
+#[cfg(feature = "syzygy")]
+fn tb_score(wdl: Wdl, ply: u32) -> i32 {
+    match wdl {
+        Wdl::Win => TB_WIN - ply as i32,
+        Wdl::Loss => -TB_WIN + ply as i32,
+        _ => 0,
+    }
+}
+
+fn to_score(score: i32) -> Score {
+    if score >= MATE_BOUND {
+        Score::Mate((MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        Score::Mate(-(MATE + score + 1) / 2)
+    } else {
+        Score::Centipawns(score)
+    }
+}
@@ -0,0 +1,1401 @@
+//! Syzygy endgame tablebases: win/draw/loss (`.rtbw`) and distance to zeroing
+//! (`.rtbz`) tables for positions with few pieces, memory-mapped and decoded on
+//! demand.
+//!
+//! The tables only store positions without castling rights, and leave out values that
+//! a capture decides anyway, so probing searches captures before looking a position
+//! up.
+//!
+//! Only built with `--features syzygy`, as reading the tables needs `mmap`.
+
+use crate::attacks;
+use crate::board_position::BoardIndex;
+use crate::castle_rights::CastleRights;
+use crate::game_state::GameState;
+use crate::piece::{BoardPiece, BoardPieceKind, PieceColor};
+use crate::piece_move::Move;
+use std::collections::HashMap;
+use std::ops::{Deref, Neg};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The most pieces, kings included, that any Syzygy table has.
+pub const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+/// The result of a position with perfect play, for the side to move. Cursed wins and
+/// blessed losses are wins and losses that the 50-move rule turns into draws.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Self> {
+        Some(match value {
+            -2 => Self::Loss,
+            -1 => Self::BlessedLoss,
+            0 => Self::Draw,
+            1 => Self::CursedWin,
+            2 => Self::Win,
+            _ => return None,
+        })
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::from_value(-(self as i32)).unwrap()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TablebaseError {
+    #[error("cannot read tablebase: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}: not a valid Syzygy table")]
+    InvalidTable(PathBuf),
+    #[error("no table for {0}")]
+    MissingTable(String),
+    #[error("positions with castling rights are not in tablebases")]
+    CastlingRights,
+    #[error("{0} pieces are too many for the tablebase")]
+    TooManyPieces(u32),
+}
+
+/// A root move, ranked by what the tables say about it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RootMove {
+    pub m: Move,
+    /// The result after the move, counting the 50-move rule.
+    pub wdl: Wdl,
+    /// Plies until the next capture or pawn move, this move included, positive when
+    /// winning and negative when losing.
+    pub dtz: i32,
+}
+
+/// The Syzygy tables found in a directory.
+pub struct Tablebase {
+    tables: Vec<TableFiles>,
+    /// Indexes into `tables`, by the material of either side.
+    by_material: HashMap<Material, usize>,
+    max_pieces: u32,
+}
+
+impl Tablebase {
+    /// Finds the tables in `dir`, which are only read once they are needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, TablebaseError> {
+        let mut found = HashMap::<String, (Option<PathBuf>, Option<PathBuf>)>::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let (Some(name), Some(extension)) = (path.file_stem(), path.extension()) else {
+                continue;
+            };
+            let (Some(name), Some(extension)) = (name.to_str(), extension.to_str()) else {
+                continue;
+            };
+            let files = found.entry(name.to_string()).or_default();
+            match extension {
+                "rtbw" => files.0 = Some(path),
+                "rtbz" => files.1 = Some(path),
+                _ => {}
+            }
+        }
+
+        let mut tablebase = Self {
+            tables: Vec::new(),
+            by_material: HashMap::new(),
+            max_pieces: 0,
+        };
+        for (name, (wdl, dtz)) in found {
+            let Some(info) = TableInfo::parse(&name) else {
+                continue;
+            };
+            // a DTZ table alone is of no use: probing it needs the WDL one.
+            let Some(wdl) = wdl else {
+                continue;
+            };
+            tablebase.max_pieces = tablebase.max_pieces.max(info.piece_count as u32);
+            tablebase
+                .by_material
+                .insert(info.key, tablebase.tables.len());
+            tablebase
+                .by_material
+                .insert(info.key.flipped(), tablebase.tables.len());
+            tablebase.tables.push(TableFiles {
+                info,
+                wdl_path: wdl,
+                dtz_path: dtz,
+                wdl: OnceLock::new(),
+                dtz: OnceLock::new(),
+            });
+        }
+        Ok(tablebase)
+    }
+
+    /// The most pieces in any of the tables, kings included.
+    pub fn max_pieces(&self) -> u32 {
+        self.max_pieces
+    }
+
+    /// How many WDL tables were found.
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Whether `state` could be in the tables: it has few enough pieces and no
+    /// castling rights.
+    pub fn covers(&self, state: &GameState) -> bool {
+        state.board().occupied().count() <= self.max_pieces
+            && state.castling_rights() == CastleRights::EMPTY
+    }
+
+    /// The result of `state` with perfect play, assuming the 50-move counter was just
+    /// reset.
+    pub fn probe_wdl(&self, state: &GameState) -> Result<Wdl, TablebaseError> {
+        self.check(state)?;
+        Ok(self.search(state, false)?.0)
+    }
+
+    /// The distance to zeroing of `state`, in plies: how long until the next capture or
+    /// pawn move with perfect play, positive when winning and negative when losing,
+    /// and 0 for draws. Cursed wins and blessed losses are 100 plies further away.
+    ///
+    /// The result may be off by a ply in positions whose best move zeroes, as the
+    /// tables don't store those.
+    pub fn probe_dtz(&self, state: &GameState) -> Result<i32, TablebaseError> {
+        self.check(state)?;
+
+        let (wdl, zeroing_best_move) = self.search(state, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        // the tables store nothing meaningful then.
+        if zeroing_best_move {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        if let Some(dtz) = self.probe_table(state, TableKind::Dtz, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Ok((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+        }
+
+        // the table is for the other side to move: look one ply ahead instead.
+        let mut min_dtz = i32::MAX;
+        for m in state.legal_moves() {
+            let zeroing = is_zeroing(state, m);
+            let mut child = *state;
+            child.perform_move(m);
+
+            // a zeroing move starts the count again, so only its result matters.
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&child, false)?.0)
+            } else {
+                -self.probe_dtz(&child)?
+            };
+            if dtz == 1 && child.is_check() && child.legal_moves().next().is_none() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+        // without legal moves, the side to move is mated.
+        Ok(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    /// The legal moves of `state`, best first: the quickest wins, then wins the
+    /// 50-move rule spoils, draws, and the longest losses.
+    pub fn rank_root_moves(&self, state: &GameState) -> Result<Vec<RootMove>, TablebaseError> {
+        self.check(state)?;
+        let clock = state.half_move_clock() as i32;
+
+        let mut moves = Vec::new();
+        for m in state.legal_moves() {
+            let mut child = *state;
+            child.perform_move(m);
+
+            let zeroing = child.half_move_clock() == 0;
+            let mut dtz = if zeroing {
+                dtz_before_zeroing(-self.probe_wdl(&child)?)
+            } else {
+                let dtz = -self.probe_dtz(&child)?;
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && child.is_check() && child.legal_moves().next().is_none() {
+                dtz = 1;
+            }
+
+            // the 50-move count starts again after a zeroing move.
+            let clock = if zeroing { 0 } else { clock };
+            let wdl = match dtz {
+                1.. if dtz + clock <= 99 => Wdl::Win,
+                1.. => Wdl::CursedWin,
+                0 => Wdl::Draw,
+                _ if -dtz + clock <= 99 => Wdl::Loss,
+                _ => Wdl::BlessedLoss,
+            };
+            moves.push(RootMove { m, wdl, dtz });
+        }
+
+        // quicker wins and slower losses first.
+        moves.sort_by_key(|root| (std::cmp::Reverse(root.wdl), root.dtz));
+        Ok(moves)
+    }
+
+    fn check(&self, state: &GameState) -> Result<(), TablebaseError> {
+        if state.castling_rights() != CastleRights::EMPTY {
+            return Err(TablebaseError::CastlingRights);
+        }
+        let pieces = state.board().occupied().count();
+        if pieces > self.max_pieces.max(2) {
+            return Err(TablebaseError::TooManyPieces(pieces));
+        }
+        Ok(())
+    }
+
+    /// The result of `state`, and whether its best move is a zeroing one, so that the
+    /// DTZ table doesn't hold a value for it. Captures are searched first since the
+    /// tables don't store positions a capture decides, and, if `check_zeroing_moves`,
+    /// pawn moves too.
+    fn search(
+        &self,
+        state: &GameState,
+        check_zeroing_moves: bool,
+    ) -> Result<(Wdl, bool), TablebaseError> {
+        let mut best = Wdl::Loss;
+        let mut moves = 0;
+        let mut searched = 0;
+
+        let board = state.board();
+        for m in state.legal_moves() {
+            moves += 1;
+            let searches = match check_zeroing_moves {
+                true => is_zeroing(state, m),
+                false => board.is_capture(m),
+            };
+            if !searches {
+                continue;
+            }
+            searched += 1;
+
+            let mut child = *state;
+            child.perform_move(m);
+            let value = -self.search(&child, false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        // with nothing but captures, the table may not even hold the right value, as in
+        // positions with an en passant target.
+        let no_more_moves = searched > 0 && searched == moves;
+        let value = if no_more_moves {
+            best
+        } else {
+            let value = self.probe_table(state, TableKind::Wdl, Wdl::Draw)?;
+            Wdl::from_value(value.unwrap_or(0)).ok_or_else(|| self.invalid(state))?
+        };
+
+        if best >= value {
+            Ok((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Ok((value, false))
+        }
+    }
+
+    fn invalid(&self, state: &GameState) -> TablebaseError {
+        let material = Material::of(state);
+        match self.by_material.get(&material) {
+            Some(&i) => TablebaseError::InvalidTable(self.tables[i].wdl_path.clone()),
+            None => TablebaseError::MissingTable(material.name()),
+        }
+    }
+
+    /// Looks `state` up in its WDL or DTZ table, returning the value stored, or `None`
+    /// when a DTZ table only has values for the other side to move. For DTZ tables,
+    /// `wdl` is the result of the position.
+    fn probe_table(
+        &self,
+        state: &GameState,
+        kind: TableKind,
+        wdl: Wdl,
+    ) -> Result<Option<i32>, TablebaseError> {
+        let board = state.board();
+        if board.occupied().count() == 2 {
+            return Ok(Some(Wdl::Draw as i32));
+        }
+
+        let material = Material::of(state);
+        let &i = self
+            .by_material
+            .get(&material)
+            .ok_or_else(|| TablebaseError::MissingTable(material.name()))?;
+        let files = &self.tables[i];
+        let info = &files.info;
+        let table = files.load(kind)?;
+        let invalid = || TablebaseError::InvalidTable(table.path.clone());
+
+        // tables are stored with the stronger side as white, and only for white to
+        // move when both sides have the same pieces: look at the position the other
+        // way around if need be.
+        let black_to_move = state.next_move() == PieceColor::Black;
+        let flip = material != info.key || (info.symmetric() && black_to_move);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip != black_to_move) as usize;
+
+        let mut squares = [0u8; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+
+        // with pawns, the leading pawn decides which file's table to use.
+        let mut lead_pawns = 0u64;
+        let mut file = 0;
+        if info.has_pawns {
+            let lead = table.files[0][0].pieces[0] ^ flip_color;
+            let lead_piece = board_piece(lead).ok_or_else(invalid)?;
+            lead_pawns = board.pieces(lead_piece).0;
+            for index in board.pieces(lead_piece) {
+                squares[size] = index.get_pos() ^ flip_squares;
+                pieces[size] = lead ^ flip_color;
+                size += 1;
+            }
+            let encoding = encoding();
+            let leading = (0..size)
+                .max_by_key(|&i| encoding.map_pawns[squares[i] as usize])
+                .ok_or_else(invalid)?;
+            squares.swap(0, leading);
+            file = edge_distance(squares[0] % 8) as usize;
+        }
+        let lead_pawn_count = size;
+
+        if kind == TableKind::Dtz {
+            let flags = table.files[file][0].flags;
+            let stored =
+                (flags & flag::STM != 0) == (stm == 1) || (info.symmetric() && !info.has_pawns);
+            if !stored {
+                return Ok(None);
+            }
+        }
+
+        for (index, piece) in board.piece_iterator() {
+            if lead_pawns & (1 << index.get_pos()) != 0 {
+                continue;
+            }
+            if size == MAX_PIECES {
+                return Err(invalid());
+            }
+            squares[size] = index.get_pos() ^ flip_squares;
+            pieces[size] = tb_piece(piece) ^ flip_color;
+            size += 1;
+        }
+
+        let sides = table.files[file].len();
+        let d = &table.files[file][stm % sides];
+
+        // the table's order of pieces, which is the one that compressed best.
+        for i in lead_pawn_count..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| d.pieces[i] == pieces[j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        let squares = &mut squares[..size];
+
+        let index = encode(info, d, squares, lead_pawn_count).ok_or_else(invalid)?;
+        let value = table.decompress(d, index).ok_or_else(invalid)?;
+
+        Ok(Some(match kind {
+            TableKind::Wdl => value - 2,
+            TableKind::Dtz => table.map_dtz(file, value, wdl).ok_or_else(invalid)?,
+        }))
+    }
+}
+
+/// The DTZ of a position whose best move is a zeroing one with the result `wdl`.
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+fn is_zeroing(state: &GameState, m: Move) -> bool {
+    let board = state.board();
+    board.is_capture(m)
+        || board
+            .get_piece_at(m.start())
+            .is_some_and(|piece| piece.kind() == BoardPieceKind::Pawn)
+}
+
+/// Pieces as Syzygy numbers them: pawn, knight, bishop, rook, queen and king from 1
+/// to 6 for white, and 8 more for black.
+fn tb_piece(piece: BoardPiece) -> u8 {
+    let kind = match piece.kind() {
+        BoardPieceKind::Pawn => 1,
+        BoardPieceKind::Knight => 2,
+        BoardPieceKind::Bishop => 3,
+        BoardPieceKind::Rook => 4,
+        BoardPieceKind::Queen => 5,
+        BoardPieceKind::King => 6,
+    };
+    kind + 8 * piece.color() as u8
+}
+
+fn board_piece(tb_piece: u8) -> Option<BoardPiece> {
+    let kind = match tb_piece & 7 {
+        1 => BoardPieceKind::Pawn,
+        2 => BoardPieceKind::Knight,
+        3 => BoardPieceKind::Bishop,
+        4 => BoardPieceKind::Rook,
+        5 => BoardPieceKind::Queen,
+        6 => BoardPieceKind::King,
+        _ => return None,
+    };
+    let color = match tb_piece & 8 {
+        0 => PieceColor::White,
+        _ => PieceColor::Black,
+    };
+    Some(kind.of_color(color))
+}
+
+/// How many of each piece, indexed by Syzygy piece number.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Material([u8; 16]);
+
+impl Material {
+    fn of(state: &GameState) -> Self {
+        let mut counts = [0; 16];
+        for (_, piece) in state.board().piece_iterator() {
+            counts[tb_piece(piece) as usize] += 1;
+        }
+        Self(counts)
+    }
+
+    /// The same pieces with the colors swapped.
+    fn flipped(self) -> Self {
+        let mut counts = [0; 16];
+        for (i, &count) in self.0.iter().enumerate() {
+            counts[i ^ 8] = count;
+        }
+        Self(counts)
+    }
+
+    fn count(self, piece: u8) -> u8 {
+        self.0[piece as usize]
+    }
+
+    /// The name of the table, e.g. `KRPvKN`.
+    fn name(self) -> String {
+        let side = |color: u8| {
+            [6, 5, 4, 3, 2, 1]
+                .into_iter()
+                .flat_map(|kind| {
+                    let c = board_piece(kind).unwrap().kind().char();
+                    std::iter::repeat_n(c, self.count(kind + color) as usize)
+                })
+                .collect::<String>()
+        };
+        format!("{}v{}", side(0), side(8))
+    }
+}
+
+/// What the name of a table tells about it.
+#[derive(Clone, Debug)]
+struct TableInfo {
+    /// The material with the first side of the name as white.
+    key: Material,
+    piece_count: usize,
+    has_pawns: bool,
+    /// Whether some piece other than a king is the only one of its kind on its side.
+    has_unique_pieces: bool,
+    /// The pawns of the leading side, which has fewer but some, then of the other.
+    pawn_count: [u8; 2],
+}
+
+impl TableInfo {
+    fn parse(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let mut counts = [0; 16];
+        for (side, color) in [(white, 0), (black, 8)] {
+            if !side.starts_with('K') || side.len() > MAX_PIECES - 1 {
+                return None;
+            }
+            for c in side.chars() {
+                let kind = match c {
+                    'P' => 1,
+                    'N' => 2,
+                    'B' => 3,
+                    'R' => 4,
+                    'Q' => 5,
+                    'K' => 6,
+                    _ => return None,
+                };
+                counts[kind + color] += 1;
+            }
+        }
+        let key = Material(counts);
+        if key.count(6) != 1 || key.count(14) != 1 {
+            return None;
+        }
+
+        let piece_count = counts.iter().map(|&count| count as usize).sum::<usize>();
+        if piece_count > MAX_PIECES {
+            return None;
+        }
+        let (white_pawns, black_pawns) = (key.count(1), key.count(9));
+        let has_unique_pieces = (1..6).chain(9..14).any(|piece| key.count(piece) == 1);
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+
+        Some(Self {
+            key,
+            piece_count,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count,
+        })
+    }
+
+    /// Whether both sides have the same pieces.
+    fn symmetric(&self) -> bool {
+        self.key == self.key.flipped()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+/// The files of a table, read the first time they are probed.
+struct TableFiles {
+    info: TableInfo,
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+impl TableFiles {
+    fn load(&self, kind: TableKind) -> Result<&Table, TablebaseError> {
+        let (path, cell) = match kind {
+            TableKind::Wdl => (&self.wdl_path, &self.wdl),
+            TableKind::Dtz => match &self.dtz_path {
+                Some(path) => (path, &self.dtz),
+                None => {
+                    return Err(TablebaseError::MissingTable(format!(
+                        "{}.rtbz",
+                        self.info.key.name()
+                    )))
+                }
+            },
+        };
+        cell.get_or_init(|| Table::open(path, kind, &self.info))
+            .as_ref()
+            .ok_or_else(|| TablebaseError::InvalidTable(path.clone()))
+    }
+}
+
+/// Flags of a table's pairs data.
+mod flag {
+    /// DTZ: for black to move.
+    pub const STM: u8 = 1;
+    /// DTZ: values are mapped through a table.
+    pub const MAPPED: u8 = 2;
+    /// DTZ: wins are counted in plies rather than moves.
+    pub const WIN_PLIES: u8 = 4;
+    /// DTZ: losses are counted in plies rather than moves.
+    pub const LOSS_PLIES: u8 = 8;
+    /// DTZ: the map has 16-bit values.
+    pub const WIDE: u8 = 16;
+    /// Every position has the same value.
+    pub const SINGLE_VALUE: u8 = 128;
+}
+
+/// How the positions of one side to move and one leading pawn file are indexed and
+/// compressed.
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    /// The pieces in the order they are indexed.
+    pieces: [u8; MAX_PIECES],
+    /// The sizes of the groups of pieces indexed together, ending with a 0.
+    group_len: [usize; MAX_PIECES + 1],
+    /// What the index of each group is multiplied by; after the last, the table size.
+    group_idx: [u64; MAX_PIECES + 1],
+
+    /// The value of every position with `SINGLE_VALUE`.
+    min_sym_len: u8,
+    block_size: u64,
+    /// How many values apart the entries of the sparse index are.
+    span: u64,
+    num_blocks: u64,
+    /// Offsets into the file.
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_lengths: usize,
+    block_lengths_size: usize,
+    data: usize,
+    lowest_sym: usize,
+    btree: usize,
+    /// The smallest left-aligned code of each length of Huffman code, from the
+    /// shortest.
+    base64: Vec<u64>,
+    /// How many values each symbol stands for, minus one.
+    symlen: Vec<u8>,
+    /// DTZ: where the mapped values for each result start.
+    map_idx: [usize; 4],
+}
+
+/// An open table file.
+struct Table {
+    path: PathBuf,
+    data: Mapping,
+    /// By leading pawn file (just one without pawns), then side to move (just one for
+    /// DTZ tables and tables with the same pieces on both sides).
+    files: Vec<Vec<PairsData>>,
+}
+
+impl Table {
+    fn open(path: &Path, kind: TableKind, info: &TableInfo) -> Option<Self> {
+        let data = Mapping::open(path).ok()?;
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if data.get(..4)? != magic {
+            return None;
+        }
+        let files = read_header(&data, kind, info)?;
+        Some(Self {
+            path: path.to_path_buf(),
+            data,
+            files,
+        })
+    }
+
+    /// The value stored at `index`.
+    fn decompress(&self, d: &PairsData, index: u64) -> Option<i32> {
+        if d.flags & flag::SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as i32);
+        }
+        let data = &self.data;
+
+        // the sparse index points into the list of block lengths close to where the
+        // block holding `index` is.
+        let k = (index / d.span) as usize;
+        let entry = d.sparse_index + 6 * k;
+        let mut block = read_u32_le(data, entry)? as usize;
+        let mut offset = read_u16_le(data, entry + 4)? as i64;
+        offset += (index % d.span) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: usize| {
+            if block >= d.block_lengths_size {
+                return None;
+            }
+            read_u16_le(data, d.block_lengths + 2 * block).map(|length| length as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+        if block as u64 >= d.num_blocks {
+            return None;
+        }
+
+        // the block is a sequence of canonical Huffman codes, read 32 bits at a time.
+        let mut ptr = d.data + block * d.block_size as usize;
+        let mut buf64 = read_u64_be(data, ptr)?;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf64 < *d.base64.get(len)? {
+                len += 1;
+            }
+            let shift = 64usize.checked_sub(len + d.min_sym_len as usize)?;
+            sym = (buf64 - d.base64[len])
+                .checked_shr(shift as u32)
+                .unwrap_or(0) as usize;
+            sym += read_u16_le(data, d.lowest_sym + 2 * len)? as usize;
+
+            let sym_len = *d.symlen.get(sym)? as i64;
+            if offset < sym_len + 1 {
+                break;
+            }
+            offset -= sym_len + 1;
+            let len = len + d.min_sym_len as usize;
+            buf64 = buf64.checked_shl(len as u32).unwrap_or(0);
+            buf64_size -= len as i32;
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (read_u32_be(data, ptr)? as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        // each symbol stands for a pair of symbols, down to single values.
+        while *d.symlen.get(sym)? != 0 {
+            let (left, right) = self.pair(d, sym)?;
+            let left_len = *d.symlen.get(left)? as i64;
+            if offset < left_len + 1 {
+                sym = left;
+            } else {
+                offset -= left_len + 1;
+                sym = right;
+            }
+        }
+        Some(self.pair(d, sym)?.0 as i32)
+    }
+
+    /// The two symbols `sym` stands for, or its value and 0xfff for single values.
+    fn pair(&self, d: &PairsData, sym: usize) -> Option<(usize, usize)> {
+        pair_at(&self.data, d.btree + 3 * sym)
+    }
+
+    /// Turns the value stored for a position with the result `wdl` into plies.
+    fn map_dtz(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        const WDL_TO_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+
+        let d = &self.files[file][0];
+        let mut value = value;
+        if d.flags & flag::MAPPED != 0 {
+            let start = d.map_idx[WDL_TO_MAP[(wdl as i32 + 2) as usize]];
+            value = if d.flags & flag::WIDE != 0 {
+                read_u16_le(&self.data, start + 2 * value as usize)? as i32
+            } else {
+                *self.data.get(start + value as usize)? as i32
+            };
+        }
+
+        let in_moves = match wdl {
+            Wdl::Win => d.flags & flag::WIN_PLIES == 0,
+            Wdl::Loss => d.flags & flag::LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+
+/// Reads how the table is laid out, returning the pairs data by file and side.
+fn read_header(data: &[u8], kind: TableKind, info: &TableInfo) -> Option<Vec<Vec<PairsData>>> {
+    const SPLIT: u8 = 1;
+    const HAS_PAWNS: u8 = 2;
+
+    let mut pos = 4;
+    let flags = *data.get(pos)?;
+    pos += 1;
+    if (flags & HAS_PAWNS != 0) != info.has_pawns || (flags & SPLIT != 0) == info.symmetric() {
+        return None;
+    }
+
+    let sides = if kind == TableKind::Wdl && !info.symmetric() {
+        2
+    } else {
+        1
+    };
+    let file_count = if info.has_pawns { 4 } else { 1 };
+    // pawns on both sides.
+    let pp = info.has_pawns && info.pawn_count[1] > 0;
+
+    let mut files = vec![vec![PairsData::default(); sides]; file_count];
+    for (file, pairs) in files.iter_mut().enumerate() {
+        let order = *data.get(pos)?;
+        let order2 = if pp { *data.get(pos + 1)? } else { 0xff };
+        let orders = [[order & 0xf, order2 & 0xf], [order >> 4, order2 >> 4]];
+        pos += 1 + pp as usize;
+
+        for k in 0..info.piece_count {
+            let byte = *data.get(pos)?;
+            for (side, d) in pairs.iter_mut().enumerate() {
+                d.pieces[k] = if side == 0 { byte & 0xf } else { byte >> 4 };
+            }
+            pos += 1;
+        }
+        for (side, d) in pairs.iter_mut().enumerate() {
+            set_groups(info, d, orders[side], file)?;
+        }
+    }
+    pos += pos & 1;
+
+    for d in files.iter_mut().flatten() {
+        pos = set_sizes(data, d, pos)?;
+    }
+
+    if kind == TableKind::Dtz {
+        for pairs in &mut files {
+            let d = &mut pairs[0];
+            if d.flags & flag::MAPPED == 0 {
+                continue;
+            }
+            if d.flags & flag::WIDE != 0 {
+                pos += pos & 1;
+                for i in 0..4 {
+                    d.map_idx[i] = pos + 2;
+                    pos += 2 * read_u16_le(data, pos)? as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    d.map_idx[i] = pos + 1;
+                    pos += *data.get(pos)? as usize + 1;
+                }
+            }
+        }
+        pos += pos & 1;
+    }
+
+    for d in files.iter_mut().flatten() {
+        d.sparse_index = pos;
+        pos += d.sparse_index_size * 6;
+    }
+    for d in files.iter_mut().flatten() {
+        d.block_lengths = pos;
+        pos += d.block_lengths_size * 2;
+    }
+    for d in files.iter_mut().flatten() {
+        pos = (pos + 0x3f) & !0x3f;
+        d.data = pos;
+        pos += (d.num_blocks * d.block_size) as usize;
+    }
+
+    (pos <= data.len()).then_some(files)
+}
+
+/// Splits the pieces into the groups that are indexed together, and works out what
+/// each group's index is multiplied by, in the order the table gives in `order`: the
+/// leading group first in `order[0]`, and the other side's pawns in `order[1]`.
+fn set_groups(info: &TableInfo, d: &mut PairsData, order: [u8; 2], file: usize) -> Option<()> {
+    let encoding = encoding();
+
+    // without pawns, the kings and another piece, or just the kings, lead.
+    let mut first_len: i32 = if info.has_pawns {
+        0
+    } else if info.has_unique_pieces {
+        3
+    } else {
+        2
+    };
+    let mut n = 0;
+    d.group_len[0] = 1;
+    for i in 1..info.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    let pp = info.has_pawns && info.pawn_count[1] > 0;
+    let mut next = if pp { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+    let mut idx = 1u64;
+
+    let mut k = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            d.group_idx[0] = idx;
+            idx *= if info.has_pawns {
+                *encoding.lead_pawns_size.get(d.group_len[0])?.get(file)?
+            } else if info.has_unique_pieces {
+                31_332
+            } else {
+                462
+            };
+        } else if k == order[1] as usize {
+            d.group_idx[1] = idx;
+            idx *= encoding.binomial(d.group_len[1], 48 - d.group_len[0]);
+        } else {
+            d.group_idx[next] = idx;
+            idx *= encoding.binomial(d.group_len[next], free_squares);
+            free_squares = free_squares.checked_sub(d.group_len[next])?;
+            next += 1;
+        }
+        k += 1;
+        if k > MAX_PIECES + 1 {
+            return None;
+        }
+    }
+    d.group_idx[n] = idx;
+    Some(())
+}
+
+/// Reads the Huffman code and pairs of `d`, starting at `pos`, and returns where they
+/// end.
+fn set_sizes(data: &[u8], d: &mut PairsData, mut pos: usize) -> Option<usize> {
+    d.flags = *data.get(pos)?;
+    pos += 1;
+    if d.flags & flag::SINGLE_VALUE != 0 {
+        d.min_sym_len = *data.get(pos)?;
+        return Some(pos + 1);
+    }
+
+    let groups = d.group_len.iter().position(|&len| len == 0)?;
+    let table_size = d.group_idx[groups];
+
+    d.block_size = 1u64.checked_shl(*data.get(pos)? as u32)?;
+    d.span = 1u64.checked_shl(*data.get(pos + 1)? as u32)?;
+    pos += 2;
+    d.sparse_index_size = table_size.div_ceil(d.span) as usize;
+    let padding = *data.get(pos)? as usize;
+    pos += 1;
+    d.num_blocks = read_u32_le(data, pos)? as u64;
+    pos += 4;
+    d.block_lengths_size = d.num_blocks as usize + padding;
+
+    let max_sym_len = *data.get(pos)?;
+    d.min_sym_len = *data.get(pos + 1)?;
+    pos += 2;
+    d.lowest_sym = pos;
+    let lengths = (max_sym_len.checked_sub(d.min_sym_len)? as usize) + 1;
+
+    // canonical Huffman codes: longer codes have lower values, so each length has a
+    // smallest code, which left-aligned in 64 bits tells the length of any code.
+    let mut base64 = vec![0u64; lengths];
+    for i in (0..lengths - 1).rev() {
+        let lowest = read_u16_le(data, d.lowest_sym + 2 * i)? as u64;
+        let next_lowest = read_u16_le(data, d.lowest_sym + 2 * (i + 1))? as u64;
+        base64[i] = base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+    }
+    for (i, base) in base64.iter_mut().enumerate() {
+        *base = base
+            .checked_shl(64 - i as u32 - d.min_sym_len as u32)
+            .unwrap_or(0);
+    }
+    d.base64 = base64;
+    pos += 2 * lengths;
+
+    let symbols = read_u16_le(data, pos)? as usize;
+    pos += 2;
+    d.btree = pos;
+    d.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for sym in 0..symbols {
+        if !visited[sym] {
+            set_symlen(data, d, sym, &mut visited)?;
+        }
+    }
+
+    Some(pos + 3 * symbols + (symbols & 1))
+}
+
+/// Works out how many values `sym` stands for, and the symbols it is made of.
+fn set_symlen(data: &[u8], d: &mut PairsData, sym: usize, visited: &mut [bool]) -> Option<()> {
+    visited[sym] = true;
+    let (left, right) = pair_at(data, d.btree + 3 * sym)?;
+    if right == 0xfff {
+        d.symlen[sym] = 0;
+        return Some(());
+    }
+    for child in [left, right] {
+        if !*visited.get(child)? {
+            set_symlen(data, d, child, visited)?;
+        }
+    }
+    // lengths wrap around as the reference implementation's bytes do.
+    d.symlen[sym] = d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1);
+    Some(())
+}
+
+/// The two 12-bit symbols stored in 3 bytes at `pos`.
+fn pair_at(data: &[u8], pos: usize) -> Option<(usize, usize)> {
+    let bytes = data.get(pos..pos + 3)?;
+    let left = ((bytes[1] as usize & 0xf) << 8) | bytes[0] as usize;
+    let right = ((bytes[2] as usize) << 4) | (bytes[1] as usize >> 4);
+    Some((left, right))
+}
+
+/// The index of the position with `squares`, in the order of `d.pieces` with the
+/// leading pawns first, in its table.
+fn encode(
+    info: &TableInfo,
+    d: &PairsData,
+    squares: &mut [u8],
+    lead_pawn_count: usize,
+) -> Option<u64> {
+    let encoding = encoding();
+    let size = squares.len();
+
+    // the leading piece goes on the queen side.
+    if squares[0] % 8 > 3 {
+        for square in squares.iter_mut() {
+            *square ^= 7;
+        }
+    }
+
+    let mut idx;
+    if info.has_pawns {
+        idx = encoding.lead_pawn_idx[lead_pawn_count][squares[0] as usize];
+        squares[1..lead_pawn_count].sort_by_key(|&square| encoding.map_pawns[square as usize]);
+        for (i, &square) in squares.iter().enumerate().take(lead_pawn_count).skip(1) {
+            idx += encoding.binomial(i, encoding.map_pawns[square as usize] as usize);
+        }
+    } else {
+        // without pawns, the leading piece also goes below the 5th rank...
+        if squares[0] / 8 > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 56;
+            }
+        }
+        // ...and below the a1-h8 diagonal, as does the first piece of its group
+        // that is off it.
+        for i in 0..d.group_len[0] {
+            let off = off_diagonal(squares[i]);
+            if off == 0 {
+                continue;
+            }
+            if off > 0 {
+                for square in &mut squares[i..] {
+                    *square = ((*square >> 3) | (*square << 3)) & 63;
+                }
+            }
+            break;
+        }
+
+        idx = if info.has_unique_pieces {
+            let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+            let adjust1 = (s1 > s0) as u64;
+            let adjust2 = (s2 > s0) as u64 + (s2 > s1) as u64;
+            let rank = |square: u8| (square / 8) as u64;
+
+            if off_diagonal(s0) != 0 {
+                (encoding.map_a1d1d4[s0 as usize] * 63 + (s1 as u64 - adjust1)) * 62 + s2 as u64
+                    - adjust2
+            } else if off_diagonal(s1) != 0 {
+                (6 * 63 + rank(s0) * 28 + encoding.map_b1h1h7[s1 as usize]) * 62 + s2 as u64
+                    - adjust2
+            } else if off_diagonal(s2) != 0 {
+                6 * 63 * 62
+                    + 4 * 28 * 62
+                    + rank(s0) * 7 * 28
+                    + (rank(s1) - adjust1) * 28
+                    + encoding.map_b1h1h7[s2 as usize]
+            } else {
+                6 * 63 * 62
+                    + 4 * 28 * 62
+                    + 4 * 7 * 28
+                    + rank(s0) * 7 * 6
+                    + (rank(s1) - adjust1) * 6
+                    + (rank(s2) - adjust2)
+            }
+        } else {
+            encoding.map_kk[encoding.map_a1d1d4[squares[0] as usize] as usize][squares[1] as usize]
+        };
+    }
+
+    idx *= d.group_idx[0];
+    let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+    let mut start = d.group_len[0];
+    let mut next = 1;
+    while d.group_len[next] != 0 {
+        let end = start + d.group_len[next];
+        if end > size {
+            return None;
+        }
+        squares[start..end].sort_unstable();
+
+        // squares taken by earlier groups don't count.
+        let mut n = 0;
+        for i in 0..d.group_len[next] {
+            let square = squares[start + i];
+            let adjust = squares[..start].iter().filter(|&&s| square > s).count();
+            let square =
+                (square as usize).checked_sub(adjust + if remaining_pawns { 8 } else { 0 })?;
+            n += encoding.binomial(i + 1, square);
+        }
+
+        remaining_pawns = false;
+        idx += n * d.group_idx[next];
+        start = end;
+        next += 1;
+    }
+    Some(idx)
+}
+
+/// How far `square` is above the a1-h8 diagonal: positive above, negative below.
+fn off_diagonal(square: u8) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+/// How far `file`, from 0, is from the nearest edge.
+fn edge_distance(file: u8) -> u8 {
+    file.min(7 - file)
+}
+
+/// The tables the index of a position is computed with.
+struct Encoding {
+    /// Squares below the a1-h8 diagonal, from 0 to 27.
+    map_b1h1h7: [u64; 64],
+    /// Squares in the a1-d1-d4 triangle, from 0 to 9, the diagonal last.
+    map_a1d1d4: [u64; 64],
+    /// The 462 ways to place two kings with the first in the a1-d1-d4 triangle, by
+    /// that king's `map_a1d1d4` and the other's square.
+    map_kk: [[u64; 64]; 10],
+    /// `binomial[k][n]`: the ways to choose k of n.
+    binomial: [[u64; 64]; MAX_PIECES],
+    /// Pawn squares from 47 down to 0, the leading pawn being the one with the highest.
+    map_pawns: [u64; 64],
+    /// By the number of leading pawns, and the leading one's square.
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES],
+    /// By the number of leading pawns, and the leading one's file.
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+}
+
+impl Encoding {
+    fn binomial(&self, k: usize, n: usize) -> u64 {
+        self.binomial
+            .get(k)
+            .and_then(|row| row.get(n))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn new() -> Self {
+        let mut map_b1h1h7 = [0; 64];
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                map_b1h1h7[square as usize] = code;
+                code += 1;
+            }
+        }
+
+        let mut map_a1d1d4 = [0; 64];
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in 0..=27u8 {
+            if square % 8 > 3 {
+                continue;
+            }
+            if off_diagonal(square) < 0 {
+                map_a1d1d4[square as usize] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            map_a1d1d4[square as usize] = code;
+            code += 1;
+        }
+
+        let mut map_kk = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            for s1 in 0..=27u8 {
+                let in_triangle = s1 % 8 <= 3 && off_diagonal(s1) <= 0;
+                // b1 is mapped to 0 too, as is any square outside the triangle.
+                if !in_triangle || map_a1d1d4[s1 as usize] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                let near = attacks::king_attacks(BoardIndex::new(s1).unwrap()).0 | 1 << s1;
+                for s2 in 0..64u8 {
+                    if near & (1 << s2) != 0 || (off_diagonal(s1) == 0 && off_diagonal(s2) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        map_kk[idx as usize][s2 as usize] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            map_kk[idx as usize][s2 as usize] = code;
+            code += 1;
+        }
+
+        let mut binomial = [[0; 64]; MAX_PIECES];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                binomial[k][n] = if k > 0 { binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut map_pawns = [0; 64];
+        let mut lead_pawn_idx = [[0; 64]; MAX_PIECES];
+        let mut lead_pawns_size = [[0; 4]; MAX_PIECES];
+        let mut available: u64 = 47;
+        for lead_pawn_count in 1..MAX_PIECES - 1 {
+            for (file, size) in lead_pawns_size[lead_pawn_count].iter_mut().enumerate() {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = 8 * rank + file;
+                    if lead_pawn_count == 1 {
+                        map_pawns[square] = available;
+                        map_pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    lead_pawn_idx[lead_pawn_count][square] = idx;
+                    idx += binomial[lead_pawn_count - 1][map_pawns[square] as usize];
+                }
+                *size = idx;
+            }
+        }
+
+        Self {
+            map_b1h1h7,
+            map_a1d1d4,
+            map_kk,
+            binomial,
+            map_pawns,
+            lead_pawn_idx,
+            lead_pawns_size,
+        }
+    }
+}
+
+fn encoding() -> &'static Encoding {
+    static ENCODING: OnceLock<Encoding> = OnceLock::new();
+    ENCODING.get_or_init(Encoding::new)
+}
+
+fn read_u16_le(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32_le(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(data: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(pos..pos + 8)?.try_into().ok()?))
+}
+
+/// A file mapped into memory, read-only.
+#[cfg(unix)]
+struct Mapping {
+    ptr: *const u8,
+    len: usize,
+}
+
+// Safety: the mapping is never written to, and lives as long as the value.
+#[cfg(unix)]
+unsafe impl Send for Mapping {}
+#[cfg(unix)]
+unsafe impl Sync for Mapping {}
+
+#[cfg(unix)]
+impl Mapping {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        use std::fs::File;
+        use std::os::fd::AsRawFd;
+
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        // Safety: a fresh read-only mapping of the whole file, which may outlive `file`.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: `ptr` points to `len` mapped bytes until `drop`.
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // Safety: mapped in `open`, and no longer borrowed.
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Elsewhere, the file is read into memory instead.
+#[cfg(not(unix))]
+struct Mapping(Vec<u8>);
+
+#[cfg(not(unix))]
+impl Mapping {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        std::fs::read(path).map(Self)
+    }
+}
+
+#[cfg(not(unix))]
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
@@ -0,0 +1,122 @@
+use crate::book::{decode_move, encode_move, BookError, PolyglotBook, Selection};
+use crate::game_state::GameState;
+use crate::piece_move::Move;
+use std::collections::HashMap;
+
+/// A handful of positions: the start (with a move of weight 0 and an illegal one), the
+/// Sicilian after 1. e4, both castlings, and a queen and a knight promotion.
+const BOOK: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data/book.bin");
+
+fn book() -> PolyglotBook {
+    PolyglotBook::open(BOOK).unwrap()
+}
+
+fn uci_moves(book: &PolyglotBook, state: &GameState) -> Vec<(String, u16)> {
+    book.moves(state)
+        .into_iter()
+        .map(|book_move| (book_move.m.to_uci(), book_move.weight))
+        .collect()
+}
+
+fn pairs(moves: &[(&str, u16)]) -> Vec<(String, u16)> {
+    moves.iter().map(|&(m, w)| (m.to_string(), w)).collect()
+}
+
+#[test]
+fn looks_up_moves() {
+    let book = book();
+    assert_eq!(book.len(), 11);
+
+    let mut state = GameState::starting();
+    assert_eq!(
+        uci_moves(&book, &state),
+        pairs(&[("e2e4", 10), ("d2d4", 5), ("g1f3", 1)])
+    );
+
+    state.perform_move(state.parse_uci_move("e2e4").unwrap());
+    assert_eq!(uci_moves(&book, &state), pairs(&[("c7c5", 7), ("e7e5", 3)]));
+
+    state.perform_move(state.parse_uci_move("e7e5").unwrap());
+    assert_eq!(uci_moves(&book, &state), []);
+}
+
+#[test]
+fn castling_and_promotions() {
+    let book = book();
+    let state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let moves = book.moves(&state);
+    assert!(moves.iter().all(|m| matches!(m.m, Move::Castle { .. })));
+    assert_eq!(uci_moves(&book, &state), pairs(&[("e1g1", 2), ("e1c1", 1)]));
+
+    let state = GameState::parse_from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(
+        uci_moves(&book, &state),
+        pairs(&[("a7a8q", 4), ("a7a8n", 1)])
+    );
+}
+
+#[test]
+fn move_encoding() {
+    // the king takes its own rook.
+    let state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+    let castle = state.parse_uci_move("e8c8").unwrap();
+    assert_eq!(encode_move(castle), 0o7470);
+    assert_eq!(decode_move(&state, 0o7470), Some(castle));
+
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/p7/4K3 b - - 0 1").unwrap();
+    let promotion = state.parse_uci_move("a2a1r").unwrap();
+    assert_eq!(encode_move(promotion), 3 << 12 | 0o1000);
+    assert_eq!(decode_move(&state, encode_move(promotion)), Some(promotion));
+    // not without the promotion piece.
+    assert_eq!(decode_move(&state, 0o1000), None);
+
+    for state in super::perft::perft_suite()
+        .into_iter()
+        .map(|(state, _)| state)
+    {
+        for m in state.legal_moves() {
+            assert_eq!(decode_move(&state, encode_move(m)), Some(m));
+        }
+    }
+}
+
+#[test]
+fn selection() {
+    let book = book();
+    let state = GameState::starting();
+    let pick = |selection, random| book.pick_with(&state, selection, random).unwrap().to_uci();
+
+    assert_eq!(pick(Selection::Best, 7), "e2e4");
+    // weights 10, 5 and 1.
+    assert_eq!(pick(Selection::WeightedRandom, 9), "e2e4");
+    assert_eq!(pick(Selection::WeightedRandom, 10), "d2d4");
+    assert_eq!(pick(Selection::WeightedRandom, 15), "g1f3");
+    assert_eq!(pick(Selection::WeightedRandom, 16), "e2e4");
+    assert_eq!(pick(Selection::Uniform, 2), "g1f3");
+
+    let mut counts = HashMap::new();
+    for _ in 0..200 {
+        let m = book.pick(&state, Selection::WeightedRandom).unwrap();
+        *counts.entry(m.to_uci()).or_insert(0) += 1;
+    }
+    assert!(counts["e2e4"] > counts.get("g1f3").copied().unwrap_or(0));
+
+    let out_of_book = GameState::parse_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    for selection in Selection::ALL {
+        assert_eq!(book.pick(&out_of_book, selection), None);
+        assert_eq!(selection.name().parse(), Ok(selection));
+    }
+}
+
+#[test]
+fn invalid_books() {
+    assert!(matches!(
+        PolyglotBook::from_bytes(&[0; 20]),
+        Err(BookError::Truncated(20))
+    ));
+    assert!(matches!(
+        PolyglotBook::open("test_data/missing.bin"),
+        Err(BookError::Io(_))
+    ));
+    assert!(PolyglotBook::from_bytes(&[]).unwrap().is_empty());
+}
@@ -0,0 +1,142 @@
+use crate::book::PolyglotBook;
+use crate::book_builder::{BookBuilder, BuildBookError, BuildOptions, MoveStats};
+use crate::game_state::GameState;
+use crate::pgn::{PgnError, PgnErrorKind};
+use crate::piece::PieceColor;
+
+const GAMES: &str = r#"
+[Event "Open"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {the main line} Nc6 (2... d6 3. d4) 3. Bb5 $1 a6 1-0
+
+[Event "Open"]
+[Result "1/2-1/2"]
+1.e4 c5 2.Nf3 d6 ; the Najdorf, eventually
+3.d4 1/2-1/2
+
+[Event "Open"]
+[Result "0-1"]
+1. e4 c5 2. Nc3 0-1
+
+[Event "Unfinished"]
+[Result "*"]
+1. d4 *
+
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"]
+[Result "1-0"]
+1. O-O-O Kf7 1-0
+"#;
+
+fn build(options: BuildOptions) -> (BookBuilder, PolyglotBook) {
+    let mut builder = BookBuilder::new(options);
+    builder.add_pgn(GAMES).unwrap();
+    let mut bytes = Vec::new();
+    builder.write(&mut bytes).unwrap();
+    (builder, PolyglotBook::from_bytes(&bytes).unwrap())
+}
+
+fn moves(book: &PolyglotBook, state: &GameState) -> Vec<(String, u16)> {
+    book.moves(state)
+        .into_iter()
+        .map(|book_move| (state.move_to_san(book_move.m), book_move.weight))
+        .collect()
+}
+
+fn after(uci: &[&str]) -> GameState {
+    let mut state = GameState::starting();
+    for m in uci {
+        state.perform_move(state.parse_uci_move(m).unwrap());
+    }
+    state
+}
+
+#[test]
+fn counts_results_per_move() {
+    let (builder, book) = build(BuildOptions::default());
+    assert_eq!(builder.games(), 4);
+
+    // a win and a draw with 1. e4, and a loss.
+    assert_eq!(moves(&book, &after(&[])), [("e4".to_string(), 3)]);
+    // moves that only lost are kept, but with a weight of 0.
+    let after_e4 = after(&["e2e4"]);
+    assert_eq!(moves(&book, &after_e4), [("c5".to_string(), 3)]);
+    assert_eq!(book.entries(after_e4.zobrist()).len(), 2);
+    assert_eq!(
+        moves(&book, &after(&["e2e4", "c7c5"])),
+        [("Nf3".to_string(), 1)]
+    );
+    // variations don't count.
+    let after_nf3 = after(&["e2e4", "e7e5", "g1f3"]);
+    assert_eq!(book.entries(after_nf3.zobrist()).len(), 1);
+
+    let start = GameState::parse_from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+    assert_eq!(moves(&book, &start), [("O-O-O".to_string(), 2)]);
+}
+
+#[test]
+fn filters() {
+    let (_, book) = build(BuildOptions {
+        max_ply: 2,
+        ..BuildOptions::default()
+    });
+    assert_eq!(moves(&book, &after(&["e2e4", "c7c5"])), []);
+    assert_eq!(book.len(), 5);
+
+    // only 1. e4 was played in 3 games.
+    let (_, book) = build(BuildOptions {
+        min_games: 3,
+        ..BuildOptions::default()
+    });
+    assert_eq!(book.len(), 1);
+
+    // 1. e4 scored 1.5 out of 3.
+    let (_, book) = build(BuildOptions {
+        min_score: 0.5,
+        ..BuildOptions::default()
+    });
+    assert_eq!(moves(&book, &after(&[])), [("e4".to_string(), 3)]);
+    assert_eq!(moves(&book, &after(&["e2e4"])), [("c5".to_string(), 3)]);
+}
+
+#[test]
+fn move_stats() {
+    let mut builder = BookBuilder::new(BuildOptions::default());
+    let start = GameState::starting();
+    let e4 = start.parse_uci_move("e2e4").unwrap();
+    for winner in [Some(PieceColor::White), None, Some(PieceColor::Black)] {
+        builder.add_game(start, &[e4], winner);
+    }
+    let stats = MoveStats {
+        wins: 1,
+        draws: 1,
+        losses: 1,
+    };
+    assert_eq!(stats.games(), 3);
+    assert_eq!(stats.score(), 0.5);
+    assert_eq!(builder.entries()[0].weight, 3);
+}
+
+#[test]
+fn invalid_games() {
+    let mut builder = BookBuilder::new(BuildOptions::default());
+    let error = builder
+        .add_pgn("1. e4 e5 1-0\n\n1. e4 Ke7 2. Qh5 Ke6 3. e5 Qxh5 0-1")
+        .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            BuildBookError {
+                game: 2,
+                error: PgnError {
+                    line: 3,
+                    column: 7,
+                    kind: PgnErrorKind::InvalidMove(_),
+                },
+            }
+        ),
+        "{error}"
+    );
+}
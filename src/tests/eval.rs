@@ -0,0 +1,152 @@
+use crate::eval::{self, ParseWeightsError, Tapered, Weights};
+use crate::game_state::GameState;
+
+fn evaluate(fen: &str) -> i32 {
+    eval::evaluate(
+        &GameState::parse_from_fen(fen).unwrap(),
+        &Weights::default(),
+    )
+}
+
+/// The same position with the colors swapped and the board flipped.
+fn mirror(fen: &str) -> String {
+    let fields = fen.split(' ').collect::<Vec<_>>();
+    let swap_case = |s: &str| {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_uppercase() {
+                    c.to_ascii_lowercase()
+                } else {
+                    c.to_ascii_uppercase()
+                }
+            })
+            .collect::<String>()
+    };
+
+    let board = fields[0].split('/').rev().collect::<Vec<_>>().join("/");
+    let side = if fields[1] == "w" { "b" } else { "w" };
+    let mut castling = swap_case(fields[2]).chars().collect::<Vec<_>>();
+    castling.sort_by_key(|c| (c.is_ascii_lowercase(), *c));
+    let en_passant = fields[3]
+        .replace('3', "x")
+        .replace('6', "3")
+        .replace('x', "6");
+
+    format!(
+        "{} {side} {} {en_passant} {} {}",
+        swap_case(&board),
+        castling.into_iter().collect::<String>(),
+        fields[4],
+        fields[5]
+    )
+}
+
+#[test]
+fn evaluation_is_symmetric() {
+    assert_eq!(
+        evaluate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+        0
+    );
+
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR b KQkq - 0 4",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1",
+    ] {
+        assert_eq!(evaluate(fen), evaluate(&mirror(fen)), "{fen}");
+        // and from the other side's point of view, the opposite.
+        let other_side = fen.replacen(" w ", " b ", 1);
+        if other_side != fen {
+            assert_eq!(evaluate(fen), -evaluate(&other_side), "{fen}");
+        }
+    }
+}
+
+#[test]
+fn evaluation_terms() {
+    // a pawn up is better.
+    assert!(evaluate("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1") > 0);
+    assert!(evaluate("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1") < 0);
+
+    // the bishop pair is worth a bonus.
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+    let without_pair = Weights {
+        bishop_pair: Tapered::ZERO,
+        ..Weights::default()
+    };
+    assert_eq!(
+        eval::evaluate(&state, &Weights::default()) - eval::evaluate(&state, &without_pair),
+        // nearly all the way into the endgame.
+        (30 * 2 + 50 * 22) / 24
+    );
+
+    // a passed pawn is better than one that is blocked.
+    assert!(
+        evaluate("4k3/8/8/8/3P4/8/8/4K3 w - - 0 1")
+            > evaluate("4k3/3p4/8/8/3P4/8/8/4K3 w - - 0 1") + Weights::default().material[0].eg
+    );
+
+    // doubled and isolated pawns are weaknesses.
+    assert!(
+        evaluate("4k3/8/8/8/8/8/2PP4/4K3 w - - 0 1")
+            > evaluate("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1")
+    );
+
+    // in the middlegame, the king wants to stay behind its pawns; in the endgame it
+    // wants to be in the center.
+    assert!(
+        evaluate("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQ1RK1 w - - 0 1")
+            > evaluate("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/2N1KN2/PPPP1PPP/R1BQ1R2 w - - 0 1")
+    );
+    assert!(evaluate("4k3/8/8/8/4K3/8/8/8 w - - 0 1") > 0);
+}
+
+#[test]
+fn game_phase() {
+    let phase = |fen| eval::phase(GameState::parse_from_fen(fen).unwrap().board());
+    assert_eq!(
+        phase("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+        24
+    );
+    assert_eq!(phase("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"), 0);
+    assert_eq!(phase("3rk3/8/8/8/8/8/8/3QK3 w - - 0 1"), 6);
+}
+
+#[test]
+fn weights_round_trip() {
+    let weights = Weights::default();
+    assert_eq!(weights.to_string().parse::<Weights>(), Ok(weights.clone()));
+
+    // terms that are left out keep their default weights.
+    let tuned = "# tuned\n\nbishop_pair 40/60\nmaterial 1/2 3/4 5/6 7/8 9/10 0/0 # comment\n"
+        .parse::<Weights>()
+        .unwrap();
+    assert_eq!(tuned.bishop_pair, Tapered::new(40, 60));
+    assert_eq!(tuned.material[4], Tapered::new(9, 10));
+    assert_eq!(tuned.piece_square, weights.piece_square);
+}
+
+#[test]
+fn invalid_weights() {
+    assert_eq!(
+        "\nbishop_trio 1/1".parse::<Weights>(),
+        Err(ParseWeightsError::UnknownTerm(2, "bishop_trio".to_string()))
+    );
+    assert_eq!(
+        "bishop_pair 30".parse::<Weights>(),
+        Err(ParseWeightsError::InvalidWeight(1))
+    );
+    assert_eq!(
+        "bishop_pair 30/x".parse::<Weights>(),
+        Err(ParseWeightsError::InvalidWeight(1))
+    );
+    assert_eq!(
+        "passed_pawn 1/1 2/2".parse::<Weights>(),
+        Err(ParseWeightsError::WrongCount {
+            line: 1,
+            expected: 8,
+            got: 2
+        })
+    );
+}
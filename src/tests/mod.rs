@@ -1,5 +1,31 @@
-use crate::board_position::{BoardColumn, BoardPosition};
-use crate::game_state::GameState;
+use crate::attacks;
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::board_position::{BoardColumn, BoardIndex, BoardPosition};
+use crate::castle_rights::CastleRights;
+use crate::en_passant_target::EnPassantTarget;
+use crate::game_state::{GameState, ParseUciMoveError};
+use crate::outcome::{DecisiveReason, DrawReason, Outcome};
+use crate::piece::PieceColor;
+use crate::piece_move::{CastlingNotation, Move};
+use crate::san::ParseSanError;
+
+mod book;
+mod book_builder;
+mod eval;
+mod move_picker;
+mod perft;
+mod pgn;
+mod pipe;
+mod search;
+#[cfg(feature = "syzygy")]
+mod syzygy;
+#[cfg(feature = "syzygy")]
+mod tablebase;
+mod time_manager;
+mod transposition;
+mod uci;
+mod xboard;
 
 #[test]
 fn can_parse_all_board_positions() {
@@ -27,3 +53,739 @@ fn can_parse_all_board_positions() {
 fn correct_starting() {
     let _starting = GameState::starting();
 }
+
+fn castles(state: &GameState) -> Vec<String> {
+    state
+        .legal_moves()
+        .filter(|m| matches!(m, Move::Castle { .. }))
+        .map(|m| m.to_string())
+        .collect()
+}
+
+#[test]
+fn castling_both_sides() {
+    let state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(castles(&state), ["E1 -> G1", "E1 -> C1"]);
+
+    let state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+    assert_eq!(castles(&state), ["E8 -> G8", "E8 -> C8"]);
+}
+
+#[test]
+fn castling_respects_rights_and_blockers() {
+    let state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+    assert_eq!(castles(&state), ["E1 -> G1"]);
+
+    let state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/RN2K1NR w KQkq - 0 1").unwrap();
+    assert!(castles(&state).is_empty());
+}
+
+#[test]
+fn castling_not_out_of_or_through_check() {
+    // f1 is attacked, so only the queen side is available.
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1").unwrap();
+    assert_eq!(castles(&state), ["E1 -> C1"]);
+
+    // the king is in check.
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1").unwrap();
+    assert!(castles(&state).is_empty());
+
+    // b1 may be attacked, the king does not pass through it.
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/1r6/R3K3 w Q - 0 1").unwrap();
+    assert_eq!(castles(&state), ["E1 -> C1"]);
+}
+
+#[test]
+fn castling_moves_both_pieces_and_revokes_rights() {
+    let mut state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let m = state
+        .legal_moves()
+        .find(|m| m.to_string() == "E1 -> G1")
+        .unwrap();
+    state.perform_move(m);
+    assert_eq!(state.to_fen(), "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1");
+}
+
+#[test]
+fn capturing_a_rook_revokes_its_castle_right() {
+    let mut state = GameState::parse_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let m = state
+        .legal_moves()
+        .find(|m| m.to_string() == "H1 -> H8")
+        .unwrap();
+    state.perform_move(m);
+    assert_eq!(state.to_fen(), "r3k2R/8/8/8/8/8/8/R3K3 b Qq - 0 1");
+}
+
+#[test]
+fn pawn_promotion_generates_all_choices() {
+    let state = GameState::parse_from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let promotions = state
+        .legal_moves()
+        .filter(|m| matches!(m, Move::Promotion { .. }))
+        .map(|m| m.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        promotions,
+        [
+            "A7 -> A8=Q",
+            "A7 -> A8=R",
+            "A7 -> A8=B",
+            "A7 -> A8=N",
+            "A7 -> B8=Q",
+            "A7 -> B8=R",
+            "A7 -> B8=B",
+            "A7 -> B8=N",
+        ]
+    );
+    assert!(!state
+        .legal_moves()
+        .any(|m| matches!(m, Move::Simple(from, _) if from.rank() == 7)));
+}
+
+#[test]
+fn pawn_promotion_round_trips_through_fen() {
+    let mut state = GameState::parse_from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 7 30").unwrap();
+    let m = state
+        .legal_moves()
+        .find(|m| m.to_string() == "A7 -> B8=N")
+        .unwrap();
+    state.perform_move(m);
+    let fen = state.to_fen();
+    assert_eq!(fen, "1N2k3/8/8/8/8/8/8/4K3 b - - 0 30");
+    assert_eq!(GameState::parse_from_fen(&fen).unwrap(), state);
+
+    let mut state = GameState::parse_from_fen("4k3/8/8/8/8/8/p7/4K3 b - - 0 1").unwrap();
+    let m = state
+        .legal_moves()
+        .find(|m| m.to_string() == "A2 -> A1=Q")
+        .unwrap();
+    state.perform_move(m);
+    assert_eq!(state.to_fen(), "4k3/8/8/8/8/8/8/q3K3 w - - 0 2");
+}
+
+#[test]
+fn capturing_promotion_attacks_the_king() {
+    // the king may not step onto a square the pawn would capture while promoting.
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/4p3/2K5 w - - 0 1").unwrap();
+    assert!(!state.legal_moves().any(|m| m.to_string() == "C1 -> D1"));
+    assert!(state.legal_moves().any(|m| m.to_string() == "C1 -> D2"));
+}
+
+fn pos(s: &str) -> BoardIndex {
+    s.parse::<BoardPosition>().unwrap().to_index()
+}
+
+#[test]
+fn square_attacks() {
+    let state = GameState::parse_from_fen("4k3/8/3p4/8/1N6/8/8/R3K2b w - - 0 1").unwrap();
+    let board = state.board();
+    // pawns
+    assert!(board.is_square_attacked(pos("C5"), PieceColor::Black));
+    assert!(board.is_square_attacked(pos("E5"), PieceColor::Black));
+    assert!(!board.is_square_attacked(pos("C7"), PieceColor::Black));
+    // knight
+    assert!(board.is_square_attacked(pos("A2"), PieceColor::White));
+    assert!(board.is_square_attacked(pos("C6"), PieceColor::White));
+    // kings
+    assert!(board.is_square_attacked(pos("D7"), PieceColor::Black));
+    assert!(board.is_square_attacked(pos("F1"), PieceColor::White));
+    // sliders stop at the first piece in the way
+    assert!(board.is_square_attacked(pos("D1"), PieceColor::White));
+    assert!(board.is_square_attacked(pos("A8"), PieceColor::White));
+    assert!(!board.is_square_attacked(pos("G1"), PieceColor::White));
+    assert!(board.is_square_attacked(pos("A8"), PieceColor::Black));
+    assert!(!board.is_square_attacked(pos("B8"), PieceColor::Black));
+}
+
+#[test]
+fn checks_and_checkers() {
+    let state = GameState::starting();
+    assert!(!state.is_check());
+    assert!(state.checkers().is_empty());
+
+    // double check by a knight and a rook.
+    let state = GameState::parse_from_fen("4r1k1/8/8/8/8/3n4/8/4K3 w - - 0 1").unwrap();
+    assert!(state.is_check());
+    assert!(state.board().is_in_check(PieceColor::White));
+    assert!(!state.board().is_in_check(PieceColor::Black));
+    let checkers = state.checkers().iter().collect::<Vec<_>>();
+    assert_eq!(checkers, [pos("D3"), pos("E8")]);
+    // only king moves can answer a double check.
+    assert!(state
+        .legal_moves()
+        .all(|m| matches!(m, Move::Simple(from, _) if from == pos("E1"))));
+
+    // no king, no check.
+    let state = GameState::parse_from_fen("4r3/8/8/8/8/8/8/8 w - - 0 1").unwrap();
+    assert!(!state.is_check());
+}
+
+#[test]
+fn en_passant_cannot_expose_the_king() {
+    // capturing en passant would clear the fifth rank between the king and the rook.
+    let state = GameState::parse_from_fen("4k3/8/8/KPp4r/8/8/8/8 w - c6 0 1").unwrap();
+    assert!(!state
+        .legal_moves()
+        .any(|m| matches!(m, Move::EnPassant { .. })));
+
+    let state = GameState::parse_from_fen("4k3/8/8/1Pp4r/8/8/8/K7 w - c6 0 1").unwrap();
+    assert!(state
+        .legal_moves()
+        .any(|m| matches!(m, Move::EnPassant { .. })));
+}
+
+#[test]
+fn en_passant_at_board_edges() {
+    let state = GameState::parse_from_fen("4k3/8/8/Pp6/8/8/8/4K3 w - b6 0 1").unwrap();
+    let ep = state
+        .legal_moves()
+        .filter(|m| matches!(m, Move::EnPassant { .. }))
+        .map(|m| m.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(ep.len(), 1);
+
+    let state = GameState::parse_from_fen("4k3/8/8/6pP/8/8/8/4K3 w - g6 0 1").unwrap();
+    assert_eq!(
+        state
+            .legal_moves()
+            .filter(|m| matches!(m, Move::EnPassant { .. }))
+            .count(),
+        1
+    );
+
+    // pawns on the first and last ranks, with a target that would be diagonally off the board.
+    let board = Board::parse_from_fen("P6P/8/8/8/8/8/8/p6p").unwrap();
+    for (turn, target) in [(PieceColor::White, "B6"), (PieceColor::Black, "G3")] {
+        let ept = Some(EnPassantTarget(pos(target)));
+        let moves = board
+            .all_legal_moves_for_turn(turn, ept, CastleRights::EMPTY)
+            .collect::<Vec<_>>();
+        assert!(!moves.iter().any(|m| matches!(m, Move::EnPassant { .. })));
+    }
+}
+
+#[test]
+fn impossible_en_passant_targets() {
+    // not on the 3rd or 6th rank.
+    assert!(GameState::parse_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d5 0 1").is_err());
+    assert!(GameState::parse_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - x9 0 1").is_err());
+
+    // no pawn could have made the double push, so the target is dropped.
+    let state = GameState::parse_from_fen("4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1").unwrap();
+    assert_eq!(state.to_fen(), "4k3/8/8/4P3/8/8/8/4K3 w - - 0 1");
+    let state = GameState::parse_from_fen("4k3/8/8/3pP3/8/8/8/4K3 b - d6 0 1").unwrap();
+    assert_eq!(state.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 b - - 0 1");
+
+    let state = GameState::parse_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+    assert_eq!(state.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+}
+
+/// A small, seeded xorshift generator, for tests that want many varied inputs.
+pub(crate) fn xorshift(mut seed: u64) -> impl FnMut() -> u64 {
+    move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    }
+}
+
+#[test]
+fn fuzzed_fens_do_not_panic() {
+    const SEEDS: [&str; 4] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d6 255 65535",
+    ];
+    const ALPHABET: &[u8] = b"pnbrqkPNBRQK0123456789/ -wbKQkqacdefgh";
+
+    let mut next = xorshift(0x2545_f491_4f6c_dd1d);
+
+    for i in 0..2000 {
+        let mut fen = SEEDS[i % SEEDS.len()].as_bytes().to_vec();
+        for _ in 0..=next() % 4 {
+            let at = (next() % fen.len() as u64) as usize;
+            fen[at] = ALPHABET[(next() % ALPHABET.len() as u64) as usize];
+        }
+        let fen = String::from_utf8(fen).unwrap();
+
+        if let Ok(state) = GameState::parse_from_fen(&fen) {
+            for m in state.legal_moves() {
+                let mut state = state;
+                state.perform_move(m);
+                let _ = state.legal_moves().count();
+                let _ = state.to_fen();
+            }
+        }
+    }
+}
+
+fn play(state: &mut GameState, moves: &[&str]) -> Vec<GameState> {
+    let mut history = Vec::new();
+    for name in moves {
+        let m = state
+            .legal_moves()
+            .find(|m| m.to_string() == *name)
+            .unwrap_or_else(|| panic!("{name} is not legal in {}", state.to_fen()));
+        history.push(*state);
+        state.perform_move(m);
+    }
+    history
+}
+
+fn fen_outcome(fen: &str) -> Option<Outcome> {
+    GameState::parse_from_fen(fen).unwrap().outcome()
+}
+
+#[test]
+fn checkmate_and_stalemate() {
+    let mut state = GameState::starting();
+    assert_eq!(state.outcome(), None);
+    play(
+        &mut state,
+        &["F2 -> F3", "E7 -> E5", "G2 -> G4", "D8 -> H4"],
+    );
+    assert_eq!(
+        state.outcome(),
+        Some(Outcome::Decisive {
+            winner: PieceColor::Black,
+            reason: DecisiveReason::Checkmate
+        })
+    );
+
+    assert_eq!(
+        fen_outcome("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+        Some(Outcome::Draw {
+            reason: DrawReason::Stalemate
+        })
+    );
+
+    // mate on the move that reaches the 75 move limit still counts.
+    assert_eq!(
+        fen_outcome("R5k1/5ppp/8/8/8/8/8/6K1 b - - 150 120").and_then(Outcome::winner),
+        Some(PieceColor::White)
+    );
+}
+
+#[test]
+fn move_rule_draws() {
+    let draw = |reason| Some(Outcome::Draw { reason });
+
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/R3K3 w - - 99 80"), None);
+    let fifty = fen_outcome("4k3/8/8/8/8/8/8/R3K3 w - - 100 80");
+    assert_eq!(fifty, draw(DrawReason::FiftyMoveRule));
+    assert!(!fifty.unwrap().is_automatic());
+    let seventy_five = fen_outcome("4k3/8/8/8/8/8/8/R3K3 w - - 150 80");
+    assert_eq!(seventy_five, draw(DrawReason::SeventyFiveMoveRule));
+    assert!(seventy_five.unwrap().is_automatic());
+
+    // the clock is taken as it is, even in a position set up at move 1.
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/R3K3 w - - 100 1"), fifty);
+}
+
+#[test]
+fn insufficient_material() {
+    let dead = Some(Outcome::Draw {
+        reason: DrawReason::InsufficientMaterial,
+    });
+
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), dead);
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"), dead);
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/1N2K3 b - - 0 1"), dead);
+    // bishops on dark squares only.
+    assert_eq!(fen_outcome("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"), dead);
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1"), dead);
+
+    assert_eq!(fen_outcome("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1"), None);
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1"), None);
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"), None);
+    assert_eq!(fen_outcome("4k3/8/8/8/8/8/P7/4K3 w - - 0 1"), None);
+}
+
+#[test]
+fn repetitions() {
+    let shuffle = ["G1 -> F3", "G8 -> F6", "F3 -> G1", "F6 -> G8"];
+    let mut state = GameState::starting();
+    let mut history = Vec::new();
+
+    history.extend(play(&mut state, &shuffle));
+    assert_eq!(state.repetition_count(&history), 2);
+    assert_eq!(state.outcome_with_history(&history), None);
+
+    history.extend(play(&mut state, &shuffle));
+    assert_eq!(state.repetition_count(&history), 3);
+    let threefold = state.outcome_with_history(&history).unwrap();
+    assert_eq!(
+        threefold,
+        Outcome::Draw {
+            reason: DrawReason::ThreefoldRepetition
+        }
+    );
+    assert!(!threefold.is_automatic());
+
+    history.extend(play(&mut state, &shuffle));
+    history.extend(play(&mut state, &shuffle));
+    assert_eq!(
+        state.outcome_with_history(&history),
+        Some(Outcome::Draw {
+            reason: DrawReason::FivefoldRepetition
+        })
+    );
+
+    // a pawn move makes the earlier positions unreachable.
+    history.extend(play(&mut state, &["E2 -> E4"]));
+    assert_eq!(state.repetition_count(&history), 1);
+}
+
+#[test]
+fn bitboard_basics() {
+    let bb = Bitboard::from_index(pos("A1")) | Bitboard::from_index(pos("H8"));
+    assert_eq!(bb.0, 1 | 1 << 63);
+    assert_eq!(bb.count(), 2);
+    assert!(bb.has_many());
+    assert_eq!(bb.first(), Some(pos("A1")));
+    assert_eq!(bb.last(), Some(pos("H8")));
+    assert_eq!(bb.iter().collect::<Vec<_>>(), [pos("A1"), pos("H8")]);
+    assert_eq!(bb.iter().collect::<Bitboard>(), bb);
+    assert_eq!(Bitboard::file(8), Bitboard::FILE_H);
+    assert_eq!(Bitboard::rank(8), Bitboard::RANK_8);
+    assert_eq!(Bitboard::EMPTY.first(), None);
+    assert!(!Bitboard::EMPTY.has_many());
+}
+
+#[test]
+fn attack_tables() {
+    let squares = |bb: Bitboard| bb.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(squares(attacks::knight_attacks(pos("A1"))), ["C2", "B3"]);
+    assert_eq!(attacks::king_attacks(pos("E4")).count(), 8);
+    assert_eq!(
+        squares(attacks::king_attacks(pos("H8"))),
+        ["G7", "H7", "G8"]
+    );
+    assert_eq!(
+        squares(attacks::pawn_attacks(PieceColor::White, pos("A2"))),
+        ["B3"]
+    );
+    assert_eq!(
+        squares(attacks::pawn_attacks(PieceColor::Black, pos("E5"))),
+        ["D4", "F4"]
+    );
+
+    let occupied = Bitboard::from_index(pos("D6")) | Bitboard::from_index(pos("F4"));
+    assert_eq!(
+        squares(attacks::rook_attacks(pos("D4"), occupied)),
+        ["D1", "D2", "D3", "A4", "B4", "C4", "E4", "F4", "D5", "D6"]
+    );
+    assert_eq!(
+        squares(attacks::bishop_attacks(pos("H2"), occupied)),
+        ["G1", "G3", "F4"]
+    );
+    assert_eq!(
+        attacks::queen_attacks(pos("D4"), Bitboard::EMPTY).count(),
+        27
+    );
+}
+
+#[test]
+fn board_bitboards_follow_moves() {
+    let mut state = GameState::parse_from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+
+    for _ in 0..12 {
+        let board = state.board();
+        for index in (0..64).map(|i| BoardIndex::new(i).unwrap()) {
+            match board.get_piece_at(index) {
+                Some(piece) => {
+                    assert!(board.pieces(piece).contains(index));
+                    assert!(board.occupied().contains(index));
+                }
+                None => assert!(!board.occupied().contains(index)),
+            }
+        }
+        assert_eq!(
+            board.occupied().count() as usize,
+            board.piece_iterator().count()
+        );
+
+        let m = state.legal_moves().last().unwrap();
+        state.perform_move(m);
+    }
+}
+
+#[test]
+fn zobrist_matches_polyglot() {
+    // reference keys from the Polyglot book format specification.
+    let reference = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            0x463b96181691fc9c,
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            0x823c9b50fd114196,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+            0x0756b94461c50fb0,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+            0x662fafb965db29d4,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            0x22a48b5a8e47ff78,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+            0x652a607ca3f242c1,
+        ),
+        (
+            "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+            0x00fdd303c946bdd9,
+        ),
+        (
+            "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+            0x3c8123ea7b067637,
+        ),
+        (
+            "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+            0x5c3f9b829b279560,
+        ),
+    ];
+
+    for (fen, key) in reference {
+        assert_eq!(
+            GameState::parse_from_fen(fen).unwrap().zobrist(),
+            key,
+            "{fen}"
+        );
+    }
+}
+
+#[test]
+fn zobrist_is_updated_incrementally() {
+    let mut state = GameState::parse_from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+    let mut seen = vec![state.zobrist()];
+
+    for i in 0..40 {
+        let moves = state.legal_moves().collect::<Vec<_>>();
+        if moves.is_empty() {
+            break;
+        }
+        let key = state.zobrist();
+        let m = moves[i * 7 % moves.len()];
+        let undo = state.make_move(m);
+        state.unmake_move(m, undo);
+        assert_eq!(state.zobrist(), key);
+        state.perform_move(m);
+
+        let fresh = GameState::parse_from_fen(&state.to_fen()).unwrap();
+        assert_eq!(state.zobrist(), fresh.zobrist(), "{}", state.to_fen());
+        seen.push(state.zobrist());
+    }
+
+    seen.sort();
+    seen.dedup();
+    assert!(seen.len() > 30);
+}
+
+#[test]
+fn san_formatting() {
+    let state = GameState::parse_from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+    let sans = state
+        .legal_moves()
+        .map(|m| state.move_to_san(m))
+        .collect::<Vec<_>>();
+    for san in [
+        "O-O", "O-O-O", "Nxf7", "Qxf6", "dxe6", "gxh3", "Bxa6", "Nb1", "Rb1",
+    ] {
+        assert!(sans.iter().any(|s| s == san), "{san} not in {sans:?}");
+    }
+
+    let san_of = |fen: &str, name: &str| {
+        let state = GameState::parse_from_fen(fen).unwrap();
+        let m = state.legal_moves().find(|m| m.to_string() == name).unwrap();
+        state.move_to_san(m)
+    };
+
+    // file, rank and full disambiguation.
+    assert_eq!(
+        san_of("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "A1 -> D1"),
+        "Rad1"
+    );
+    assert_eq!(san_of("R7/8/7k/8/8/8/8/R3K3 w - - 0 1", "A1 -> A4"), "R1a4");
+    assert_eq!(
+        san_of("4k3/8/8/8/8/8/8/Q1Q1K2Q w - - 0 1", "A1 -> B2"),
+        "Qab2"
+    );
+    assert_eq!(
+        san_of("2k5/8/8/8/7Q/8/K7/4Q2Q w - - 0 1", "H1 -> E4"),
+        "Qh1e4"
+    );
+    // en passant, promotion with check, mate.
+    assert_eq!(
+        san_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "E5 -> D6"),
+        "exd6"
+    );
+    assert_eq!(
+        san_of("7k/P7/8/8/8/8/8/4K3 w - - 0 1", "A7 -> A8=Q"),
+        "a8=Q+"
+    );
+    assert_eq!(
+        san_of("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "A1 -> A8"),
+        "Ra8#"
+    );
+}
+
+#[test]
+fn san_parsing() {
+    let state = GameState::starting();
+    assert_eq!(state.parse_san("Nf3").unwrap().to_string(), "G1 -> F3");
+    assert_eq!(state.parse_san("e4!?").unwrap().to_string(), "E2 -> E4");
+    assert!(matches!(
+        state.parse_san("e5"),
+        Err(ParseSanError::IllegalMove(_))
+    ));
+    assert!(matches!(
+        state.parse_san("Nz3"),
+        Err(ParseSanError::InvalidSyntax(_))
+    ));
+
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+    assert!(matches!(
+        state.parse_san("Rd1"),
+        Err(ParseSanError::AmbiguousMove(_))
+    ));
+    assert_eq!(state.parse_san("Rad1").unwrap().to_string(), "A1 -> D1");
+
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/8/R3K2R w K - 0 1").unwrap();
+    assert_eq!(state.parse_san("0-0").unwrap().to_string(), "E1 -> G1");
+    assert!(state.parse_san("O-O-O").is_err());
+
+    let state = GameState::parse_from_fen("1n5k/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(state.parse_san("axb8=N").unwrap().to_string(), "A7 -> B8=N");
+    assert_eq!(state.parse_san("a8Q").unwrap().to_string(), "A7 -> A8=Q");
+}
+
+#[test]
+fn san_round_trips() {
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ] {
+        let state = GameState::parse_from_fen(fen).unwrap();
+        let mut positions = vec![state];
+        positions.extend(state.legal_moves().map(|m| {
+            let mut next = state;
+            next.perform_move(m);
+            next
+        }));
+
+        for state in positions {
+            for m in state.legal_moves() {
+                let san = state.move_to_san(m);
+                assert_eq!(state.parse_san(&san), Ok(m), "{san} in {}", state.to_fen());
+            }
+        }
+    }
+}
+
+#[test]
+fn uci_moves() {
+    let state = GameState::parse_from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+    let castle = state.parse_uci_move("e1g1").unwrap();
+    assert!(matches!(castle, Move::Castle { .. }));
+    assert_eq!(castle.to_uci(), "e1g1");
+    assert_eq!(castle.to_uci_with(CastlingNotation::Chess960), "e1h1");
+    assert_eq!(state.parse_uci_move("e1h1"), Ok(castle));
+    assert_eq!(
+        state.parse_uci_move("e1c1").unwrap().to_string(),
+        "E1 -> C1"
+    );
+    assert_eq!(
+        state.parse_uci_move("e1e3"),
+        Err(ParseUciMoveError::IllegalMove("e1e3".to_string()))
+    );
+    for bad in ["", "e1", "e1g9", "e1g1x", "e2e4qq", "é1g1"] {
+        assert_eq!(
+            state.parse_uci_move(bad),
+            Err(ParseUciMoveError::InvalidSyntax(bad.to_string()))
+        );
+    }
+
+    let state = GameState::parse_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+    assert!(matches!(
+        state.parse_uci_move("e5d6"),
+        Ok(Move::EnPassant { .. })
+    ));
+
+    let state = GameState::parse_from_fen("1n5k/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let promotion = state.parse_uci_move("a7b8n").unwrap();
+    assert_eq!(promotion.to_string(), "A7 -> B8=N");
+    assert_eq!(promotion.to_uci(), "a7b8n");
+    assert!(state.parse_uci_move("a7a8").is_err());
+
+    for (state, _) in perft::perft_suite() {
+        for m in state.legal_moves() {
+            assert_eq!(state.parse_uci_move(&m.to_uci()), Ok(m));
+            assert_eq!(
+                state.parse_uci_move(&m.to_uci_with(CastlingNotation::Chess960)),
+                Ok(m)
+            );
+        }
+    }
+}
+
+#[test]
+fn static_exchange_evaluation() {
+    let see = |fen: &str, name: &str| {
+        let state = GameState::parse_from_fen(fen).unwrap();
+        let m = state.legal_moves().find(|m| m.to_string() == name).unwrap();
+        state.board().see(m)
+    };
+
+    // an undefended pawn.
+    assert_eq!(
+        see(
+            "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+            "E1 -> E5"
+        ),
+        100
+    );
+    // knight for pawn, with the x-rayed rook and queen behind both sides joining in.
+    assert_eq!(
+        see(
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "D3 -> E5"
+        ),
+        -200
+    );
+    // the queen recaptures, but white stops before losing the rook.
+    assert_eq!(see("4k3/8/3q4/8/3p4/8/3R4/3RK3 w - - 0 1", "D2 -> D4"), 100);
+    // the king may only recapture once nothing can take it back.
+    assert_eq!(see("4k3/8/8/b7/8/8/3p4/1N2K3 w - - 0 1", "B1 -> D2"), 100);
+    assert_eq!(see("3rk3/8/8/b7/8/8/3p4/1N2K3 w - - 0 1", "B1 -> D2"), -200);
+    // en passant and promotions.
+    assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "E5 -> D6"), 100);
+    assert_eq!(see("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "A7 -> A8=Q"), 800);
+    assert_eq!(see("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "B7 -> A8=Q"), 1300);
+    // a quiet move that hangs a piece.
+    assert_eq!(see("4k3/8/8/8/2p5/8/8/3QK3 w - - 0 1", "D1 -> D3"), -900);
+}
@@ -0,0 +1,128 @@
+use super::perft::perft_suite;
+use crate::board_position::BoardIndex;
+use crate::game_state::GameState;
+use crate::move_picker::{MoveOrdering, MovePicker};
+use crate::piece_move::{Move, MoveSet};
+use std::collections::HashSet;
+
+/// The suite positions and the positions one move into them.
+fn positions() -> Vec<GameState> {
+    perft_suite()
+        .into_iter()
+        .flat_map(|(state, _)| {
+            let children = state.legal_moves().map(move |m| {
+                let mut child = state;
+                child.perform_move(m);
+                child
+            });
+            std::iter::once(state).chain(children.collect::<Vec<_>>())
+        })
+        .collect()
+}
+
+fn possible_moves(state: &GameState, set: MoveSet) -> Vec<Move> {
+    let mut moves = Vec::new();
+    state.push_possible_moves(set, &mut moves);
+    moves
+}
+
+#[test]
+fn captures_and_quiets_make_up_all_moves() {
+    for state in positions() {
+        let captures = possible_moves(&state, MoveSet::Captures);
+        let quiets = possible_moves(&state, MoveSet::Quiets);
+        let all = possible_moves(&state, MoveSet::All);
+
+        assert_eq!(
+            captures.len() + quiets.len(),
+            all.len(),
+            "{}",
+            state.to_fen()
+        );
+        let split = captures.iter().chain(&quiets).collect::<HashSet<_>>();
+        assert_eq!(split, all.iter().collect(), "{}", state.to_fen());
+
+        let board = state.board();
+        assert!(captures
+            .iter()
+            .all(|&m| board.is_capture(m) || m.promotion().is_some()));
+        assert!(quiets
+            .iter()
+            .all(|&m| !board.is_capture(m) && m.promotion().is_none()));
+    }
+}
+
+fn picked(state: &GameState, ordering: &MoveOrdering, hash_move: Option<Move>) -> Vec<Move> {
+    let mut picker = MovePicker::new(ordering, 0, hash_move, None);
+    std::iter::from_fn(|| picker.next(state, ordering)).collect()
+}
+
+#[test]
+fn picks_every_legal_move_once() {
+    let empty = MoveOrdering::new();
+    for state in positions() {
+        let legal = state.legal_moves().collect::<HashSet<_>>();
+
+        let moves = picked(&state, &empty, None);
+        assert_eq!(moves.len(), legal.len(), "{}", state.to_fen());
+        assert_eq!(moves.iter().copied().collect::<HashSet<_>>(), legal);
+
+        // with a hash move and killers, which may not even be legal here.
+        let Some(&last) = moves.last() else {
+            continue;
+        };
+        let mut ordering = MoveOrdering::new();
+        for &killer in moves.iter().rev().take(3) {
+            ordering.update(state.next_move(), 0, 1, killer, None, &[]);
+        }
+        ordering.update(
+            state.next_move(),
+            0,
+            1,
+            Move::Simple(pos(0), pos(9)),
+            None,
+            &[],
+        );
+
+        let moves = picked(&state, &ordering, Some(last));
+        assert_eq!(moves[0], last);
+        assert_eq!(moves.len(), legal.len(), "{}", state.to_fen());
+        assert_eq!(moves.into_iter().collect::<HashSet<_>>(), legal);
+    }
+}
+
+#[test]
+fn picking_order() {
+    let state =
+        GameState::parse_from_fen("4k3/8/2p2p2/1n1pp2q/2P1P3/3N4/8/4K2R w K - 0 1").unwrap();
+    let uci = |m: Move| m.to_uci();
+    let moves = picked(&state, &MoveOrdering::new(), None);
+
+    // the most valuable victims first, then the least valuable attackers.
+    assert_eq!(uci(moves[0]), "h1h5");
+    assert_eq!(uci(moves[1]), "c4b5");
+    assert_eq!(
+        moves[2..4].iter().map(|&m| uci(m)).collect::<HashSet<_>>(),
+        HashSet::from(["c4d5".to_string(), "e4d5".to_string()])
+    );
+    // losing captures come last.
+    assert_eq!(uci(*moves.last().unwrap()), "d3e5");
+
+    // then killers, before the other quiet moves.
+    let mut ordering = MoveOrdering::new();
+    let castle = state.parse_uci_move("e1g1").unwrap();
+    ordering.update(state.next_move(), 0, 4, castle, None, &[]);
+    let moves = picked(&state, &ordering, None);
+    assert_eq!(moves[4], castle);
+
+    // and history orders the rest.
+    let push = state.parse_uci_move("c4c5").unwrap();
+    let mut ordering = MoveOrdering::new();
+    ordering.update(state.next_move(), 1, 4, push, None, &[]);
+    let moves = picked(&state, &ordering, None);
+    assert_eq!(moves[4], push);
+}
+
+fn pos(index: u8) -> BoardIndex {
+    BoardIndex::new(index).unwrap()
+}
@@ -0,0 +1,96 @@
+use crate::game_state::GameState;
+
+const PERFT_SUITE: &str = include_str!("../../test_data/perft.epd");
+
+/// Parses lines like `<fen without clocks> ;D1 20 ;D2 400`.
+pub(super) fn perft_suite() -> Vec<(GameState, Vec<(u32, u64)>)> {
+    PERFT_SUITE
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split(';');
+            let position = fields.next().unwrap().trim();
+            let state = GameState::parse_from_fen(&format!("{position} 0 1")).unwrap();
+            let counts = fields
+                .map(|field| {
+                    let (depth, count) = field.trim().split_once(' ').unwrap();
+                    let depth = depth.strip_prefix('D').unwrap().parse().unwrap();
+                    (depth, count.parse().unwrap())
+                })
+                .collect();
+            (state, counts)
+        })
+        .collect()
+}
+
+fn check_suite(max_nodes: u64) {
+    for (state, counts) in perft_suite() {
+        for (depth, expected) in counts {
+            if expected > max_nodes {
+                break;
+            }
+
+            assert_eq!(
+                state.perft(depth),
+                expected,
+                "perft({depth}) of {}",
+                state.to_fen()
+            );
+        }
+    }
+}
+
+#[test]
+fn perft_suite_shallow() {
+    check_suite(100_000);
+}
+
+#[test]
+#[ignore = "takes long, run with `cargo test --release -- --ignored`"]
+fn perft_suite_deep() {
+    check_suite(200_000_000);
+}
+
+#[test]
+fn divide_adds_up_to_perft() {
+    let state = GameState::parse_from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+    let divide = state.perft_divide(2);
+    assert_eq!(divide.len(), 48);
+    assert_eq!(divide.iter().map(|(_, n)| n).sum::<u64>(), state.perft(2));
+    assert!(GameState::starting().perft_divide(0).is_empty());
+}
+
+/// Walks the whole tree, checking that every move is taken back exactly.
+fn check_make_unmake(state: &mut GameState, depth: u32) {
+    if depth == 0 {
+        return;
+    }
+
+    let before = *state;
+    for m in before.legal_moves() {
+        let undo = state.make_move(m);
+        let fresh = GameState::parse_from_fen(&state.to_fen()).unwrap();
+        assert_eq!(
+            state.zobrist(),
+            fresh.zobrist(),
+            "{m} from {}",
+            before.to_fen()
+        );
+
+        check_make_unmake(state, depth - 1);
+
+        state.unmake_move(m, undo);
+        assert_eq!(*state, before, "{m} from {}", before.to_fen());
+        assert_eq!(state.zobrist(), before.zobrist());
+    }
+}
+
+#[test]
+fn make_unmake_round_trips() {
+    for (mut state, _) in perft_suite() {
+        check_make_unmake(&mut state, 2);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::game_state::GameState;
+use crate::pgn::{Game, GameResult, Line, PgnError, PgnErrorKind, PgnReader};
+use crate::san::ParseSanError;
+
+const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "A \"quoted\" name"]
+
+{Opening comment} 1. e4 e5 2. Nf3 $1 Nc6 {This opening is called the Ruy Lopez.}
+3. Bb5 a6 (3... Nf6 4. O-O (4. d3) Nxe4) 4.Ba4 Nf6!? ; a line comment
+5. O-O Be7 1/2-1/2
+
+% an escaped line, for some other program
+[Event "Second"]
+[Result "0-1"]
+
+1. f3 e5 2. g4?? Qh4# 0-1
+
+[Event "No result in the moves"]
+[Result "1-0"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"]
+
+1. O-O-O Kf7
+"#;
+
+fn read(pgn: &str) -> Vec<Result<Game, PgnError>> {
+    PgnReader::new(pgn.as_bytes()).collect()
+}
+
+fn sans(start: GameState, line: &Line) -> Vec<String> {
+    let mut state = start;
+    line.moves
+        .iter()
+        .map(|played| {
+            let san = state.move_to_san(played.m);
+            state.perform_move(played.m);
+            san
+        })
+        .collect()
+}
+
+#[test]
+fn reads_tags_and_moves() {
+    let games = read(GAMES)
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(games.len(), 3);
+
+    let game = &games[0];
+    assert_eq!(game.tags.len(), 8);
+    assert_eq!(game.tag("White"), Some("Fischer, Robert J."));
+    assert_eq!(game.tag("Annotator"), Some("A \"quoted\" name"));
+    assert_eq!(game.tag("ECO"), None);
+    assert_eq!(game.result, GameResult::Draw);
+    assert_eq!(
+        sans(game.start, &game.mainline),
+        ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7"]
+    );
+
+    let game = &games[1];
+    assert_eq!(game.tag("Event"), Some("Second"));
+    assert_eq!(game.result, GameResult::BlackWins);
+    assert_eq!(game.result.winner(), Some(crate::piece::PieceColor::Black));
+    assert!(game.end().outcome().is_some());
+
+    // the result comes from the tag, and the game from the FEN.
+    let game = &games[2];
+    assert_eq!(game.result, GameResult::WhiteWins);
+    assert_eq!(sans(game.start, &game.mainline), ["O-O-O", "Kf7"]);
+    assert_eq!(game.end().to_fen(), "8/5k2/8/8/8/8/8/2KR4 w - - 2 2");
+}
+
+#[test]
+fn reads_comments_variations_and_nags() {
+    let game = read(GAMES).remove(0).unwrap();
+    let moves = &game.mainline.moves;
+    assert_eq!(game.mainline.comments, ["Opening comment"]);
+    assert_eq!(moves[2].nags, [1]);
+    assert_eq!(moves[3].comments, ["This opening is called the Ruy Lopez."]);
+    assert_eq!(moves[7].nags, [5]);
+    assert_eq!(moves[7].comments, ["a line comment"]);
+
+    // 3... Nf6 4. O-O (4. d3) Nxe4, instead of 3... a6.
+    assert_eq!(moves[5].variations.len(), 1);
+    let variation = &moves[5].variations[0];
+    let mut before = game.start;
+    for played in &moves[..5] {
+        before.perform_move(played.m);
+    }
+    assert_eq!(sans(before, variation), ["Nf6", "O-O", "Nxe4"]);
+    before.perform_move(variation.moves[0].m);
+    assert_eq!(sans(before, &variation.moves[1].variations[0]), ["d3"]);
+
+    let game = read(GAMES).remove(1).unwrap();
+    assert_eq!(game.mainline.moves[2].nags, [4]);
+}
+
+#[test]
+fn errors_have_positions() {
+    let error = |pgn: &str| {
+        let error = read(pgn).remove(0).unwrap_err();
+        (error.line, error.column, error.kind)
+    };
+
+    assert!(matches!(
+        error("1. e4 e5\n2. Ke3"),
+        (
+            2,
+            4,
+            PgnErrorKind::InvalidMove(ParseSanError::IllegalMove(_))
+        )
+    ));
+    assert!(matches!(
+        error("[Event \"x\"]\n[FEN \"8/8 w\"]\n1. e4"),
+        (2, 1, PgnErrorKind::InvalidFen(_))
+    ));
+    assert!(matches!(
+        error("[Event \"x]\n"),
+        (1, 1, PgnErrorKind::InvalidTag(_))
+    ));
+    assert!(matches!(
+        error("1. e4 {never closed\n1-0"),
+        (1, 7, PgnErrorKind::UnterminatedComment)
+    ));
+    assert!(matches!(
+        error("1. e4 (1. d4 1-0"),
+        (1, 14, PgnErrorKind::UnterminatedVariation)
+    ));
+    assert!(matches!(
+        error("(1. e4) 1-0"),
+        (1, 1, PgnErrorKind::Unexpected(_))
+    ));
+    assert!(matches!(
+        error("1. e4 ) 1-0"),
+        (1, 7, PgnErrorKind::Unexpected(_))
+    ));
+
+    let e = read("\n  1. e4 e6 2. Ke3").remove(0).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "line 2, column 15: \"Ke3\" is not a legal move"
+    );
+}
+
+#[test]
+fn goes_on_after_errors() {
+    let games =
+        read("1. e4 e5 2. Ke3 Nc6 1-0\n\n1. d4 0-1\n[Event \"x\"]\n1. e5\n[Event \"y\"]\n*");
+    assert_eq!(games.len(), 4);
+    assert!(games[0].is_err());
+    assert_eq!(games[1].as_ref().unwrap().result, GameResult::BlackWins);
+    assert!(games[2].is_err());
+    assert_eq!(games[3].as_ref().unwrap().tag("Event"), Some("y"));
+    assert_eq!(games[3].as_ref().unwrap().result, GameResult::Unfinished);
+}
+
+#[test]
+fn streams_many_games() {
+    struct Repeat {
+        game: &'static [u8],
+        left: usize,
+        at: usize,
+    }
+
+    impl std::io::Read for Repeat {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.at == self.game.len() {
+                if self.left == 0 {
+                    return Ok(0);
+                }
+                self.left -= 1;
+                self.at = 0;
+            }
+            let n = buf.len().min(self.game.len() - self.at);
+            buf[..n].copy_from_slice(&self.game[self.at..self.at + n]);
+            self.at += n;
+            Ok(n)
+        }
+    }
+
+    const GAME: &[u8] = b"[Event \"?\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1/2-1/2\n\n";
+    let reader = std::io::BufReader::new(Repeat {
+        game: GAME,
+        left: 2000,
+        at: GAME.len(),
+    });
+    let mut count = 0;
+    for game in PgnReader::new(reader) {
+        assert_eq!(game.unwrap().mainline.moves.len(), 6);
+        count += 1;
+    }
+    assert_eq!(count, 2000);
+}
+
+#[test]
+fn skips_runs_of_dots() {
+    // as in a corrupt line, with far more dots than there is stack to recurse on.
+    let pgn = format!("1. e4 {} e5 *", ".".repeat(1_000_000));
+    let game = read(&pgn).remove(0).unwrap();
+    assert_eq!(sans(game.start, &game.mainline), ["e4", "e5"]);
+}
+
+#[test]
+fn no_games_from_between_games() {
+    let pgn = "{Exported by some database} [Event \"First\"]\n\n1. e4 1-0 {won on time}\n\
+               Game two follows.\n\n[Event \"Second\"]\n\n1. d4 * {the end}\n";
+    let games = read(pgn)
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(games.len(), 2);
+
+    // what comes before a game's tags goes with it.
+    assert_eq!(games[0].tag("Event"), Some("First"));
+    assert_eq!(games[0].mainline.comments, ["Exported by some database"]);
+    assert_eq!(games[0].result, GameResult::WhiteWins);
+    assert_eq!(games[1].tag("Event"), Some("Second"));
+    assert_eq!(games[1].mainline.comments, ["won on time"]);
+    assert_eq!(sans(games[1].start, &games[1].mainline), ["d4"]);
+}
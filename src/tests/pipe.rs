@@ -0,0 +1,53 @@
+use std::io::{self, BufRead, BufReader, PipeReader, PipeWriter, Write};
+use std::thread::JoinHandle;
+
+/// An engine running on its own thread, talked to through pipes like a GUI would.
+pub(super) struct Engine {
+    input: PipeWriter,
+    output: BufReader<PipeReader>,
+    thread: JoinHandle<()>,
+}
+
+impl Engine {
+    pub(super) fn start(run: fn(BufReader<PipeReader>, PipeWriter) -> io::Result<()>) -> Self {
+        let (input_reader, input) = io::pipe().unwrap();
+        let (output, output_writer) = io::pipe().unwrap();
+        let thread = std::thread::spawn(move || {
+            run(BufReader::new(input_reader), output_writer).unwrap();
+        });
+
+        Self {
+            input,
+            output: BufReader::new(output),
+            thread,
+        }
+    }
+
+    pub(super) fn send(&mut self, command: &str) {
+        writeln!(self.input, "{command}").unwrap();
+    }
+
+    /// Reads lines up to and including the first one starting with `prefix`.
+    pub(super) fn read_until(&mut self, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            assert_ne!(
+                self.output.read_line(&mut line).unwrap(),
+                0,
+                "no {prefix:?} in {lines:?}"
+            );
+            let line = line.trim_end().to_string();
+            let done = line.starts_with(prefix);
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    pub(super) fn quit(mut self) {
+        self.send("quit");
+        self.thread.join().unwrap();
+    }
+}
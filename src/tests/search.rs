@@ -0,0 +1,233 @@
+use crate::board::Board;
+use crate::eval::{Tapered, Weights};
+use crate::game_state::GameState;
+use crate::piece::BoardPieceKind;
+use crate::piece_move::Move;
+use crate::search::{Limits, Score, SearchResult, Searcher};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+/// Counting only material, the same in the middlegame as in the endgame, to keep the
+/// scores easy to check.
+fn material_searcher() -> Searcher {
+    let material = [
+        BoardPieceKind::Pawn,
+        BoardPieceKind::Rook,
+        BoardPieceKind::Knight,
+        BoardPieceKind::Bishop,
+        BoardPieceKind::Queen,
+        BoardPieceKind::King,
+    ]
+    .map(|kind| Tapered::new(kind.value(), kind.value()));
+
+    let mut searcher = Searcher::new();
+    searcher.set_weights(Weights {
+        material,
+        ..Weights::ZERO
+    });
+    searcher
+}
+
+fn search(fen: &str, depth: u32) -> (GameState, SearchResult) {
+    let state = GameState::parse_from_fen(fen).unwrap();
+    let result = material_searcher().search(
+        &state,
+        Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        },
+    );
+    (state, result)
+}
+
+#[test]
+fn finds_mates() {
+    let (state, result) = search("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", 3);
+    assert_eq!(result.score, Score::Mate(1));
+    assert_eq!(state.move_to_san(result.best_move.unwrap()), "Ra8#");
+
+    let (_, result) = search("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 4);
+    assert_eq!(result.score, Score::Mate(2));
+    assert_eq!(result.pv.len(), 3);
+
+    // the side to move is the one getting mated.
+    let (_, result) = search("7k/1R6/8/8/8/8/R7/6K1 b - - 0 1", 3);
+    assert_eq!(result.score, Score::Mate(-1));
+}
+
+#[test]
+fn game_over_at_the_root() {
+    let (_, result) = search("6Rk/6Q1/8/8/8/8/8/K7 b - - 0 1", 3);
+    assert_eq!(result.best_move, None);
+    assert_eq!(result.score, Score::Mate(0));
+
+    let (_, result) = search("7k/8/6Q1/8/8/8/8/K7 b - - 0 1", 3);
+    assert_eq!(result.best_move, None);
+    assert_eq!(result.score, Score::Centipawns(0));
+}
+
+#[test]
+fn wins_material() {
+    let (state, result) = search("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 2);
+    assert_eq!(state.move_to_san(result.best_move.unwrap()), "Rxd5");
+    assert_eq!(result.score, Score::Centipawns(500));
+
+    // the pv is a legal line.
+    let (mut state, result) = search(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        3,
+    );
+    assert_eq!(result.depth, 3);
+    assert_eq!(result.best_move, result.pv.first().copied());
+    for m in result.pv {
+        assert!(state.legal_moves().any(|legal| legal == m));
+        state.perform_move(m);
+    }
+}
+
+#[test]
+fn fifty_move_rule_is_a_draw() {
+    let (_, result) = search("4k3/8/8/8/8/8/8/Q3K3 w - - 99 80", 2);
+    assert_eq!(result.score, Score::Centipawns(0));
+
+    // unless the move that reaches it mates.
+    let (_, result) = search("6k1/5ppp/8/8/8/8/8/R3K3 w - - 99 80", 3);
+    assert_eq!(result.score, Score::Mate(1));
+}
+
+#[test]
+fn repetitions_are_draws() {
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 50 80").unwrap();
+    let limits = Limits {
+        depth: Some(2),
+        ..Limits::default()
+    };
+    assert_eq!(
+        material_searcher().search(&state, limits.clone()).score,
+        Score::Centipawns(900)
+    );
+
+    // every move leads back to a position seen before, with the same side to move.
+    let history = state
+        .legal_moves()
+        .flat_map(|m| {
+            let mut child = state;
+            child.perform_move(m);
+            [state, child]
+        })
+        .collect::<Vec<_>>();
+    let mut searcher = material_searcher();
+    searcher.set_history(&history);
+    assert_eq!(searcher.search(&state, limits).score, Score::Centipawns(0));
+}
+
+#[test]
+fn limits_and_stopping() {
+    let state = GameState::starting();
+
+    let mut searcher = Searcher::new();
+    let result = searcher.search(
+        &state,
+        Limits {
+            nodes: Some(5000),
+            ..Limits::default()
+        },
+    );
+    assert!(result.nodes < 5000 + 1024);
+    assert!(result.best_move.is_some());
+
+    // even when stopped right away, the first iteration is finished.
+    searcher.stop_handle().store(true, Ordering::Relaxed);
+    let result = searcher.search(
+        &state,
+        Limits {
+            infinite: true,
+            ..Limits::default()
+        },
+    );
+    assert_eq!(result.depth, 1);
+    assert!(state.legal_moves().any(|m| Some(m) == result.best_move));
+}
+
+#[test]
+fn reports_each_iteration() {
+    let depths = Arc::new(Mutex::new(Vec::new()));
+    let mut searcher = Searcher::new();
+    searcher.on_iteration({
+        let depths = Arc::clone(&depths);
+        move |result| depths.lock().unwrap().push(result.depth)
+    });
+    searcher.search(
+        &GameState::starting(),
+        Limits {
+            depth: Some(3),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(*depths.lock().unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn quiescence_sees_past_the_horizon() {
+    // at depth 1 the pawn looks free, but c6 takes the queen back.
+    let (state, result) = search("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", 1);
+    assert_ne!(state.move_to_san(result.best_move.unwrap()), "Qxd5");
+    assert_eq!(result.score, Score::Centipawns(700));
+
+    // in check at the horizon, the escape is searched.
+    let (_, result) = search("4k3/8/8/8/8/r7/1r6/4K3 b - - 0 1", 1);
+    assert_eq!(result.score, Score::Mate(1));
+}
+
+#[test]
+fn positions_can_be_shared_between_threads() {
+    fn shareable<T: Send + Sync + Copy>() {}
+    shareable::<GameState>();
+    shareable::<Board>();
+    shareable::<Move>();
+}
+
+#[test]
+fn several_threads() {
+    let mut searcher = material_searcher();
+    searcher.set_threads(4);
+    let depths = Arc::new(Mutex::new(Vec::new()));
+    searcher.on_iteration({
+        let depths = Arc::clone(&depths);
+        move |result| depths.lock().unwrap().push(result.depth)
+    });
+
+    let state = GameState::parse_from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+    let limits = Limits {
+        depth: Some(4),
+        ..Limits::default()
+    };
+    let result = searcher.search(&state, limits.clone());
+    assert_eq!(result.score, Score::Mate(2));
+    assert_eq!(result.pv.len(), 3);
+    // only the main thread reports, once per depth.
+    let depths = depths.lock().unwrap().clone();
+    assert_eq!(depths, (1..=depths.len() as u32).collect::<Vec<_>>());
+
+    // fresh searchers from here on, with nothing left in their tables.
+    let threaded = || {
+        let mut searcher = material_searcher();
+        searcher.set_threads(4);
+        searcher
+    };
+    let result = threaded().search(&GameState::starting(), limits);
+    assert_eq!(result.depth, 4);
+    assert!(GameState::starting()
+        .legal_moves()
+        .any(|m| Some(m) == result.best_move));
+
+    // the nodes of every thread count towards the limit.
+    let result = threaded().search(
+        &GameState::starting(),
+        Limits {
+            nodes: Some(20_000),
+            ..Limits::default()
+        },
+    );
+    assert!(result.nodes < 20_000 + 4 * 1024, "{}", result.nodes);
+}
@@ -0,0 +1,898 @@
+//! Syzygy tables with real values for the three-piece endings: every position is solved
+//! by retrograde analysis, with a move generator of its own, then written in the
+//! tables' format much as the generator would, Huffman-coded pairs of symbols and all.
+
+use crate::tablebase::Wdl;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::path::Path;
+
+const PAWN: u8 = 1;
+const KING: u8 = 6;
+const BLACK_KING: u8 = 14;
+
+const STRAIGHT: [(i8, i8); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const DIAGONAL: [(i8, i8); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+const ALL_WAYS: [(i8, i8); 8] = [
+    (0, -1),
+    (-1, 0),
+    (1, 0),
+    (0, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+const KNIGHT: [(i8, i8); 8] = [
+    (-2, -1),
+    (-1, -2),
+    (1, -2),
+    (2, -1),
+    (-2, 1),
+    (-1, 2),
+    (1, 2),
+    (2, 1),
+];
+
+/// Every placement of the white king, the other white piece and the black king, for
+/// either side to move.
+const POSITIONS: usize = 2 * 64 * 64 * 64;
+
+/// Flags a move that captures or moves a pawn.
+const ZEROING: u32 = 1 << 31;
+
+/// What retrograde analysis found out about a legal position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) struct Solution {
+    pub(super) wdl: Wdl,
+    /// Plies to the next capture, pawn move or mate, as `Tablebase::probe_dtz` counts
+    /// them.
+    pub(super) dtz: i32,
+    /// A capture reaches the result, so the WDL table needn't hold it.
+    capture_decides: bool,
+    /// The result is reached by zeroing right away, so the DTZ table needn't hold it.
+    zeroing_decides: bool,
+}
+
+/// The positions of the white king and one more white piece against the black king.
+pub(super) struct Solved {
+    /// Syzygy's number for the piece: 1 for a pawn up to 5 for a queen.
+    pub(super) piece: u8,
+    solutions: Vec<Option<Solution>>,
+}
+
+impl Solved {
+    /// The table's name, e.g. `KQvK`.
+    pub(super) fn name(&self) -> String {
+        format!("K{}vK", self.piece_char())
+    }
+
+    /// The white piece in FEN.
+    pub(super) fn piece_char(&self) -> char {
+        b" PNBRQ"[self.piece as usize] as char
+    }
+
+    /// The solution of a position, by squares from a1 = 0 to h8 = 63, if it is legal.
+    pub(super) fn get(
+        &self,
+        white_king: u8,
+        piece: u8,
+        black_king: u8,
+        white_to_move: bool,
+    ) -> Option<Solution> {
+        self.solutions[position(white_king, piece, black_king, white_to_move)]
+    }
+
+    /// The legal positions, as the squares of the white king, the piece and the black
+    /// king and whether white is to move, with their solutions.
+    pub(super) fn iter(&self) -> impl Iterator<Item = ((u8, u8, u8, bool), Solution)> + '_ {
+        self.solutions
+            .iter()
+            .enumerate()
+            .filter_map(|(p, solution)| Some((squares(p), (*solution)?)))
+    }
+}
+
+fn position(white_king: u8, piece: u8, black_king: u8, white_to_move: bool) -> usize {
+    (!white_to_move as usize) << 18
+        | (white_king as usize) << 12
+        | (piece as usize) << 6
+        | black_king as usize
+}
+
+fn squares(p: usize) -> (u8, u8, u8, bool) {
+    (
+        (p >> 12 & 63) as u8,
+        (p >> 6 & 63) as u8,
+        (p & 63) as u8,
+        p >> 18 == 0,
+    )
+}
+
+fn bit(square: u8) -> u64 {
+    1 << square
+}
+
+fn bits(mut set: u64) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        let square = set.trailing_zeros() as u8;
+        set &= set.checked_sub(1)?;
+        Some(square)
+    })
+}
+
+/// The squares a white `kind` attacks from `from`, sliding up to the first piece in
+/// `occupied`.
+fn attacks(kind: u8, from: u8, occupied: u64) -> u64 {
+    let steps: &[(i8, i8)] = match kind {
+        PAWN => &DIAGONAL[2..],
+        2 => &KNIGHT,
+        3 => &DIAGONAL,
+        4 => &STRAIGHT,
+        _ => &ALL_WAYS,
+    };
+    let slides = matches!(kind, 3..=5);
+
+    let mut targets = 0;
+    for &(file_step, rank_step) in steps {
+        let (mut file, mut rank) = ((from % 8) as i8, (from / 8) as i8);
+        loop {
+            file += file_step;
+            rank += rank_step;
+            if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+                break;
+            }
+            let square = (rank * 8 + file) as u8;
+            targets |= bit(square);
+            if !slides || occupied & bit(square) != 0 {
+                break;
+            }
+        }
+    }
+    targets
+}
+
+fn is_legal(piece: u8, p: usize) -> bool {
+    let (white_king, square, black_king, white_to_move) = squares(p);
+    let occupied = bit(white_king) | bit(square) | bit(black_king);
+    occupied.count_ones() == 3
+        && (piece != PAWN || (8..56).contains(&square))
+        && attacks(KING, white_king, 0) & bit(black_king) == 0
+        && !(white_to_move && is_check(piece, p))
+}
+
+/// Whether the black king is attacked: the white king never is, next to the other.
+fn is_check(piece: u8, p: usize) -> bool {
+    let (white_king, square, black_king, _) = squares(p);
+    attacks(piece, square, bit(white_king) | bit(black_king)) & bit(black_king) != 0
+}
+
+/// The moves of a legal position: those to other positions of the table, flagged
+/// with `ZEROING`, then the results for the side moving of those leaving it, and
+/// whether they capture.
+fn moves(piece: u8, p: usize, promotions: &[&Solved]) -> (Vec<u32>, Vec<(Wdl, bool)>) {
+    let (white_king, square, black_king, white_to_move) = squares(p);
+    let occupied = bit(white_king) | bit(square) | bit(black_king);
+    let mut inside = Vec::new();
+    let mut leaving = Vec::new();
+
+    if white_to_move {
+        let near_black_king = attacks(KING, black_king, 0) | bit(black_king);
+        for to in bits(attacks(KING, white_king, 0) & !near_black_king & !bit(square)) {
+            inside.push(position(to, square, black_king, false) as u32);
+        }
+        if piece != PAWN {
+            for to in bits(attacks(piece, square, occupied) & !occupied) {
+                inside.push(position(white_king, to, black_king, false) as u32);
+            }
+        } else if occupied & bit(square + 8) == 0 {
+            let to = square + 8;
+            if to >= 56 {
+                for promoted in promotions {
+                    let solution = promoted.get(white_king, to, black_king, false).unwrap();
+                    leaving.push((-solution.wdl, false));
+                }
+            } else {
+                inside.push(position(white_king, to, black_king, false) as u32 | ZEROING);
+                if square < 16 && occupied & bit(square + 16) == 0 {
+                    let to = square + 16;
+                    inside.push(position(white_king, to, black_king, false) as u32 | ZEROING);
+                }
+            }
+        }
+    } else {
+        // the piece's attacks go through where the king was.
+        let guarded = attacks(KING, white_king, 0) | attacks(piece, square, bit(white_king));
+        for to in bits(attacks(KING, black_king, 0) & !guarded) {
+            if to == square {
+                // with the kings alone on the board, it's a draw.
+                leaving.push((Wdl::Draw, true));
+            } else {
+                inside.push(position(white_king, square, to, true) as u32);
+            }
+        }
+    }
+    (inside, leaving)
+}
+
+/// Solves the positions with `piece`, looking up those a pawn promotes to in
+/// `promotions`.
+pub(super) fn solve(piece: u8, promotions: &[&Solved]) -> Solved {
+    let mut legal = vec![false; POSITIONS];
+    let mut starts = vec![0; POSITIONS + 1];
+    let mut targets = Vec::new();
+    let mut best_leaving = vec![None; POSITIONS];
+    let mut best_capture = vec![None; POSITIONS];
+    let mut leaves = vec![false; POSITIONS];
+    for p in 0..POSITIONS {
+        if is_legal(piece, p) {
+            legal[p] = true;
+            let (inside, leaving) = moves(piece, p, promotions);
+            targets.extend(inside);
+            leaves[p] = !leaving.is_empty();
+            best_leaving[p] = leaving.iter().map(|&(wdl, _)| wdl).max();
+            best_capture[p] = leaving
+                .iter()
+                .filter(|&&(_, capture)| capture)
+                .map(|&(wdl, _)| wdl)
+                .max();
+        }
+        starts[p + 1] = targets.len();
+    }
+    let moves_of = |p: usize| &targets[starts[p]..starts[p + 1]];
+
+    // the moves the other way, to go back from positions as they are solved.
+    let mut back_starts = vec![0; POSITIONS + 1];
+    for &target in &targets {
+        back_starts[(target & !ZEROING) as usize + 1] += 1;
+    }
+    for p in 0..POSITIONS {
+        back_starts[p + 1] += back_starts[p];
+    }
+    let mut back = vec![0; targets.len()];
+    let mut filled = back_starts.clone();
+    for p in 0..POSITIONS {
+        for &target in moves_of(p) {
+            let to = (target & !ZEROING) as usize;
+            back[filled[to]] = p as u32 | (target & ZEROING);
+            filled[to] += 1;
+        }
+    }
+    let back_of = |p: usize| &back[back_starts[p]..back_starts[p + 1]];
+
+    // wins and losses first, which make the positions leading to them wins, or losses
+    // once every move leads to a win.
+    let mut wdl = vec![None; POSITIONS];
+    let mut undecided = vec![0; POSITIONS];
+    let mut queue = VecDeque::new();
+    for p in (0..POSITIONS).filter(|&p| legal[p]) {
+        undecided[p] = moves_of(p).len();
+        let decided = match best_leaving[p] {
+            Some(Wdl::Win) => Some(Wdl::Win),
+            _ if undecided[p] > 0 => None,
+            Some(best) => Some(best),
+            None if is_check(piece, p) => Some(Wdl::Loss),
+            None => Some(Wdl::Draw),
+        };
+        if let Some(result) = decided {
+            wdl[p] = Some(result);
+            queue.push_back(p);
+        }
+    }
+    while let Some(p) = queue.pop_front() {
+        for &from in back_of(p) {
+            let from = (from & !ZEROING) as usize;
+            if wdl[from].is_some() {
+                continue;
+            }
+            match wdl[p] {
+                Some(Wdl::Loss) => wdl[from] = Some(Wdl::Win),
+                Some(Wdl::Win) => {
+                    undecided[from] -= 1;
+                    if undecided[from] > 0 {
+                        continue;
+                    }
+                    // a draw by leaving the table may be left.
+                    wdl[from] = Some(best_leaving[from].unwrap_or(Wdl::Loss));
+                }
+                _ => continue,
+            }
+            queue.push_back(from);
+        }
+    }
+    let wdl = |p: usize| wdl[p].unwrap_or(Wdl::Draw);
+    let mated = |p: usize| legal[p] && moves_of(p).is_empty() && !leaves[p] && is_check(piece, p);
+
+    // then distances to zeroing, from the positions that zero or mate right away.
+    let mut dtz = vec![0i32; POSITIONS];
+    let mut zeroing_decides = vec![false; POSITIONS];
+    let mut left = vec![0; POSITIONS];
+    for p in (0..POSITIONS).filter(|&p| legal[p]) {
+        let moves = moves_of(p);
+        match wdl(p) {
+            Wdl::Win => {
+                let zeroes = best_leaving[p] == Some(Wdl::Win)
+                    || moves
+                        .iter()
+                        .any(|&m| m & ZEROING != 0 && wdl((m & !ZEROING) as usize) == Wdl::Loss);
+                let mates = moves.iter().any(|&m| mated((m & !ZEROING) as usize));
+                zeroing_decides[p] = zeroes;
+                if zeroes || mates {
+                    dtz[p] = 1;
+                    queue.push_back(p);
+                }
+            }
+            Wdl::Loss => {
+                left[p] = moves.iter().filter(|&&m| m & ZEROING == 0).count();
+                zeroing_decides[p] = left[p] == 0 && !mated(p);
+                if left[p] == 0 {
+                    dtz[p] = -1;
+                    queue.push_back(p);
+                }
+            }
+            _ => {}
+        }
+    }
+    while let Some(p) = queue.pop_front() {
+        let plies = dtz[p].abs() + 1;
+        for &from in back_of(p) {
+            if from & ZEROING != 0 {
+                continue;
+            }
+            let from = from as usize;
+            if dtz[from] != 0 {
+                continue;
+            }
+            match (wdl(from), wdl(p)) {
+                (Wdl::Win, Wdl::Loss) => dtz[from] = plies,
+                (Wdl::Loss, Wdl::Win) => {
+                    left[from] -= 1;
+                    if left[from] > 0 {
+                        continue;
+                    }
+                    dtz[from] = -plies;
+                }
+                _ => continue,
+            }
+            queue.push_back(from);
+        }
+    }
+
+    let solutions = (0..POSITIONS)
+        .map(|p| {
+            legal[p].then(|| Solution {
+                wdl: wdl(p),
+                dtz: dtz[p],
+                capture_decides: best_capture[p] == Some(wdl(p)),
+                zeroing_decides: zeroing_decides[p],
+            })
+        })
+        .collect();
+    Solved { piece, solutions }
+}
+
+/// How a DTZ table stores its values, which the generator picks table by table.
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct DtzFormat {
+    /// For black to move rather than white.
+    pub(super) black_to_move: bool,
+    /// In plies rather than in moves, which puts even distances a ply further.
+    pub(super) plies: bool,
+    /// Through a map from the values stored, with 16-bit entries if `wide`.
+    pub(super) mapped: bool,
+    pub(super) wide: bool,
+}
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+const BLOCK_SIZE: usize = 32;
+const SPAN: usize = 128;
+
+/// Writes the WDL and DTZ tables of `solved` to `dir`.
+pub(super) fn write_tables(dir: &Path, solved: &Solved, format: DtzFormat) {
+    let pawns = solved.piece == PAWN;
+    let file_count = if pawns { 4 } else { 1 };
+    let size = if pawns { 6 * 63 * 62 } else { 31_332 };
+    // a different order of pieces and of groups for each side, as the generator
+    // picks whichever compresses best.
+    let (pieces, orders) = if pawns {
+        ([[PAWN, KING, BLACK_KING], [PAWN, BLACK_KING, KING]], [0, 2])
+    } else {
+        let piece = solved.piece;
+        (
+            [[piece, KING, BLACK_KING], [KING, BLACK_KING, piece]],
+            [0, 0],
+        )
+    };
+    let dtz_pieces = pieces[1];
+    let dtz_order = orders[1];
+
+    let mut wdl = vec![vec![vec![None; size]; 2]; file_count];
+    let mut dtz = vec![vec![None; size]; file_count];
+    let mut maps = vec![[Vec::new(), Vec::new()]; file_count];
+    let mut stored_dtz = Vec::new();
+    for ((white_king, square, black_king, white_to_move), solution) in solved.iter() {
+        let on = |pieces: [u8; 3]| {
+            pieces.map(|piece| match piece {
+                KING => white_king,
+                BLACK_KING => black_king,
+                _ => square,
+            })
+        };
+        let side = !white_to_move as usize;
+        let (file, idx) = index(pieces[side], on(pieces[side]), orders[side]);
+        // anything no better than the capture would do.
+        let value = if solution.capture_decides {
+            0
+        } else {
+            (solution.wdl as i32 + 2) as u16
+        };
+        set(&mut wdl[file][side][idx], value);
+
+        if white_to_move == format.black_to_move
+            || solution.wdl == Wdl::Draw
+            || solution.zeroing_decides
+        {
+            continue;
+        }
+        let plies = solution.dtz.unsigned_abs() as u16;
+        let value = if format.plies { plies - 1 } else { plies / 2 };
+        let (file, idx) = index(dtz_pieces, on(dtz_pieces), dtz_order);
+        let map = &mut maps[file][(solution.wdl == Wdl::Loss) as usize];
+        if !map.contains(&value) {
+            map.push(value);
+        }
+        stored_dtz.push((file, idx, solution.wdl, value));
+    }
+    for map in maps.iter_mut().flatten() {
+        map.sort();
+    }
+    for (file, idx, result, value) in stored_dtz {
+        let value = if format.mapped {
+            let map = &maps[file][(result == Wdl::Loss) as usize];
+            map.iter().position(|&v| v == value).unwrap() as u16
+        } else {
+            value
+        };
+        set(&mut dtz[file][idx], value);
+    }
+
+    let flags = SPLIT | if pawns { HAS_PAWNS } else { 0 };
+    let wdl_files = (0..file_count)
+        .map(|file| {
+            let mut header = vec![orders[0] as u8 | (orders[1] as u8) << 4];
+            header.extend((0..3).map(|k| pieces[0][k] | pieces[1][k] << 4));
+            let sides = wdl[file].iter().map(|values| compress(0, values)).collect();
+            (header, sides)
+        })
+        .collect::<Vec<_>>();
+    let name = solved.name();
+    std::fs::write(
+        dir.join(format!("{name}.rtbw")),
+        table(WDL_MAGIC, flags, &wdl_files, &[]),
+    )
+    .unwrap();
+
+    let mut dtz_flags = if format.black_to_move { STM } else { 0 };
+    if format.plies {
+        dtz_flags |= WIN_PLIES | LOSS_PLIES;
+    }
+    if format.mapped {
+        dtz_flags |= MAPPED | if format.wide { WIDE } else { 0 };
+    }
+    let dtz_files = (0..file_count)
+        .map(|file| {
+            let mut header = vec![dtz_order as u8];
+            header.extend(dtz_pieces);
+            (header, vec![compress(dtz_flags, &dtz[file])])
+        })
+        .collect::<Vec<_>>();
+    let maps = if format.mapped {
+        maps.iter()
+            .map(|[wins, losses]| {
+                let empty = Vec::new();
+                let maps = [wins, losses, &empty, &empty];
+                let mut bytes = Vec::new();
+                for map in maps {
+                    if format.wide {
+                        bytes.extend((map.len() as u16).to_le_bytes());
+                        bytes.extend(map.iter().flat_map(|value| value.to_le_bytes()));
+                    } else {
+                        bytes.push(map.len() as u8);
+                        bytes.extend(map.iter().map(|&value| u8::try_from(value).unwrap()));
+                    }
+                }
+                bytes
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    std::fs::write(
+        dir.join(format!("{name}.rtbz")),
+        table(DTZ_MAGIC, flags, &dtz_files, &maps),
+    )
+    .unwrap();
+}
+
+/// Stores `value` at an index, which every position mapped to it must agree on.
+fn set(entry: &mut Option<u16>, value: u16) {
+    assert!(
+        entry.is_none_or(|stored| stored == value),
+        "positions disagree"
+    );
+    *entry = Some(value);
+}
+
+/// The leading pawn's file and the index of the pieces on `squares`, which are in the
+/// order of the table's `pieces`, with the leading group's index multiplied in
+/// `order`th.
+fn index(pieces: [u8; 3], mut squares: [u8; 3], order: usize) -> (usize, usize) {
+    // the leading piece goes on the queen side.
+    if squares[0] % 8 > 3 {
+        squares = squares.map(|square| square ^ 7);
+    }
+    if pieces[0] != PAWN {
+        return (0, unique_index(squares));
+    }
+
+    // each of the others skips the squares of those before it.
+    let below = |i: usize| (0..i).filter(|&j| squares[j] < squares[i]).count();
+    let groups = [
+        ((squares[0] / 8 - 1) as usize, 6),
+        (squares[1] as usize - below(1), 63),
+        (squares[2] as usize - below(2), 62),
+    ];
+    let mut sequence = vec![1, 2];
+    sequence.insert(order, 0);
+    let mut index = 0;
+    let mut factor = 1;
+    for group in sequence {
+        let (value, size) = groups[group];
+        index += value * factor;
+        factor *= size;
+    }
+    ((squares[0] % 8) as usize, index)
+}
+
+/// The index of three pieces indexed together, with the first in the a1-d1-d4
+/// triangle, as tables without pawns but with a piece of a kind of its own have them.
+fn unique_index(mut squares: [u8; 3]) -> usize {
+    let diagonal = |square: u8| (square / 8) as i32 - (square % 8) as i32;
+    let rank = |square: u8| (square / 8) as usize;
+    let transpose = |square: u8| (square >> 3 | square << 3) & 63;
+    if squares[0] / 8 > 3 {
+        squares = squares.map(|square| square ^ 56);
+    }
+    // the first piece off the a1-h8 diagonal goes below it.
+    if let Some(i) = (0..3).find(|&i| diagonal(squares[i]) != 0) {
+        if diagonal(squares[i]) > 0 {
+            for square in &mut squares[i..] {
+                *square = transpose(*square);
+            }
+        }
+    }
+
+    const TRIANGLE: [u8; 10] = [1, 2, 3, 10, 11, 19, 0, 9, 18, 27];
+    let triangle = |square: u8| TRIANGLE.iter().position(|&s| s == square).unwrap();
+    let below_diagonal = |square: u8| (0..square).filter(|&s| diagonal(s) < 0).count();
+    let [s0, s1, s2] = squares;
+    let skip1 = (s1 > s0) as usize;
+    let skip2 = (s2 > s0) as usize + (s2 > s1) as usize;
+    let s1 = s1 as usize;
+    let s2 = s2 as usize;
+
+    if diagonal(s0) != 0 {
+        (triangle(s0) * 63 + s1 - skip1) * 62 + s2 - skip2
+    } else if diagonal(s1 as u8) != 0 {
+        (6 * 63 + rank(s0) * 28 + below_diagonal(s1 as u8)) * 62 + s2 - skip2
+    } else if diagonal(s2 as u8) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank(s0) * 7 * 28
+            + (rank(s1 as u8) - skip1) * 28
+            + below_diagonal(s2 as u8)
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank(s0) * 7 * 6
+            + (rank(s1 as u8) - skip1) * 6
+            + rank(s2 as u8)
+            - skip2
+    }
+}
+
+/// The values of one side to move, and one leading pawn file, compressed.
+struct Pairs {
+    /// From the flags to the pairs of symbols.
+    header: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    blocks: Vec<u8>,
+}
+
+/// Compresses `values`, where `None` is for positions whose value doesn't matter:
+/// symbols stand for pairs of symbols, down to single values, and are Huffman-coded
+/// in blocks.
+fn compress(flags: u8, values: &[Option<u16>]) -> Pairs {
+    // what doesn't matter is best the commonest value.
+    let mut counts = HashMap::<u16, usize>::new();
+    for &value in values.iter().flatten() {
+        *counts.entry(value).or_default() += 1;
+    }
+    let commonest = counts
+        .iter()
+        .max_by_key(|&(&value, &count)| (count, Reverse(value)))
+        .map_or(0, |(&value, _)| value);
+    let values = values
+        .iter()
+        .map(|value| value.unwrap_or(commonest))
+        .collect::<Vec<_>>();
+
+    let mut leaves = values.clone();
+    leaves.sort();
+    leaves.dedup();
+    if leaves.len() == 1 {
+        return Pairs {
+            header: vec![flags | SINGLE_VALUE, leaves[0] as u8],
+            sparse_index: Vec::new(),
+            block_lengths: Vec::new(),
+            blocks: Vec::new(),
+        };
+    }
+
+    // the commonest pair of symbols in a row becomes a symbol of its own, over and
+    // over.
+    let mut pairs = leaves
+        .iter()
+        .map(|&value| (value, 0xfff))
+        .collect::<Vec<_>>();
+    let mut lengths = vec![1; pairs.len()];
+    let mut text = values
+        .iter()
+        .map(|value| leaves.binary_search(value).unwrap() as u16)
+        .collect::<Vec<_>>();
+    for _ in 0..16 {
+        let mut counts = HashMap::<(u16, u16), usize>::new();
+        for pair in text.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_default() += 1;
+        }
+        let Some((&(left, right), &count)) = counts
+            .iter()
+            .filter(|&(&(left, right), _)| lengths[left as usize] + lengths[right as usize] <= 256)
+            .max_by_key(|&(&pair, &count)| (count, Reverse(pair)))
+        else {
+            break;
+        };
+        if count < 8 {
+            break;
+        }
+
+        let symbol = pairs.len() as u16;
+        pairs.push((left, right));
+        lengths.push(lengths[left as usize] + lengths[right as usize]);
+        let mut paired = Vec::with_capacity(text.len());
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] == left && text.get(i + 1) == Some(&right) {
+                paired.push(symbol);
+                i += 2;
+            } else {
+                paired.push(text[i]);
+                i += 1;
+            }
+        }
+        text = paired;
+    }
+
+    // Huffman code lengths for the symbols left in the text.
+    let mut frequencies = vec![0usize; pairs.len()];
+    for &symbol in &text {
+        frequencies[symbol as usize] += 1;
+    }
+    let mut code_lengths = vec![0u32; pairs.len()];
+    let mut heap = BinaryHeap::new();
+    let mut parents = Vec::new();
+    for (symbol, &frequency) in frequencies.iter().enumerate() {
+        if frequency > 0 {
+            heap.push(Reverse((frequency, parents.len())));
+            parents.push((usize::MAX, symbol));
+        }
+    }
+    while heap.len() > 1 {
+        let Reverse((first, a)) = heap.pop().unwrap();
+        let Reverse((second, b)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push((usize::MAX, usize::MAX));
+        parents[a].0 = node;
+        parents[b].0 = node;
+        heap.push(Reverse((first + second, node)));
+    }
+    for node in 0..parents.len() {
+        let (_, symbol) = parents[node];
+        if symbol == usize::MAX {
+            continue;
+        }
+        let mut depth = 0;
+        let mut up = node;
+        while parents[up].0 != usize::MAX {
+            up = parents[up].0;
+            depth += 1;
+        }
+        code_lengths[symbol] = depth.max(1);
+    }
+
+    // canonical codes: the longest codes come first, as do their symbols.
+    let mut order = (0..pairs.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&symbol| (code_lengths[symbol] == 0, Reverse(code_lengths[symbol])));
+    let mut renumbered = vec![0; pairs.len()];
+    for (new, &old) in order.iter().enumerate() {
+        renumbered[old] = new;
+    }
+    let coded = frequencies
+        .iter()
+        .filter(|&&frequency| frequency > 0)
+        .count();
+    let min_len = code_lengths
+        .iter()
+        .copied()
+        .filter(|&len| len > 0)
+        .min()
+        .unwrap();
+    let max_len = code_lengths.iter().copied().max().unwrap();
+    assert!(max_len <= 32);
+    let lengths_count = (max_len - min_len + 1) as usize;
+    // by length from the shortest, the first symbol with it, and the smallest code.
+    let mut lowest_sym = vec![0; lengths_count];
+    let mut base = vec![0u64; lengths_count];
+    for len in (min_len..=max_len).rev() {
+        let i = (len - min_len) as usize;
+        lowest_sym[i] = order[..coded]
+            .iter()
+            .filter(|&&symbol| code_lengths[symbol] > len)
+            .count();
+        if len < max_len {
+            let longer = lowest_sym[i] - lowest_sym[i + 1];
+            base[i] = (base[i + 1] + longer as u64) / 2;
+        }
+    }
+
+    // whole codes in each block, each block holding up to 65536 values.
+    let mut blocks = Vec::new();
+    let mut block_values = Vec::new();
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let mut used = 0;
+    let mut in_block = 0;
+    for &symbol in &text {
+        let len = code_lengths[symbol as usize];
+        let i = (len - min_len) as usize;
+        let code = base[i] + (renumbered[symbol as usize] - lowest_sym[i]) as u64;
+        let symbol_values = lengths[symbol as usize];
+        if used + len as usize > BLOCK_SIZE * 8 || in_block + symbol_values > 65536 {
+            blocks.extend(std::mem::replace(&mut block, vec![0; BLOCK_SIZE]));
+            block_values.push(in_block);
+            used = 0;
+            in_block = 0;
+        }
+        for b in (0..len).rev() {
+            if code >> b & 1 != 0 {
+                block[used / 8] |= 0x80 >> (used % 8);
+            }
+            used += 1;
+        }
+        in_block += symbol_values;
+    }
+    blocks.extend(block);
+    block_values.push(in_block);
+
+    let mut block_starts = vec![0];
+    for &count in &block_values {
+        block_starts.push(block_starts.last().unwrap() + count);
+    }
+    let mut sparse_index = Vec::new();
+    for k in 0..values.len().div_ceil(SPAN) {
+        let middle = k * SPAN + SPAN / 2;
+        let block = (block_starts.partition_point(|&start| start <= middle) - 1)
+            .min(block_values.len() - 1);
+        sparse_index.extend((block as u32).to_le_bytes());
+        sparse_index.extend(
+            u16::try_from(middle - block_starts[block])
+                .unwrap()
+                .to_le_bytes(),
+        );
+    }
+    let block_lengths = block_values
+        .iter()
+        .flat_map(|&count| ((count - 1) as u16).to_le_bytes())
+        .collect();
+
+    let mut header = vec![
+        flags,
+        BLOCK_SIZE.trailing_zeros() as u8,
+        SPAN.trailing_zeros() as u8,
+        0,
+    ];
+    header.extend((block_values.len() as u32).to_le_bytes());
+    header.extend([max_len as u8, min_len as u8]);
+    header.extend(
+        lowest_sym
+            .iter()
+            .flat_map(|&sym| (sym as u16).to_le_bytes()),
+    );
+    header.extend((pairs.len() as u16).to_le_bytes());
+    for &old in &order {
+        let (left, right) = pairs[old];
+        let (left, right) = if right == 0xfff {
+            (left as usize, 0xfff)
+        } else {
+            (renumbered[left as usize], renumbered[right as usize])
+        };
+        header.extend([
+            left as u8,
+            (left >> 8) as u8 | (right << 4) as u8,
+            (right >> 4) as u8,
+        ]);
+    }
+    if pairs.len() % 2 == 1 {
+        header.push(0);
+    }
+
+    Pairs {
+        header,
+        sparse_index,
+        block_lengths,
+        blocks,
+    }
+}
+
+/// A table file, from a header and the pairs data of each side for each file, and,
+/// for DTZ tables, the map of each file.
+fn table(magic: [u8; 4], flags: u8, files: &[(Vec<u8>, Vec<Pairs>)], maps: &[Vec<u8>]) -> Vec<u8> {
+    let even = |data: &mut Vec<u8>| data.resize(data.len().next_multiple_of(2), 0);
+    let mut data = magic.to_vec();
+    data.push(flags);
+    for (header, _) in files {
+        data.extend(header);
+    }
+    even(&mut data);
+
+    let pairs = files
+        .iter()
+        .flat_map(|(_, sides)| sides)
+        .collect::<Vec<_>>();
+    for d in &pairs {
+        data.extend(&d.header);
+    }
+    for (map, (_, sides)) in maps.iter().zip(files) {
+        if sides[0].header[0] & WIDE != 0 {
+            even(&mut data);
+        }
+        data.extend(map);
+    }
+    even(&mut data);
+    for d in &pairs {
+        data.extend(&d.sparse_index);
+    }
+    for d in &pairs {
+        data.extend(&d.block_lengths);
+    }
+    for d in &pairs {
+        data.resize(data.len().next_multiple_of(64), 0);
+        data.extend(&d.blocks);
+    }
+    // room for the decoder to read ahead.
+    data.resize(data.len().next_multiple_of(64) + 64, 0);
+    data
+}
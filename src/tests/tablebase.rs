@@ -0,0 +1,490 @@
+use super::syzygy::{solve, write_tables, DtzFormat, Solved};
+use crate::game_state::GameState;
+use crate::search::{Limits, Searcher};
+use crate::tablebase::{Tablebase, TablebaseError, Wdl};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+/// The number of KQvK positions in a table, with the three pieces indexed together.
+const KQVK_SIZE: usize = 31_332;
+
+/// What a side to move of a synthetic table holds: the same value for every position,
+/// either stored once or Huffman-compressed in blocks.
+#[derive(Copy, Clone)]
+enum Values {
+    Single(u8),
+    Compressed(u8),
+}
+
+/// A KQvK table in the Syzygy format, white having the queen, with one `Values` per
+/// side to move (white first), or one for DTZ tables, which are for white to move.
+fn kqvk(magic: [u8; 4], sides: &[Values]) -> Vec<u8> {
+    const VALUES_PER_BLOCK: usize = 1000;
+    const BLOCK_SIZE: usize = 64;
+    const SPAN: usize = 1024;
+    let blocks = KQVK_SIZE.div_ceil(VALUES_PER_BLOCK);
+
+    // split, as both sides don't have the same pieces; no pawns; the pieces in one
+    // group, queen and kings.
+    let mut data = magic.to_vec();
+    data.push(1);
+    data.push(0);
+    for piece in [5, 6, 14] {
+        data.push(piece | piece << 4);
+    }
+    data.push(0);
+
+    for &side in sides {
+        match side {
+            Values::Single(value) => data.extend([0x80, value]),
+            Values::Compressed(value) => {
+                // blocks of 2^6 bytes, a sparse index entry every 2^10 values.
+                data.extend([0, 6, 10, 0]);
+                data.extend((blocks as u32).to_le_bytes());
+                // one-bit codes, the lowest symbol being 1.
+                data.extend([1, 1]);
+                data.extend(1u16.to_le_bytes());
+                // symbol 0 is the value, symbol 1 stands for it twice.
+                data.extend(2u16.to_le_bytes());
+                data.extend([value, 0xf0, 0xff, 0, 0, 0]);
+            }
+        }
+    }
+
+    let compressed = sides
+        .iter()
+        .filter(|side| matches!(side, Values::Compressed(_)))
+        .count();
+    for _ in 0..compressed {
+        for k in 0..KQVK_SIZE.div_ceil(SPAN) {
+            let middle = k * SPAN + SPAN / 2;
+            data.extend(((middle / VALUES_PER_BLOCK) as u32).to_le_bytes());
+            data.extend(((middle % VALUES_PER_BLOCK) as u16).to_le_bytes());
+        }
+    }
+    for _ in 0..compressed {
+        for block in 0..blocks {
+            let values = VALUES_PER_BLOCK.min(KQVK_SIZE - block * VALUES_PER_BLOCK);
+            data.extend((values as u16 - 1).to_le_bytes());
+        }
+    }
+    // every code is 0: symbol 1, over and over.
+    for _ in 0..compressed {
+        data.resize(data.len().next_multiple_of(64) + blocks * BLOCK_SIZE, 0);
+    }
+    // room for the decoder to read ahead, and for the last alignment.
+    data.resize(data.len().next_multiple_of(64) + 64, 0);
+    data
+}
+
+/// A fresh directory with the given files.
+fn table_dir(name: &str, files: &[(&str, Vec<u8>)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("knix-syzygy-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, data) in files {
+        std::fs::write(dir.join(file), data).unwrap();
+    }
+    dir
+}
+
+/// KQvK tables where white always wins in 5 moves to zeroing, whoever is to move.
+fn kqvk_tablebase(name: &str, wdl: [Values; 2], dtz: Values) -> (Tablebase, PathBuf) {
+    let dir = table_dir(
+        name,
+        &[
+            ("KQvK.rtbw", kqvk(WDL_MAGIC, &wdl)),
+            ("KQvK.rtbz", kqvk(DTZ_MAGIC, &[dtz])),
+        ],
+    );
+    (Tablebase::open(&dir).unwrap(), dir)
+}
+
+fn state(fen: &str) -> GameState {
+    GameState::parse_from_fen(fen).unwrap()
+}
+
+/// A FEN with the pieces on the given squares.
+fn fen(pieces: &[(char, u8)], white_to_move: bool) -> String {
+    let mut board = [None; 64];
+    for &(piece, square) in pieces {
+        board[square as usize] = Some(piece);
+    }
+    let ranks = (0..8)
+        .rev()
+        .map(|rank| {
+            let mut row = String::new();
+            let mut empty = 0;
+            for file in 0..8 {
+                match board[rank * 8 + file] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            row.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        row.push(piece);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row.push_str(&empty.to_string());
+            }
+            row
+        })
+        .collect::<Vec<_>>();
+    let side = if white_to_move { "w" } else { "b" };
+    format!("{} {side} - - 0 1", ranks.join("/"))
+}
+
+#[test]
+fn finds_tables() {
+    let (tablebase, dir) = kqvk_tablebase(
+        "finds",
+        [Values::Single(4), Values::Single(0)],
+        Values::Single(5),
+    );
+    assert_eq!(tablebase.len(), 1);
+    assert_eq!(tablebase.max_pieces(), 3);
+    assert!(tablebase.covers(&state("4k3/8/8/8/8/8/8/3QK3 w - - 0 1")));
+    assert!(!tablebase.covers(&state("4k3/8/8/8/8/8/8/2RQK3 w - - 0 1")));
+
+    // other files are left alone.
+    std::fs::write(dir.join("README"), "").unwrap();
+    std::fs::write(dir.join("KQvK.txt"), "").unwrap();
+    assert_eq!(Tablebase::open(&dir).unwrap().len(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert!(matches!(
+        Tablebase::open("test_data/missing"),
+        Err(TablebaseError::Io(_))
+    ));
+}
+
+#[test]
+fn single_values() {
+    let (tablebase, dir) = kqvk_tablebase(
+        "single",
+        [Values::Single(4), Values::Single(0)],
+        Values::Single(5),
+    );
+    let wdl = |fen| tablebase.probe_wdl(&state(fen)).unwrap();
+    let dtz = |fen| tablebase.probe_dtz(&state(fen)).unwrap();
+
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"), Wdl::Loss);
+    // with the colors the other way around.
+    assert_eq!(wdl("3qk3/8/8/8/8/8/8/4K3 b - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("3qk3/8/8/8/8/8/8/4K3 w - - 0 1"), Wdl::Loss);
+    // the king takes the queen.
+    assert_eq!(wdl("8/8/8/8/8/8/3Qk3/K7 b - - 0 1"), Wdl::Draw);
+    assert_eq!(dtz("8/8/8/8/8/8/3Qk3/K7 b - - 0 1"), 0);
+
+    // the value stored is in moves, and counts the zeroing one.
+    assert_eq!(dtz("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), 11);
+    // only white to move is stored, so black looks one move ahead.
+    assert_eq!(dtz("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"), -12);
+
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), Wdl::Draw);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn compressed_values() {
+    let (tablebase, dir) = kqvk_tablebase(
+        "compressed",
+        [Values::Compressed(4), Values::Compressed(0)],
+        Values::Compressed(5),
+    );
+
+    // every index a position maps to is in the table, or its value couldn't be read.
+    let mut positions = 0;
+    for king in 0..64 {
+        for queen in (0..64).step_by(3) {
+            for other_king in (0..64).step_by(5) {
+                if king == queen || queen == other_king || king == other_king {
+                    continue;
+                }
+                for (queen_is_white, white_to_move) in
+                    [(true, true), (true, false), (false, true), (false, false)]
+                {
+                    let pieces = if queen_is_white {
+                        [('K', king), ('Q', queen), ('k', other_king)]
+                    } else {
+                        [('k', king), ('q', queen), ('K', other_king)]
+                    };
+                    let state = state(&fen(&pieces, white_to_move));
+                    let waiting = state.next_move().other();
+                    if state.board().is_in_check(waiting)
+                        || state.board().king_position(waiting).is_none()
+                    {
+                        continue;
+                    }
+
+                    let queen_side = queen_is_white == white_to_move;
+                    let takes_queen = state.legal_moves().any(|m| state.board().is_capture(m));
+                    let expected = match (queen_side, takes_queen) {
+                        (true, _) => Wdl::Win,
+                        (false, true) => Wdl::Draw,
+                        (false, false) => Wdl::Loss,
+                    };
+                    let found = tablebase.probe_wdl(&state);
+                    assert_eq!(found.ok(), Some(expected), "{}", state.to_fen());
+                    positions += 1;
+                }
+            }
+        }
+    }
+    assert!(positions > 50_000);
+
+    assert_eq!(
+        tablebase
+            .probe_dtz(&state("8/8/8/8/8/8/8/kq2K3 b - - 0 1"))
+            .unwrap(),
+        11
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ranks_root_moves() {
+    let (tablebase, dir) = kqvk_tablebase(
+        "root",
+        [Values::Single(4), Values::Single(0)],
+        Values::Single(5),
+    );
+
+    let state = state("4k3/8/4K3/8/8/8/8/7Q w - - 0 1");
+    let moves = tablebase.rank_root_moves(&state).unwrap();
+    assert_eq!(moves.len(), state.legal_moves().count());
+    // the mates come first.
+    let mut mates = moves[..2]
+        .iter()
+        .map(|root| root.m.to_uci())
+        .collect::<Vec<_>>();
+    mates.sort();
+    assert_eq!(mates, ["h1a8", "h1h8"]);
+    assert!(moves[..2]
+        .iter()
+        .all(|root| (root.wdl, root.dtz) == (Wdl::Win, 1)));
+    assert!(moves[2..]
+        .iter()
+        .all(|root| (root.wdl, root.dtz) == (Wdl::Win, 13)));
+
+    // too late to win before the 50-move rule.
+    let state = GameState::parse_from_fen("4k3/8/4K3/8/8/8/8/7Q w - - 90 80").unwrap();
+    let moves = tablebase.rank_root_moves(&state).unwrap();
+    assert!(moves[..2].iter().all(|root| root.wdl == Wdl::Win));
+    assert!(moves[2..].iter().all(|root| root.wdl == Wdl::CursedWin));
+
+    let mut searcher = Searcher::new();
+    searcher.set_tablebase(Some(Arc::new(tablebase)));
+    let result = searcher.search(&state, Limits::default());
+    assert_eq!(result.best_move, Some(moves[0].m));
+    assert_eq!(result.depth, 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn errors() {
+    let (tablebase, dir) = kqvk_tablebase(
+        "errors",
+        [Values::Single(4), Values::Single(0)],
+        Values::Single(5),
+    );
+    let wdl = |fen| tablebase.probe_wdl(&state(fen));
+
+    assert!(matches!(
+        wdl("4k3/8/8/8/8/8/8/4K2R w K - 0 1"),
+        Err(TablebaseError::CastlingRights)
+    ));
+    assert!(matches!(
+        wdl("4k3/8/8/8/8/8/8/RN2K3 w - - 0 1"),
+        Err(TablebaseError::TooManyPieces(4))
+    ));
+    let missing = wdl("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap_err();
+    assert_eq!(missing.to_string(), "no table for KRvK");
+    std::fs::remove_dir_all(dir).unwrap();
+
+    let mut broken = kqvk(WDL_MAGIC, &[Values::Single(4), Values::Single(0)]);
+    broken[0] = 0;
+    let dir = table_dir("broken", &[("KQvK.rtbw", broken)]);
+    let tablebase = Tablebase::open(&dir).unwrap();
+    assert!(matches!(
+        tablebase.probe_wdl(&state("4k3/8/8/8/8/8/8/3QK3 w - - 0 1")),
+        Err(TablebaseError::InvalidTable(_))
+    ));
+    // without a DTZ table.
+    assert!(matches!(
+        tablebase.probe_dtz(&state("4k3/8/8/8/8/8/8/4K3 w - - 0 1")),
+        Ok(0)
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+
+    // cut short.
+    let dir = table_dir(
+        "truncated",
+        &[(
+            "KQvK.rtbw",
+            kqvk(WDL_MAGIC, &[Values::Compressed(4), Values::Compressed(0)])[..100].to_vec(),
+        )],
+    );
+    let tablebase = Tablebase::open(&dir).unwrap();
+    assert!(tablebase
+        .probe_wdl(&state("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"))
+        .is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// The endings with one piece against the king, solved once for all the tests: queen,
+/// rook, bishop and knight, then the pawn, whose promotions look the others up.
+fn solved() -> &'static [Solved] {
+    static SOLVED: OnceLock<Vec<Solved>> = OnceLock::new();
+    SOLVED.get_or_init(|| {
+        let mut solved = Vec::from([5, 4, 3, 2].map(|piece| solve(piece, &[])));
+        let pawn = solve(1, &solved.iter().collect::<Vec<_>>());
+        solved.push(pawn);
+        solved
+    })
+}
+
+/// Tables of the solved endings, with DTZ values stored each way the format has.
+fn solved_tablebase(name: &str) -> (Tablebase, PathBuf) {
+    let dir = table_dir(name, &[]);
+    let formats = [
+        DtzFormat::default(),
+        DtzFormat {
+            black_to_move: true,
+            plies: true,
+            mapped: true,
+            wide: false,
+        },
+        DtzFormat::default(),
+        DtzFormat::default(),
+        DtzFormat {
+            black_to_move: false,
+            plies: true,
+            mapped: true,
+            wide: true,
+        },
+    ];
+    for (solved, format) in solved().iter().zip(formats) {
+        write_tables(&dir, solved, format);
+    }
+    (Tablebase::open(&dir).unwrap(), dir)
+}
+
+/// A solved position, by the squares of the white king, the piece and the black king,
+/// or the same one with the colors the other way around.
+fn solved_fen(solved: &Solved, squares: (u8, u8, u8, bool), flipped: bool) -> String {
+    let (white_king, square, black_king, white_to_move) = squares;
+    let piece = solved.piece_char();
+    if flipped {
+        let pieces = [
+            ('k', white_king ^ 56),
+            (piece.to_ascii_lowercase(), square ^ 56),
+            ('K', black_king ^ 56),
+        ];
+        fen(&pieces, !white_to_move)
+    } else {
+        fen(
+            &[('K', white_king), (piece, square), ('k', black_king)],
+            white_to_move,
+        )
+    }
+}
+
+#[test]
+fn solved_endings() {
+    let [queen, rook, bishop, knight, _] = solved() else {
+        unreachable!()
+    };
+    let longest = |solved: &Solved| solved.iter().map(|(_, solution)| solution.dtz).max();
+    // mate takes at most 10 moves with a queen, and 16 with a rook.
+    assert_eq!(longest(queen), Some(19));
+    assert_eq!(longest(rook), Some(31));
+    for solved in [bishop, knight] {
+        assert!(solved.iter().all(|(_, solution)| solution.wdl == Wdl::Draw));
+    }
+    for solved in solved() {
+        assert!(solved
+            .iter()
+            .all(|(_, solution)| (solution.wdl == Wdl::Draw) == (solution.dtz == 0)));
+    }
+}
+
+#[test]
+fn real_values() {
+    let (tablebase, dir) = solved_tablebase("real");
+    for solved in solved() {
+        // in moves, even distances are a ply further.
+        let rounding = if solved.piece == 5 { 0..=1 } else { 0..=0 };
+        for (n, (squares, solution)) in solved.iter().enumerate().step_by(37) {
+            for flipped in [false, true] {
+                let state = state(&solved_fen(solved, squares, flipped));
+                let wdl = tablebase.probe_wdl(&state).unwrap();
+                assert_eq!(wdl, solution.wdl, "{}", state.to_fen());
+                if n % 4 != 0 {
+                    continue;
+                }
+                let dtz = tablebase.probe_dtz(&state).unwrap();
+                assert!(
+                    dtz.signum() == solution.dtz.signum()
+                        && rounding.contains(&(dtz.abs() - solution.dtz.abs())),
+                    "{}: {dtz} rather than {}",
+                    state.to_fen(),
+                    solution.dtz
+                );
+            }
+        }
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn known_positions() {
+    let (tablebase, dir) = solved_tablebase("known");
+    let wdl = |fen| tablebase.probe_wdl(&state(fen)).unwrap();
+    let dtz = |fen| tablebase.probe_dtz(&state(fen)).unwrap();
+
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"), Wdl::Loss);
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"), Wdl::Draw);
+    assert_eq!(dtz("4k3/8/4K3/8/8/8/8/7Q w - - 0 1"), 1);
+    assert_eq!(dtz("4k3/8/4K3/8/8/8/8/7R w - - 0 1"), 1);
+    assert_eq!(dtz("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), 0);
+
+    // the king on the sixth rank ahead of its pawn wins, whoever is to move.
+    assert_eq!(wdl("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Wdl::Loss);
+    // one step further, black to move is stalemated.
+    assert_eq!(wdl("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Wdl::Draw);
+    // a rook's pawn can't win with the other king in the corner, but the pawn outruns
+    // a king outside its square.
+    assert_eq!(wdl("k7/8/8/8/8/8/P7/K7 w - - 0 1"), Wdl::Draw);
+    assert_eq!(wdl("7k/8/8/8/8/8/P7/K7 b - - 0 1"), Wdl::Loss);
+    // the same, with black's pawn.
+    assert_eq!(wdl("k7/p7/8/8/8/8/8/7K b - - 0 1"), Wdl::Win);
+    // a winning pawn move zeroes right away.
+    assert_eq!(dtz("8/4P3/8/8/8/8/k7/4K3 w - - 0 1"), 1);
+
+    // the quickest win first, with real distances.
+    let state = state("8/8/8/8/4k3/8/8/R3K3 w - - 0 1");
+    let moves = tablebase.rank_root_moves(&state).unwrap();
+    let best = moves[0];
+    assert_eq!(best.wdl, Wdl::Win);
+    assert_eq!(best.dtz, tablebase.probe_dtz(&state).unwrap());
+    let mut child = state;
+    child.perform_move(best.m);
+    assert_eq!(-tablebase.probe_dtz(&child).unwrap() + 1, best.dtz);
+
+    let mut searcher = Searcher::new();
+    searcher.set_tablebase(Some(Arc::new(tablebase)));
+    let result = searcher.search(&state, Limits::default());
+    assert_eq!(result.best_move, Some(best.m));
+    std::fs::remove_dir_all(dir).unwrap();
+}
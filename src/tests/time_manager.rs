@@ -0,0 +1,145 @@
+use crate::game_state::GameState;
+use crate::search::{Limits, Searcher};
+use crate::time_manager::TimeManager;
+use std::time::Duration;
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn limits_of(fen: &str, limits: Limits) -> (Option<Duration>, Option<Duration>) {
+    let state = GameState::parse_from_fen(fen).unwrap();
+    let time = TimeManager::new(&state, &limits);
+    (time.soft_limit(), time.hard_limit())
+}
+
+const WHITE: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const BLACK: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+
+#[test]
+fn time_limits() {
+    let clock = Limits {
+        white_time: Some(ms(60_000)),
+        black_time: Some(ms(10_000)),
+        black_increment: Some(ms(1_000)),
+        ..Limits::default()
+    };
+
+    // early on, the game is expected to go on for 50 more moves.
+    assert_eq!(
+        limits_of(WHITE, clock.clone()),
+        (Some(ms(1_200)), Some(ms(3_600)))
+    );
+    // black's own clock and increment count for black.
+    assert_eq!(
+        limits_of(BLACK, clock.clone()),
+        (Some(ms(950)), Some(ms(2_850)))
+    );
+    // later on, for fewer.
+    assert_eq!(
+        limits_of("4k3/8/8/8/8/8/8/4K3 w - - 0 80", clock.clone()),
+        (Some(ms(3_000)), Some(ms(9_000)))
+    );
+    // unless the time control says otherwise.
+    let to_go = Limits {
+        moves_to_go: Some(10),
+        ..clock.clone()
+    };
+    assert_eq!(limits_of(WHITE, to_go), (Some(ms(6_000)), Some(ms(18_000))));
+
+    // with little left, never more than a fraction of it.
+    let last_move = Limits {
+        white_time: Some(ms(1_000)),
+        moves_to_go: Some(1),
+        ..Limits::default()
+    };
+    assert_eq!(
+        limits_of(WHITE, last_move),
+        (Some(ms(475)), Some(Duration::from_micros(712_500)))
+    );
+
+    let move_time = Limits {
+        move_time: Some(ms(500)),
+        ..clock
+    };
+    assert_eq!(limits_of(WHITE, move_time), (Some(ms(500)), Some(ms(500))));
+    let infinite = Limits {
+        infinite: true,
+        ..Limits::default()
+    };
+    assert_eq!(limits_of(WHITE, infinite), (None, None));
+    assert_eq!(limits_of(WHITE, Limits::default()), (None, None));
+}
+
+#[test]
+fn instability_buys_time() {
+    let state = GameState::starting();
+    let limits = Limits {
+        white_time: Some(ms(60_000)),
+        ..Limits::default()
+    };
+    let mut time = TimeManager::new(&state, &limits);
+    let e4 = state.parse_uci_move("e2e4").unwrap();
+    let d4 = state.parse_uci_move("d2d4").unwrap();
+
+    let soft_ms = |time: &TimeManager| time.soft_limit().unwrap().as_secs_f64() * 1000.0;
+
+    time.iteration_done(e4, 20);
+    time.iteration_done(e4, 25);
+    assert_eq!(soft_ms(&time).round(), 1_200.0);
+
+    // a new best move.
+    time.iteration_done(d4, 25);
+    assert_eq!(soft_ms(&time).round(), 1_680.0);
+    // a failing score.
+    time.iteration_done(d4, -50);
+    assert_eq!(soft_ms(&time).round(), 2_040.0);
+
+    // never beyond the hard limit.
+    for i in 0..10 {
+        time.iteration_done(if i % 2 == 0 { e4 } else { d4 }, -100 * i);
+    }
+    assert!(time.soft_limit().unwrap() <= ms(3_000));
+    assert_eq!(time.hard_limit(), Some(ms(3_600)));
+
+    // and stable iterations settle down again.
+    for _ in 0..10 {
+        time.iteration_done(d4, 0);
+    }
+    assert!(soft_ms(&time) < 1_210.0);
+}
+
+#[test]
+fn single_legal_move() {
+    let state = GameState::parse_from_fen("7k/8/8/8/8/8/1q6/K7 w - - 0 1").unwrap();
+    assert_eq!(state.legal_moves().count(), 1);
+
+    let result = Searcher::new().search(
+        &state,
+        Limits {
+            white_time: Some(ms(60_000)),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(result.depth, 1);
+
+    // when asked for a depth, it is searched anyway.
+    let result = Searcher::new().search(
+        &state,
+        Limits {
+            depth: Some(3),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(result.depth, 3);
+}
+
+#[test]
+fn stays_within_the_time_limit() {
+    let limits = Limits {
+        white_time: Some(ms(1_000)),
+        ..Limits::default()
+    };
+    let result = Searcher::new().search(&GameState::starting(), limits);
+    assert!(result.time < ms(1_000), "{:?}", result.time);
+}
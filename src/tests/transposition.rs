@@ -0,0 +1,133 @@
+use crate::game_state::GameState;
+use crate::search::{Limits, Searcher, MATE_BOUND};
+use crate::transposition::{Bound, TranspositionTable};
+use std::sync::Arc;
+
+#[test]
+fn store_and_probe() {
+    let table = TranspositionTable::new(1);
+    let state = GameState::starting();
+    let best = state.parse_uci_move("g1f3").unwrap();
+
+    assert_eq!(table.probe(state.zobrist(), 0), None);
+    table.store(state.zobrist(), 0, 5, Bound::Lower, 35, Some(best));
+    let entry = table.probe(state.zobrist(), 0).unwrap();
+    assert_eq!(entry.depth, 5);
+    assert_eq!(entry.bound, Bound::Lower);
+    assert_eq!(entry.score, 35);
+    assert_eq!(entry.best_move(&state), Some(best));
+
+    // without a new best move, the old one is kept.
+    table.store(state.zobrist(), 0, 6, Bound::Upper, -20, None);
+    let entry = table.probe(state.zobrist(), 0).unwrap();
+    assert_eq!(
+        (entry.depth, entry.bound, entry.score),
+        (6, Bound::Upper, -20)
+    );
+    assert_eq!(entry.best_move(&state), Some(best));
+
+    // a move that isn't legal, as when another position has the same key, is ignored.
+    let other = GameState::parse_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(entry.best_move(&other), None);
+
+    // promotions are told apart.
+    let state = GameState::parse_from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let under = state.parse_uci_move("b7b8n").unwrap();
+    table.store(state.zobrist(), 0, 1, Bound::Exact, 0, Some(under));
+    let entry = table.probe(state.zobrist(), 0).unwrap();
+    assert_eq!(entry.best_move(&state), Some(under));
+
+    table.clear();
+    assert_eq!(table.probe(state.zobrist(), 0), None);
+}
+
+#[test]
+fn mate_scores_are_relative_to_the_position() {
+    let table = TranspositionTable::new(1);
+    // found 3 plies from the root, mating 7 plies after that.
+    let mate = MATE_BOUND + 118;
+    table.store(1, 3, 4, Bound::Exact, mate, None);
+    assert_eq!(table.probe(1, 3).unwrap().score, mate);
+    assert_eq!(table.probe(1, 1).unwrap().score, mate + 2);
+
+    table.store(2, 3, 4, Bound::Exact, -mate, None);
+    assert_eq!(table.probe(2, 5).unwrap().score, -mate + 2);
+
+    // other scores are the same at any ply.
+    table.store(3, 3, 4, Bound::Exact, 250, None);
+    assert_eq!(table.probe(3, 9).unwrap().score, 250);
+}
+
+#[test]
+fn replacement() {
+    // a single bucket.
+    let table = TranspositionTable::new(0);
+    for key in 1..=4 {
+        table.store(key, 0, key as u32, Bound::Exact, 0, None);
+    }
+    assert_eq!(table.hash_full(), 1000);
+
+    // the shallowest entry makes way.
+    table.store(5, 0, 3, Bound::Exact, 0, None);
+    assert_eq!(table.probe(1, 0), None);
+    assert!((2..=5).all(|key| table.probe(key, 0).is_some()));
+
+    // and entries from earlier searches go first, however deep.
+    table.new_search();
+    assert_eq!(table.hash_full(), 0);
+    table.store(6, 0, 1, Bound::Exact, 0, None);
+    table.store(7, 0, 1, Bound::Exact, 0, None);
+    assert!(table.probe(6, 0).is_some());
+    assert!(table.probe(7, 0).is_some());
+    assert_eq!(table.hash_full(), 500);
+}
+
+#[test]
+fn shared_between_threads() {
+    let table = Arc::new(TranspositionTable::new(0));
+    let threads = (0..4u64)
+        .map(|thread| {
+            let table = Arc::clone(&table);
+            std::thread::spawn(move || {
+                for i in 0..10_000u64 {
+                    let key = ((thread << 32) | (i % 8)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                    table.store(key, 0, 1, Bound::Exact, thread as i32, None);
+                    // whatever is found for a key was stored for it.
+                    if let Some(entry) = table.probe(key, 0) {
+                        assert_eq!(entry.score, thread as i32);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn searches_reuse_the_table() {
+    let state = GameState::parse_from_fen(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    )
+    .unwrap();
+    let limits = Limits {
+        depth: Some(3),
+        ..Limits::default()
+    };
+
+    let table = Arc::new(TranspositionTable::new(1));
+    let mut searcher = Searcher::new();
+    searcher.set_table(Arc::clone(&table));
+    let first = searcher.search(&state, limits.clone());
+    assert!(first.hash_full > 0);
+
+    let second = searcher.search(&state, limits);
+    assert!(
+        second.nodes < first.nodes,
+        "{} {}",
+        second.nodes,
+        first.nodes
+    );
+    assert_eq!(second.score, first.score);
+}
@@ -0,0 +1,159 @@
+use super::pipe::Engine;
+use crate::game_state::GameState;
+use crate::uci;
+
+#[test]
+fn handshake() {
+    let mut engine = Engine::start(uci::run);
+    engine.send("uci");
+    let lines = engine.read_until("uciok");
+    assert!(lines[0].starts_with("id name knix"));
+    // there is no Chess960 castling to offer.
+    assert!(!lines.iter().any(|l| l.contains("UCI_Chess960")));
+    assert!(lines
+        .iter()
+        .any(|l| l.starts_with("option name Hash type spin")));
+    assert!(lines
+        .iter()
+        .any(|l| l.starts_with("option name Threads type spin")));
+    #[cfg(feature = "syzygy")]
+    assert!(lines
+        .iter()
+        .any(|l| l.starts_with("option name SyzygyPath type string")));
+
+    engine.send("isready");
+    assert_eq!(engine.read_until("readyok"), ["readyok"]);
+    engine.quit();
+}
+
+#[test]
+fn plays_legal_moves() {
+    let mut engine = Engine::start(uci::run);
+    engine.send("setoption name Hash value 1");
+    engine.send("setoption name Threads value 2");
+    engine.send("ucinewgame");
+    engine.send("position startpos moves e2e4 e7e5 g1f3");
+    engine.send("go depth 1");
+    let lines = engine.read_until("bestmove");
+    assert!(lines.iter().any(|l| l.starts_with("info depth")));
+    assert!(lines.iter().any(|l| l.contains(" hashfull ")));
+
+    let best = lines.last().unwrap().strip_prefix("bestmove ").unwrap();
+    let mut state = GameState::starting();
+    for m in ["e2e4", "e7e5", "g1f3"] {
+        state.perform_move(state.parse_uci_move(m).unwrap());
+    }
+    assert!(state.parse_uci_move(best).is_ok(), "{best}");
+
+    engine.send("position fen 4k3/8/8/8/8/8/8/4K2R w K -");
+    engine.send("go wtime 1000 btime 1000 winc 10 binc 10 movestogo 20");
+    let lines = engine.read_until("bestmove");
+    let best = lines.last().unwrap().strip_prefix("bestmove ").unwrap();
+    let state = GameState::parse_from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+    assert!(state.parse_uci_move(best).is_ok(), "{best}");
+
+    // no legal moves at all.
+    engine.send("position fen 7k/5QQ1/8/8/8/8/8/K7 b - - 0 1");
+    engine.send("go movetime 10");
+    assert_eq!(
+        engine.read_until("bestmove").last().unwrap(),
+        "bestmove 0000"
+    );
+    engine.quit();
+}
+
+#[test]
+fn castling_notation() {
+    let mut engine = Engine::start(uci::run);
+    engine.send("position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1 e8e7");
+    engine.send("isready");
+    assert_eq!(engine.read_until("readyok"), ["readyok"]);
+
+    // king takes rook, as some GUIs send it even in standard chess.
+    engine.send("position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1h1 e8e7");
+    engine.send("isready");
+    assert_eq!(engine.read_until("readyok"), ["readyok"]);
+    engine.quit();
+}
+
+#[test]
+fn eval_file() {
+    let path = std::env::temp_dir().join(format!("knix-weights-{}.txt", std::process::id()));
+    std::fs::write(&path, "bishop_pair 40/60\n").unwrap();
+
+    let mut engine = Engine::start(uci::run);
+    engine.send(&format!("setoption name EvalFile value {}", path.display()));
+    engine.send("isready");
+    assert_eq!(engine.read_until("readyok"), ["readyok"]);
+
+    std::fs::write(&path, "bishop_pair 40\n").unwrap();
+    engine.send(&format!("setoption name EvalFile value {}", path.display()));
+    assert_eq!(
+        engine.read_until("info"),
+        ["info string invalid eval file: line 1: weights have to be written as mg/eg"]
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    engine.send(&format!("setoption name EvalFile value {}", path.display()));
+    assert!(engine.read_until("info")[0].starts_with("info string cannot read"));
+    engine.quit();
+}
+
+#[test]
+fn infinite_search_waits_for_stop() {
+    let mut engine = Engine::start(uci::run);
+    engine.send("position startpos");
+    engine.send("go infinite");
+    engine.send("isready");
+    let lines = engine.read_until("readyok");
+    assert!(
+        !lines.iter().any(|l| l.starts_with("bestmove")),
+        "{lines:?}"
+    );
+
+    engine.send("stop");
+    engine.read_until("bestmove");
+    engine.quit();
+}
+
+#[test]
+fn reports_bad_input() {
+    let mut engine = Engine::start(uci::run);
+    engine.send("position fen not a fen");
+    engine.send("position startpos moves e2e5");
+    engine.send("frobnicate");
+    engine.send("isready");
+    let lines = engine.read_until("readyok");
+    assert_eq!(lines.len(), 4);
+    assert!(lines[..3].iter().all(|l| l.starts_with("info string")));
+    engine.quit();
+}
+
+#[test]
+fn plays_from_the_book() {
+    let mut engine = Engine::start(uci::run);
+    engine.send("uci");
+    let lines = engine.read_until("uciok");
+    assert!(lines.iter().any(|l| l.starts_with("option name OwnBook")));
+
+    let book = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data/book.bin");
+    engine.send(&format!("setoption name BookFile value {book}"));
+    engine.send("setoption name BookSelection value Best");
+    engine.send("position startpos moves e2e4");
+
+    // only when asked to.
+    engine.send("go depth 1");
+    let lines = engine.read_until("bestmove");
+    assert!(lines.iter().any(|l| l.starts_with("info depth")));
+
+    engine.send("setoption name OwnBook value true");
+    engine.send("go depth 20");
+    assert_eq!(engine.read_until("bestmove"), ["bestmove c7c5"]);
+
+    // out of the book, the search takes over.
+    engine.send("position startpos moves e2e4 e7e5");
+    engine.send("go depth 1");
+    let lines = engine.read_until("bestmove");
+    assert!(lines.iter().any(|l| l.starts_with("info depth")));
+    engine.quit();
+}
@@ -0,0 +1,195 @@
+use super::pipe::Engine;
+use crate::game_state::GameState;
+use crate::xboard;
+
+fn start() -> Engine {
+    let mut engine = Engine::start(xboard::run);
+    engine.send("xboard");
+    engine.send("protover 2");
+    let features = engine.read_until("feature");
+    assert!(features[0].contains("usermove=1"));
+    assert!(features[0].ends_with("done=1"));
+    engine
+}
+
+/// Reads the engine's move, checking that it is legal in `state`, and plays it there.
+fn engine_move(engine: &mut Engine, state: &mut GameState) {
+    let lines = engine.read_until("move");
+    let uci = lines.last().unwrap().strip_prefix("move ").unwrap();
+    state.perform_move(state.parse_uci_move(uci).unwrap());
+}
+
+#[test]
+fn plays_black_after_new() {
+    let mut engine = start();
+    engine.send("new");
+    engine.send("level 40 0:05 0");
+    engine.send("time 500");
+    engine.send("otim 500");
+    // as many as the searcher can run.
+    engine.send("cores 100000");
+
+    let mut state = GameState::starting();
+    engine.send("usermove e2e4");
+    state.perform_move(state.parse_uci_move("e2e4").unwrap());
+    engine_move(&mut engine, &mut state);
+
+    engine.send("usermove e2e5");
+    engine.send("ping 1");
+    assert_eq!(engine.read_until("pong"), ["Illegal move: e2e5", "pong 1"]);
+    engine.quit();
+}
+
+#[test]
+fn force_go_and_take_backs() {
+    let mut engine = start();
+    engine.send("new");
+    engine.send("force");
+    engine.send("usermove e2e4");
+    engine.send("usermove e7e5");
+    engine.send("remove");
+    engine.send("undo");
+    engine.send("ping 1");
+    // nothing to say in force mode.
+    assert_eq!(engine.read_until("pong"), ["pong 1"]);
+
+    // with everything taken back, it's white's turn again.
+    engine.send("st 1");
+    engine.send("sd 2");
+    engine.send("post");
+    engine.send("go");
+    let mut state = GameState::starting();
+    let lines = engine.read_until("move");
+    assert!(lines.len() > 1, "no thinking output in {lines:?}");
+    let uci = lines.last().unwrap().strip_prefix("move ").unwrap();
+    state.perform_move(state.parse_uci_move(uci).unwrap());
+
+    engine.send("usermove e7e5");
+    state.perform_move(state.parse_uci_move("e7e5").unwrap());
+    engine_move(&mut engine, &mut state);
+    engine.quit();
+}
+
+#[test]
+fn force_aborts_the_search() {
+    let mut engine = start();
+    engine.send("new");
+    engine.send("st 5");
+    engine.send("go");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    engine.send("force");
+    engine.send("ping 1");
+    assert_eq!(engine.read_until("pong"), ["pong 1"]);
+
+    // still the starting position, with white to move.
+    engine.send("usermove e2e4");
+    engine.send("undo");
+    engine.send("usermove d2d4");
+    engine.send("ping 2");
+    assert_eq!(engine.read_until("pong"), ["pong 2"]);
+    engine.quit();
+}
+
+#[test]
+fn setboard_and_results() {
+    let mut engine = start();
+    engine.send("new");
+    engine.send("force");
+    engine.send("setboard not a fen");
+    assert!(engine.read_until("tellusererror")[0].starts_with("tellusererror"));
+
+    engine.send("setboard 7k/8/6Q1/8/8/8/8/K5R1 w - - 0 1");
+    engine.send("usermove g6g7");
+    assert_eq!(engine.read_until("1-0"), ["1-0 {White mates}"]);
+
+    engine.send("setboard 7k/8/6Q1/8/8/8/8/K7 b - - 0 1");
+    engine.send("go");
+    assert_eq!(engine.read_until("1/2-1/2"), ["1/2-1/2 {Stalemate}"]);
+
+    engine.send("result 1/2-1/2 {Stalemate}");
+
+    // replaying a game in force mode claims nothing, until the engine is to move.
+    engine.send("setboard 7k/8/8/8/8/8/8/KR6 w - - 0 1");
+    for m in ["b1b2", "h8g8", "b2b1", "g8h8"].repeat(2) {
+        engine.send(&format!("usermove {m}"));
+    }
+    engine.send("ping 1");
+    assert_eq!(engine.read_until("pong"), ["pong 1"]);
+    engine.send("go");
+    assert_eq!(
+        engine.read_until("1/2-1/2"),
+        ["1/2-1/2 {3-fold repetition}"]
+    );
+
+    engine.send("force");
+    engine.send("frobnicate");
+    assert_eq!(
+        engine.read_until("Error"),
+        ["Error (unknown command): frobnicate"]
+    );
+    engine.quit();
+}
+
+#[test]
+fn rejects_bad_time_controls() {
+    let mut engine = start();
+    for level in [
+        "40 5 -1",
+        "40 5 nan",
+        "40 5 inf",
+        "40 5 1e300",
+        "40 999999999999999999:00 0",
+    ] {
+        engine.send(&format!("level {level}"));
+        assert_eq!(
+            engine.read_until("Error"),
+            [format!("Error (invalid level): {level}")]
+        );
+    }
+
+    // out of range clocks are ignored.
+    engine.send("time 99999999999999999999");
+    engine.send("otim 9999999999999999999");
+    engine.send("ping 1");
+    assert_eq!(engine.read_until("pong"), ["pong 1"]);
+    engine.quit();
+}
+
+#[test]
+fn plays_from_the_book() {
+    let mut engine = Engine::start(xboard::run);
+    engine.send("xboard");
+    engine.send("protover 2");
+    let features = engine.read_until("feature");
+    assert!(features[0].contains("option=\"BookFile -file \""));
+    assert!(features[0].contains("option=\"BookSelection -combo Best /// *WeightedRandom"));
+
+    let book = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data/book.bin");
+    engine.send(&format!("option BookFile={book}"));
+    engine.send("option BookSelection=Best");
+    engine.send("new");
+    engine.send("sd 2");
+    engine.send("usermove e2e4");
+    assert_eq!(engine.read_until("move"), ["move c7c5"]);
+
+    // out of the book, the search takes over.
+    let mut state = GameState::starting();
+    for m in ["e2e4", "c7c5", "g1f3"] {
+        state.perform_move(state.parse_uci_move(m).unwrap());
+    }
+    engine.send("usermove g1f3");
+    engine_move(&mut engine, &mut state);
+
+    engine.send("option BookSelection=Sometimes");
+    engine.send("option Ponder=1");
+    engine.send("ping 1");
+    assert_eq!(
+        engine.read_until("pong"),
+        [
+            "Error (unknown book move selection \"Sometimes\"): BookSelection=Sometimes",
+            "Error (unknown option): Ponder=1",
+            "pong 1"
+        ]
+    );
+    engine.quit();
+}
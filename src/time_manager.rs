@@ -0,0 +1,143 @@
+//! Deciding how long to think about a move, given the clock.
+
+use crate::game_state::GameState;
+use crate::piece::PieceColor;
+use crate::piece_move::Move;
+use crate::search::Limits;
+use std::time::{Duration, Instant};
+
+/// Kept on the clock at all times, for the time it takes the move to reach the GUI.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// A score this much worse than the previous iteration's means trouble worth thinking
+/// longer about.
+const FAILING_MARGIN: i32 = 30;
+
+/// How far the soft deadline may be pushed back, relative to where it started.
+const MAX_EXTENSION: f64 = 2.5;
+
+/// Turns the limits of `go` into two deadlines: a soft one after which no new
+/// iteration is started, and a hard one at which the search is stopped. The soft
+/// deadline moves back while the search keeps changing its mind, or its score drops.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+    /// Whether iterative deepening may stop as soon as there is nothing to decide.
+    timed: bool,
+    /// How much the soft deadline is stretched, 1 to start with.
+    extension: f64,
+    /// How often the best move changed lately, decaying every iteration.
+    instability: f64,
+    previous: Option<(Move, i32)>,
+}
+
+impl TimeManager {
+    /// Starts the clock for a search of `state` under `limits`.
+    pub fn new(state: &GameState, limits: &Limits) -> Self {
+        let (soft, hard) = time_limits(state, limits);
+        Self {
+            start: Instant::now(),
+            soft,
+            hard,
+            timed: soft.is_some(),
+            extension: 1.0,
+            instability: 0.0,
+            previous: None,
+        }
+    }
+
+    /// Not limited by time at all.
+    pub fn unlimited() -> Self {
+        Self {
+            start: Instant::now(),
+            soft: None,
+            hard: None,
+            timed: false,
+            extension: 1.0,
+            instability: 0.0,
+            previous: None,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// How long the search may go on, even in the middle of an iteration.
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+
+    /// After how long no new iteration should be started, as extended so far.
+    pub fn soft_limit(&self) -> Option<Duration> {
+        let soft = self.soft?.mul_f64(self.extension);
+        Some(self.hard.map_or(soft, |hard| soft.min(hard)))
+    }
+
+    /// Whether the hard limit has been reached.
+    pub fn out_of_time(&self) -> bool {
+        self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+
+    /// Takes the result of an iteration into account: a new best move or a dropping
+    /// score give the search more time.
+    pub fn iteration_done(&mut self, best_move: Move, score: i32) {
+        self.instability *= 0.5;
+        let mut failing = false;
+        if let Some((previous_move, previous_score)) = self.previous {
+            if previous_move != best_move {
+                self.instability += 1.0;
+            }
+            failing = score < previous_score - FAILING_MARGIN;
+        }
+        self.previous = Some((best_move, score));
+
+        let extension = 1.0 + 0.4 * self.instability + if failing { 0.5 } else { 0.0 };
+        self.extension = extension.min(MAX_EXTENSION);
+    }
+
+    /// Whether to start another iteration. Not when there is only one legal move and
+    /// the clock is running, since there is nothing to think about.
+    pub fn should_continue(&self, legal_moves: usize) -> bool {
+        if self.timed && legal_moves == 1 {
+            return false;
+        }
+        self.soft_limit().is_none_or(|soft| self.elapsed() < soft)
+    }
+}
+
+/// The soft and hard time limits, from the start of the search.
+fn time_limits(state: &GameState, limits: &Limits) -> (Option<Duration>, Option<Duration>) {
+    if limits.infinite {
+        return (None, None);
+    }
+    if let Some(move_time) = limits.move_time {
+        return (Some(move_time), Some(move_time));
+    }
+
+    let (time, increment) = match state.next_move() {
+        PieceColor::White => (limits.white_time, limits.white_increment),
+        PieceColor::Black => (limits.black_time, limits.black_increment),
+    };
+    let Some(time) = time else {
+        return (None, None);
+    };
+    let increment = increment.unwrap_or_default();
+    let moves_to_go = limits
+        .moves_to_go
+        .unwrap_or_else(|| estimate_moves_to_go(state.full_move_counter()))
+        .max(1);
+
+    let available = time.saturating_sub(MOVE_OVERHEAD);
+    let soft = (time / moves_to_go + increment * 3 / 4).min(available / 2);
+    let hard = (soft * 3).min(available * 3 / 4);
+    (Some(soft), Some(hard))
+}
+
+/// Without a time control that says so, how many more moves the game is likely to
+/// last: long at first, and at least 20 however long it goes on.
+fn estimate_moves_to_go(move_number: u16) -> u32 {
+    50u32.saturating_sub(move_number as u32 / 2).max(20)
+}
@@ -0,0 +1,273 @@
+//! A fixed-size hash table of search results, keyed by Zobrist hash, that any number
+//! of threads can read and write at once.
+
+use crate::board_position::BoardIndex;
+use crate::game_state::GameState;
+use crate::piece_move::Move;
+use crate::search::MATE_BOUND;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// How the stored score relates to the position's real score.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The real score is at least this: the search failed high.
+    Lower,
+    /// The real score is at most this: no move reached alpha.
+    Upper,
+}
+
+/// What was learned about a position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entry {
+    pub depth: u32,
+    pub bound: Bound,
+    /// Adjusted to the ply the entry was probed from.
+    pub score: i32,
+    best_move: Option<PackedMove>,
+}
+
+impl Entry {
+    /// The stored best move, if it is legal in `state`, which it is unless a different
+    /// position had the same key.
+    pub fn best_move(&self, state: &GameState) -> Option<Move> {
+        let best = self.best_move?;
+        state
+            .legal_moves_from(best.start())
+            .find(|&m| PackedMove::new(m) == best)
+    }
+}
+
+/// Entries that share a slot in the table, of which only the most valuable are kept.
+const BUCKET_SIZE: usize = 4;
+
+/// Each entry is stored as the key XORed with the data, then the data, so that an
+/// entry torn by a concurrent write no longer matches its key and is ignored.
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket([Slot; BUCKET_SIZE]);
+
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    /// Bumped every search, so that entries from earlier ones are replaced first.
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// The size used unless told otherwise.
+    pub const DEFAULT_MEGABYTES: usize = 16;
+    /// The largest size the front ends accept.
+    pub const MAX_MEGABYTES: usize = 65536;
+
+    /// A table taking up about `megabytes`, and at least one bucket.
+    pub fn new(megabytes: usize) -> Self {
+        let buckets = (megabytes.saturating_mul(1024 * 1024) / size_of::<Bucket>()).max(1);
+        Self {
+            buckets: (0..buckets).map(|_| Bucket::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    /// Forgets everything, e.g. for a new game.
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.0) {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Called at the start of every search.
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, key: u64, ply: u32) -> Option<Entry> {
+        self.bucket(key).0.iter().find_map(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            let check = slot.check.load(Ordering::Relaxed);
+            if data == 0 || check ^ data != key {
+                return None;
+            }
+
+            let mut entry = unpack(data)?;
+            entry.score = from_table(entry.score, ply);
+            Some(entry)
+        })
+    }
+
+    /// Stores what a search `ply` plies away from the root found. Replaces the entry
+    /// for the same position, or else the least valuable one in its bucket: empty,
+    /// from an old search, or shallow.
+    pub fn store(
+        &self,
+        key: u64,
+        ply: u32,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        best_move: Option<Move>,
+    ) {
+        let age = self.age.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+
+        let same = bucket.0.iter().find(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            data != 0 && slot.check.load(Ordering::Relaxed) ^ data == key
+        });
+        let slot = same.unwrap_or_else(|| {
+            bucket
+                .0
+                .iter()
+                .min_by_key(|slot| {
+                    let data = slot.data.load(Ordering::Relaxed);
+                    if data == 0 {
+                        return i32::MIN;
+                    }
+                    let staleness = age.wrapping_sub(data_age(data)) as i32;
+                    data_depth(data) as i32 - 8 * staleness
+                })
+                .expect("buckets are not empty")
+        });
+
+        let mut best_move = best_move.map(PackedMove::new);
+        if best_move.is_none() && same.is_some() {
+            // a move that was best before is still a good guess.
+            best_move = unpack(slot.data.load(Ordering::Relaxed)).and_then(|old| old.best_move);
+        }
+
+        let data = pack(
+            Entry {
+                depth,
+                bound,
+                score: to_table(score, ply),
+                best_move,
+            },
+            age,
+        );
+        slot.check.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    /// How full the table is with entries from the current search, in permille, as
+    /// estimated from its first thousand entries.
+    pub fn hash_full(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = self
+            .buckets
+            .iter()
+            .flat_map(|bucket| &bucket.0)
+            .take(1000)
+            .map(|slot| slot.data.load(Ordering::Relaxed));
+
+        let (mut used, mut total) = (0, 0);
+        for data in sample {
+            total += 1;
+            if data != 0 && data_age(data) == age {
+                used += 1;
+            }
+        }
+        used * 1000 / total
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        // maps the key onto the buckets evenly, without needing a power of two of them.
+        let index = (key as u128 * self.buckets.len() as u128) >> 64;
+        &self.buckets[index as usize]
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MEGABYTES)
+    }
+}
+
+/// A move as its start, end and promotion, which is enough to tell it apart from the
+/// other legal moves.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct PackedMove(u16);
+
+impl PackedMove {
+    fn new(m: Move) -> Self {
+        let promotion = m.promotion().map_or(0, |kind| kind as u16);
+        Self(m.start().get_pos() as u16 | (m.end().get_pos() as u16) << 6 | promotion << 12)
+    }
+
+    fn start(self) -> BoardIndex {
+        BoardIndex::new((self.0 & 0x3F) as u8).expect("six bits fit on the board")
+    }
+}
+
+// the layout of an entry's data, from the lowest bits: the best move (15 bits, then
+// one telling whether there is one), the score (16), the depth (8), the bound (8, 0
+// for an empty entry) and the age (8).
+
+fn pack(entry: Entry, age: u8) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    let best_move = entry.best_move.map_or(0, |m| m.0 as u64 | 1 << 15);
+
+    best_move
+        | (entry.score as i16 as u16 as u64) << 16
+        | (entry.depth.min(u8::MAX as u32) as u64) << 32
+        | bound << 40
+        | (age as u64) << 48
+}
+
+fn unpack(data: u64) -> Option<Entry> {
+    let bound = match (data >> 40) as u8 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    let best_move = (data & 1 << 15 != 0).then_some(PackedMove(data as u16 & 0x7FFF));
+
+    Some(Entry {
+        depth: data_depth(data),
+        bound,
+        score: (data >> 16) as u16 as i16 as i32,
+        best_move,
+    })
+}
+
+fn data_depth(data: u64) -> u32 {
+    (data >> 32) as u8 as u32
+}
+
+fn data_age(data: u64) -> u8 {
+    (data >> 48) as u8
+}
+
+/// Mate scores count the plies from the root; in the table they count from the
+/// position itself, since it can be reached at any ply.
+fn to_table(score: i32, ply: u32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: u32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
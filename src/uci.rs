@@ -0,0 +1,354 @@
+//! The Universal Chess Interface, spoken over any reader/writer pair.
+
+use crate::book::{PolyglotBook, Selection};
+use crate::eval::Weights;
+use crate::game_state::{GameState, ParseGameStateError, ParseUciMoveError};
+use crate::piece_move::Move;
+use crate::search::{Limits, Score, SearchResult, Searcher};
+#[cfg(feature = "syzygy")]
+use crate::tablebase::Tablebase;
+use crate::transposition::TranspositionTable;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Reads UCI commands from `input` and answers on `output` until `quit` or the end of
+/// the input.
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) -> io::Result<()> {
+    let mut uci = Uci::new(output);
+    for line in input.lines() {
+        if !uci.handle(&line?)? {
+            break;
+        }
+    }
+    uci.stop_search();
+    Ok(())
+}
+
+struct Uci<W> {
+    out: Arc<Mutex<W>>,
+    state: GameState,
+    /// The positions played before `state`, oldest first.
+    history: Vec<GameState>,
+    weights: Weights,
+    /// Kept from one search to the next, until `ucinewgame`.
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    /// Played from before searching, with `OwnBook`.
+    book: Option<Arc<PolyglotBook>>,
+    own_book: bool,
+    book_selection: Selection,
+    /// From `SyzygyPath`.
+    #[cfg(feature = "syzygy")]
+    tablebase: Option<Arc<Tablebase>>,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+    /// Whether the running search, if any, only ends on `stop`.
+    infinite: bool,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    fn new(output: W) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(output)),
+            state: GameState::starting(),
+            history: Vec::new(),
+            weights: Weights::default(),
+            table: Arc::default(),
+            threads: 1,
+            book: None,
+            own_book: false,
+            book_selection: Selection::default(),
+            #[cfg(feature = "syzygy")]
+            tablebase: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            search: None,
+            infinite: false,
+        }
+    }
+
+    /// Returns whether to keep reading commands.
+    fn handle(&mut self, line: &str) -> io::Result<bool> {
+        let mut tokens = line.split_whitespace();
+        let Some(command) = tokens.next() else {
+            return Ok(true);
+        };
+        let args = tokens.collect::<Vec<_>>();
+
+        match command {
+            "uci" => {
+                self.send(concat!("id name knix ", env!("CARGO_PKG_VERSION")))?;
+                self.send("id author the knix developers")?;
+                self.send(&format!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    TranspositionTable::DEFAULT_MEGABYTES,
+                    TranspositionTable::MAX_MEGABYTES
+                ))?;
+                self.send(&format!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    Searcher::MAX_THREADS
+                ))?;
+                self.send("option name EvalFile type string default <empty>")?;
+                self.send("option name OwnBook type check default false")?;
+                self.send("option name BookFile type string default <empty>")?;
+                self.send(&format!(
+                    "option name BookSelection type combo default {} var {}",
+                    Selection::default().name(),
+                    Selection::ALL.map(Selection::name).join(" var ")
+                ))?;
+                #[cfg(feature = "syzygy")]
+                self.send("option name SyzygyPath type string default <empty>")?;
+                self.send("uciok")?;
+            }
+            "isready" => self.send("readyok")?,
+            "ucinewgame" => {
+                self.finish_search();
+                self.state = GameState::starting();
+                self.history.clear();
+                self.table.clear();
+            }
+            "setoption" => self.set_option(&args)?,
+            "position" => {
+                self.finish_search();
+                if let Err(e) = self.set_position(&args) {
+                    self.send(&format!("info string {e}"))?;
+                }
+            }
+            "go" => self.go(parse_limits(&args))?,
+            "stop" => self.stop_search(),
+            "quit" => return Ok(false),
+            _ => self.send(&format!("info string unknown command {command:?}"))?,
+        }
+
+        Ok(true)
+    }
+
+    fn send(&self, line: &str) -> io::Result<()> {
+        send(&self.out, line)
+    }
+
+    /// `setoption name <name> [value <value>]`
+    fn set_option(&mut self, args: &[&str]) -> io::Result<()> {
+        let value_at = args.iter().position(|&arg| arg == "value");
+        let name = args[..value_at.unwrap_or(args.len())]
+            .iter()
+            .skip_while(|&&arg| arg == "name")
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let value = value_at
+            .map(|at| args[at + 1..].join(" "))
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "Hash" => match value.parse::<usize>() {
+                Ok(megabytes) => {
+                    self.finish_search();
+                    self.table = Arc::new(TranspositionTable::new(
+                        megabytes.clamp(1, TranspositionTable::MAX_MEGABYTES),
+                    ));
+                }
+                Err(_) => self.send(&format!("info string invalid hash size {value:?}"))?,
+            },
+            "Threads" => match value.parse::<usize>() {
+                Ok(threads) => self.threads = threads.clamp(1, Searcher::MAX_THREADS),
+                Err(_) => self.send(&format!("info string invalid thread count {value:?}"))?,
+            },
+            // evaluation weights, as written by `Weights`'s `Display`.
+            "EvalFile" if value.is_empty() || value == "<empty>" => {
+                self.weights = Weights::default()
+            }
+            "EvalFile" => match std::fs::read_to_string(&value) {
+                Ok(text) => match text.parse() {
+                    Ok(weights) => self.weights = weights,
+                    Err(e) => self.send(&format!("info string invalid eval file: {e}"))?,
+                },
+                Err(e) => self.send(&format!("info string cannot read {value:?}: {e}"))?,
+            },
+            "OwnBook" => self.own_book = value == "true",
+            // a Polyglot `.bin` book.
+            "BookFile" if value.is_empty() || value == "<empty>" => self.book = None,
+            "BookFile" => match PolyglotBook::open(&value) {
+                Ok(book) => self.book = Some(Arc::new(book)),
+                Err(e) => self.send(&format!("info string {e}"))?,
+            },
+            "BookSelection" => match value.parse() {
+                Ok(selection) => self.book_selection = selection,
+                Err(e) => self.send(&format!("info string {e}"))?,
+            },
+            // a directory of Syzygy `.rtbw` and `.rtbz` files.
+            #[cfg(feature = "syzygy")]
+            "SyzygyPath" if value.is_empty() || value == "<empty>" => self.tablebase = None,
+            #[cfg(feature = "syzygy")]
+            "SyzygyPath" => match Tablebase::open(&value) {
+                Ok(tablebase) => {
+                    self.send(&format!(
+                        "info string found {} tables of up to {} pieces",
+                        tablebase.len(),
+                        tablebase.max_pieces()
+                    ))?;
+                    self.tablebase = Some(Arc::new(tablebase));
+                }
+                Err(e) => self.send(&format!("info string {e}"))?,
+            },
+            _ => self.send(&format!("info string unknown option {name:?}"))?,
+        }
+        Ok(())
+    }
+
+    /// `position (startpos | fen <fen>) [moves <move>...]`
+    fn set_position(&mut self, args: &[&str]) -> Result<(), UciError> {
+        let moves_at = args.iter().position(|&arg| arg == "moves");
+        let (setup, moves) = match moves_at {
+            Some(at) => (&args[..at], &args[at + 1..]),
+            None => (args, &[][..]),
+        };
+
+        let mut state = match setup {
+            ["startpos"] => GameState::starting(),
+            ["fen", fen @ ..] => {
+                let mut fen = fen.join(" ");
+                // some GUIs leave out the clocks.
+                if fen.split(' ').count() == 4 {
+                    fen.push_str(" 0 1");
+                }
+                GameState::parse_from_fen(&fen)?
+            }
+            _ => return Err(UciError::InvalidPosition(args.join(" "))),
+        };
+
+        let mut history = Vec::with_capacity(moves.len());
+        for uci in moves {
+            let m = state.parse_uci_move(uci)?;
+            history.push(state);
+            state.perform_move(m);
+        }
+
+        self.state = state;
+        self.history = history;
+        Ok(())
+    }
+
+    fn go(&mut self, limits: Limits) -> io::Result<()> {
+        self.finish_search();
+        self.infinite = limits.infinite;
+
+        let mut searcher = Searcher::new();
+        searcher.set_history(&self.history);
+        searcher.set_weights(self.weights.clone());
+        searcher.set_table(Arc::clone(&self.table));
+        searcher.set_threads(self.threads);
+        searcher.set_book(
+            self.book.clone().filter(|_| self.own_book),
+            self.book_selection,
+        );
+        #[cfg(feature = "syzygy")]
+        searcher.set_tablebase(self.tablebase.clone());
+        self.stop = searcher.stop_handle();
+
+        let out = Arc::clone(&self.out);
+        searcher.on_iteration(move |info| {
+            // nobody to tell if the GUI went away.
+            let _ = send(&out, &info_line(info));
+        });
+
+        let out = Arc::clone(&self.out);
+        let stop = Arc::clone(&self.stop);
+        let state = self.state;
+
+        self.search = Some(std::thread::spawn(move || {
+            let infinite = limits.infinite;
+            let result = searcher.search(&state, limits);
+
+            // under `go infinite`, the best move may only be sent after `stop`.
+            while infinite && !stop.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            let best = result.best_move.map_or("0000".to_string(), |m| m.to_uci());
+            let _ = send(&out, &format!("bestmove {best}"));
+        }));
+        Ok(())
+    }
+
+    /// Lets the running search end by itself, unless it never would.
+    fn finish_search(&mut self) {
+        if self.infinite {
+            self.stop_search();
+        } else if let Some(search) = self.search.take() {
+            search.join().expect("search thread panicked");
+        }
+    }
+
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(search) = self.search.take() {
+            search.join().expect("search thread panicked");
+        }
+    }
+}
+
+fn send<W: Write>(out: &Mutex<W>, line: &str) -> io::Result<()> {
+    let mut out = out.lock().unwrap();
+    writeln!(out, "{line}")?;
+    out.flush()
+}
+
+/// `go [depth <n>] [nodes <n>] [movetime <ms>] [wtime <ms>] [btime <ms>] [winc <ms>]
+/// [binc <ms>] [movestogo <n>] [infinite]`, ignoring anything else.
+fn parse_limits(args: &[&str]) -> Limits {
+    let mut limits = Limits::default();
+    let mut args = args.iter();
+
+    let number = |args: &mut std::slice::Iter<&str>| args.next().and_then(|n| n.parse().ok());
+    let millis = |args: &mut std::slice::Iter<&str>| number(args).map(Duration::from_millis);
+
+    while let Some(&arg) = args.next() {
+        match arg {
+            "depth" => limits.depth = number(&mut args).map(|n| n as u32),
+            "nodes" => limits.nodes = number(&mut args),
+            "movetime" => limits.move_time = millis(&mut args),
+            "wtime" => limits.white_time = millis(&mut args),
+            "btime" => limits.black_time = millis(&mut args),
+            "winc" => limits.white_increment = millis(&mut args),
+            "binc" => limits.black_increment = millis(&mut args),
+            "movestogo" => limits.moves_to_go = number(&mut args).map(|n| n as u32),
+            "infinite" => limits.infinite = true,
+            _ => {}
+        }
+    }
+
+    limits
+}
+
+fn info_line(info: &SearchResult) -> String {
+    let score = match info.score {
+        Score::Centipawns(cp) => format!("cp {cp}"),
+        Score::Mate(moves) => format!("mate {moves}"),
+    };
+    let millis = info.time.as_millis();
+    let nps = info.nodes as u128 * 1000 / millis.max(1);
+    let pv = info
+        .pv
+        .iter()
+        .map(|m: &Move| m.to_uci())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "info depth {} score {score} nodes {} time {millis} nps {nps} hashfull {} pv {pv}",
+        info.depth, info.nodes, info.hash_full
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum UciError {
+    #[error("invalid position command: {0:?}")]
+    InvalidPosition(String),
+    #[error("invalid fen: {0}")]
+    InvalidFen(#[from] ParseGameStateError),
+    #[error("invalid move: {0}")]
+    InvalidMove(#[from] ParseUciMoveError),
+}
@@ -0,0 +1,470 @@
+//! The Chess Engine Communication Protocol, as spoken by xboard and WinBoard (protover 2).
+
+use crate::book::{PolyglotBook, Selection};
+use crate::game_state::GameState;
+use crate::outcome::{DecisiveReason, DrawReason, Outcome};
+use crate::piece::PieceColor;
+use crate::piece_move::Move;
+use crate::search::{Limits, Score, SearchResult, Searcher};
+#[cfg(feature = "syzygy")]
+use crate::tablebase::Tablebase;
+use crate::transposition::TranspositionTable;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Reads xboard commands from `input` and answers on `output` until `quit` or the end
+/// of the input.
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) -> io::Result<()> {
+    let mut xboard = XBoard::new(output);
+    for line in input.lines() {
+        if !xboard.handle(&line?)? {
+            break;
+        }
+    }
+    xboard.abort_search();
+    Ok(())
+}
+
+/// The time control set by `level` or `st`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TimeControl {
+    /// `moves` moves (or the whole game if 0) in `base`, plus `increment` per move.
+    Conventional {
+        moves: u32,
+        base: Duration,
+        increment: Duration,
+    },
+    /// A fixed time for every move.
+    PerMove(Duration),
+}
+
+struct XBoard<W> {
+    out: Arc<Mutex<W>>,
+    state: GameState,
+    /// The positions played before `state`, oldest first.
+    history: Vec<GameState>,
+    /// `None` in force mode.
+    engine_side: Option<PieceColor>,
+    time_control: Option<TimeControl>,
+    depth: Option<u32>,
+    own_time: Option<Duration>,
+    opponent_time: Option<Duration>,
+    /// Kept from one search to the next, until `new`.
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    /// Played from before searching, set with `option BookFile=<path>`.
+    book: Option<Arc<PolyglotBook>>,
+    book_selection: Selection,
+    /// From `egtpath syzygy`.
+    #[cfg(feature = "syzygy")]
+    tablebase: Option<Arc<Tablebase>>,
+    post: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    /// Keeps the search from sending its move. Set by the search itself once it has,
+    /// so that whichever comes first wins.
+    abort: Arc<AtomicBool>,
+    search: Option<JoinHandle<Option<Move>>>,
+}
+
+impl<W: Write + Send + 'static> XBoard<W> {
+    fn new(output: W) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(output)),
+            state: GameState::starting(),
+            history: Vec::new(),
+            engine_side: Some(PieceColor::Black),
+            time_control: None,
+            depth: None,
+            own_time: None,
+            opponent_time: None,
+            table: Arc::default(),
+            threads: 1,
+            book: None,
+            book_selection: Selection::default(),
+            #[cfg(feature = "syzygy")]
+            tablebase: None,
+            post: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(AtomicBool::new(false)),
+            abort: Arc::new(AtomicBool::new(false)),
+            search: None,
+        }
+    }
+
+    /// Returns whether to keep reading commands.
+    fn handle(&mut self, line: &str) -> io::Result<bool> {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+
+        match command {
+            // answered right away, even while thinking.
+            "?" => {
+                self.finish_search(true);
+                return Ok(true);
+            }
+            "post" => {
+                self.post.store(true, Ordering::Relaxed);
+                return Ok(true);
+            }
+            "nopost" => {
+                self.post.store(false, Ordering::Relaxed);
+                return Ok(true);
+            }
+            // these change the game, so whatever is being thought about no longer matters.
+            "quit" | "new" | "force" | "result" | "setboard" | "undo" | "remove" => {
+                self.abort_search()
+            }
+            _ => self.finish_search(false),
+        }
+
+        match command {
+            "" | "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" => {}
+            "protover" => self.send(&format!(
+                "feature myname=\"knix {}\" setboard=1 usermove=1 ping=1 san=0 colors=0 \
+                 sigint=0 sigterm=0 analyze=0 memory=1 smp=1{} option=\"BookFile -file \" \
+                 option=\"BookSelection -combo {}\" done=1",
+                env!("CARGO_PKG_VERSION"),
+                if cfg!(feature = "syzygy") {
+                    " egt=\"syzygy\""
+                } else {
+                    ""
+                },
+                // the default is marked with a `*`.
+                Selection::ALL
+                    .map(|selection| if selection == Selection::default() {
+                        format!("*{}", selection.name())
+                    } else {
+                        selection.name().to_string()
+                    })
+                    .join(" /// ")
+            ))?,
+            "ping" => self.send(&format!("pong {args}"))?,
+            "new" => {
+                self.state = GameState::starting();
+                self.history.clear();
+                self.engine_side = Some(PieceColor::Black);
+                self.depth = None;
+                self.table.clear();
+            }
+            "force" | "result" => self.engine_side = None,
+            "go" => {
+                self.engine_side = Some(self.state.next_move());
+                self.think()?;
+            }
+            "setboard" => match GameState::parse_from_fen(args) {
+                Ok(state) => {
+                    self.state = state;
+                    self.history.clear();
+                }
+                Err(e) => self.send(&format!("tellusererror Illegal position: {e}"))?,
+            },
+            "usermove" => self.user_move(args)?,
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "level" => match parse_level(args) {
+                Some(time_control) => self.time_control = Some(time_control),
+                None => self.send(&format!("Error (invalid level): {args}"))?,
+            },
+            "st" => match args.parse() {
+                Ok(seconds) => {
+                    self.time_control = Some(TimeControl::PerMove(Duration::from_secs(seconds)))
+                }
+                Err(_) => self.send(&format!("Error (invalid time): {args}"))?,
+            },
+            "sd" => match args.parse() {
+                Ok(depth) => self.depth = Some(depth),
+                Err(_) => self.send(&format!("Error (invalid depth): {args}"))?,
+            },
+            "memory" => match args.parse::<usize>() {
+                Ok(megabytes) => {
+                    self.table = Arc::new(TranspositionTable::new(
+                        megabytes.clamp(1, TranspositionTable::MAX_MEGABYTES),
+                    ))
+                }
+                Err(_) => self.send(&format!("Error (invalid memory): {args}"))?,
+            },
+            "cores" => match args.parse::<usize>() {
+                Ok(threads) => self.threads = threads.clamp(1, Searcher::MAX_THREADS),
+                Err(_) => self.send(&format!("Error (invalid cores): {args}"))?,
+            },
+            #[cfg(feature = "syzygy")]
+            "egtpath" => match args.split_once(' ') {
+                Some(("syzygy", path)) => match Tablebase::open(path.trim()) {
+                    Ok(tablebase) => self.tablebase = Some(Arc::new(tablebase)),
+                    Err(e) => self.send(&format!("tellusererror {e}"))?,
+                },
+                _ => self.send(&format!("Error (unsupported tablebases): {args}"))?,
+            },
+            "option" => self.set_option(args)?,
+            "time" => self.own_time = parse_centiseconds(args),
+            "otim" => self.opponent_time = parse_centiseconds(args),
+            "quit" => return Ok(false),
+            // without `usermove=1`, moves come on their own.
+            _ if self.state.parse_uci_move(command).is_ok() => self.user_move(command)?,
+            _ => self.send(&format!("Error (unknown command): {command}"))?,
+        }
+
+        Ok(true)
+    }
+
+    fn send(&self, line: &str) -> io::Result<()> {
+        send(&self.out, line)
+    }
+
+    /// `option <name>=<value>`, for the options announced in `feature`.
+    fn set_option(&mut self, args: &str) -> io::Result<()> {
+        let (name, value) = args.split_once('=').unwrap_or((args, ""));
+        match name {
+            // a Polyglot `.bin` book.
+            "BookFile" if value.is_empty() => self.book = None,
+            "BookFile" => match PolyglotBook::open(value) {
+                Ok(book) => self.book = Some(Arc::new(book)),
+                Err(e) => self.send(&format!("tellusererror {e}"))?,
+            },
+            "BookSelection" => match value.parse() {
+                Ok(selection) => self.book_selection = selection,
+                Err(e) => self.send(&format!("Error ({e}): {args}"))?,
+            },
+            _ => self.send(&format!("Error (unknown option): {args}"))?,
+        }
+        Ok(())
+    }
+
+    fn user_move(&mut self, uci: &str) -> io::Result<()> {
+        let m = match self.state.parse_uci_move(uci) {
+            Ok(m) => m,
+            Err(_) => return self.send(&format!("Illegal move: {uci}")),
+        };
+
+        self.history.push(self.state);
+        self.state.perform_move(m);
+
+        if self.engine_side == Some(self.state.next_move()) {
+            return self.think();
+        }
+        // draws are only claimed by the engine, before its move.
+        match game_result(&self.state, &self.history, false) {
+            Some(result) => self.send(&result),
+            None => Ok(()),
+        }
+    }
+
+    fn take_back(&mut self, plies: usize) {
+        for _ in 0..plies {
+            if let Some(state) = self.history.pop() {
+                self.state = state;
+            }
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        let mut limits = Limits {
+            depth: self.depth,
+            ..Limits::default()
+        };
+
+        let (white_time, black_time) = match self.state.next_move() {
+            PieceColor::White => (self.own_time, self.opponent_time),
+            PieceColor::Black => (self.opponent_time, self.own_time),
+        };
+
+        match self.time_control {
+            Some(TimeControl::PerMove(time)) => limits.move_time = Some(time),
+            Some(TimeControl::Conventional {
+                moves,
+                base,
+                increment,
+            }) => {
+                // until told otherwise by `time` and `otim`, both clocks are full.
+                limits.white_time = white_time.or(Some(base));
+                limits.black_time = black_time.or(Some(base));
+                limits.white_increment = Some(increment);
+                limits.black_increment = Some(increment);
+                if moves > 0 {
+                    // our moves so far, counting from where the game was set up.
+                    let played = (self.history.len() / 2) as u32;
+                    limits.moves_to_go = Some(moves - played % moves);
+                }
+            }
+            None => {
+                limits.white_time = white_time;
+                limits.black_time = black_time;
+            }
+        }
+
+        limits
+    }
+
+    fn think(&mut self) -> io::Result<()> {
+        if let Some(result) = game_result(&self.state, &self.history, true) {
+            return self.send(&result);
+        }
+
+        let mut searcher = Searcher::new();
+        searcher.set_history(&self.history);
+        searcher.set_table(Arc::clone(&self.table));
+        searcher.set_threads(self.threads);
+        searcher.set_book(self.book.clone(), self.book_selection);
+        #[cfg(feature = "syzygy")]
+        searcher.set_tablebase(self.tablebase.clone());
+        self.stop = searcher.stop_handle();
+
+        let out = Arc::clone(&self.out);
+        let post = Arc::clone(&self.post);
+        searcher.on_iteration(move |info| {
+            if post.load(Ordering::Relaxed) {
+                // nobody to tell if the GUI went away.
+                let _ = send(&out, &thinking_line(info));
+            }
+        });
+
+        let out = Arc::clone(&self.out);
+        let abort = Arc::new(AtomicBool::new(false));
+        self.abort = Arc::clone(&abort);
+        let state = self.state;
+        let history = self.history.clone();
+        let limits = self.limits();
+
+        self.search = Some(std::thread::spawn(move || {
+            let best = searcher.search(&state, limits).best_move?;
+            if abort.swap(true, Ordering::SeqCst) {
+                return None;
+            }
+
+            let _ = send(&out, &format!("move {}", best.to_uci()));
+
+            let mut history = history;
+            history.push(state);
+            let mut state = state;
+            state.perform_move(best);
+            if let Some(result) = game_result(&state, &history, true) {
+                let _ = send(&out, &result);
+            }
+
+            Some(best)
+        }));
+        Ok(())
+    }
+
+    /// Stops the search, if any, without playing its move unless it was already sent.
+    fn abort_search(&mut self) {
+        self.abort.store(true, Ordering::SeqCst);
+        self.finish_search(true);
+    }
+
+    /// Waits for the search, if any, and plays the move it announced.
+    fn finish_search(&mut self, stop: bool) {
+        if stop {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+
+        let Some(search) = self.search.take() else {
+            return;
+        };
+        if let Some(m) = search.join().expect("search thread panicked") {
+            self.history.push(self.state);
+            self.state.perform_move(m);
+        }
+    }
+}
+
+fn send<W: Write>(out: &Mutex<W>, line: &str) -> io::Result<()> {
+    let mut out = out.lock().unwrap();
+    writeln!(out, "{line}")?;
+    out.flush()
+}
+
+/// `level <moves> <minutes>[:<seconds>] <increment seconds>`
+fn parse_level(args: &str) -> Option<TimeControl> {
+    let [moves, base, increment] = args.split_whitespace().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+
+    let (minutes, seconds) = base.split_once(':').unwrap_or((base, "0"));
+    let base = minutes
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(60)?
+        .checked_add(seconds.parse().ok()?)?;
+
+    Some(TimeControl::Conventional {
+        moves: moves.parse().ok()?,
+        base: Duration::from_secs(base),
+        // negative, infinite or NaN increments are rejected rather than panicking.
+        increment: Duration::try_from_secs_f64(increment.parse().ok()?).ok()?,
+    })
+}
+
+fn parse_centiseconds(args: &str) -> Option<Duration> {
+    let centiseconds = args.parse::<u64>().ok()?;
+    Some(Duration::from_millis(centiseconds.checked_mul(10)?))
+}
+
+/// `<depth> <score> <time in centiseconds> <nodes> <pv>`
+fn thinking_line(info: &SearchResult) -> String {
+    let score = match info.score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => 100_000 + moves,
+        Score::Mate(moves) => -100_000 + moves,
+    };
+    let pv = info
+        .pv
+        .iter()
+        .map(|m| m.to_uci())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "{} {score} {} {} {pv}",
+        info.depth,
+        info.time.as_millis() / 10,
+        info.nodes
+    )
+}
+
+/// The result command announcing that the game is over, or claiming a draw if `claim`.
+fn game_result(state: &GameState, history: &[GameState], claim: bool) -> Option<String> {
+    let outcome = state.outcome_with_history(history)?;
+    let over = matches!(
+        outcome,
+        Outcome::Decisive { .. }
+            | Outcome::Draw {
+                reason: DrawReason::Stalemate
+            }
+    );
+    if !claim && !over {
+        return None;
+    }
+    let result = match outcome {
+        Outcome::Decisive {
+            winner: PieceColor::White,
+            ..
+        } => "1-0",
+        Outcome::Decisive {
+            winner: PieceColor::Black,
+            ..
+        } => "0-1",
+        Outcome::Draw { .. } => "1/2-1/2",
+    };
+    let reason = match outcome {
+        Outcome::Decisive {
+            winner: PieceColor::White,
+            reason: DecisiveReason::Checkmate,
+        } => "White mates",
+        Outcome::Decisive {
+            winner: PieceColor::Black,
+            reason: DecisiveReason::Checkmate,
+        } => "Black mates",
+        Outcome::Draw { reason } => match reason {
+            DrawReason::Stalemate => "Stalemate",
+            DrawReason::InsufficientMaterial => "Insufficient material",
+            DrawReason::FiftyMoveRule | DrawReason::SeventyFiveMoveRule => "50 move rule",
+            DrawReason::ThreefoldRepetition | DrawReason::FivefoldRepetition => "3-fold repetition",
+        },
+    };
+
+    Some(format!("{result} {{{reason}}}"))
+}